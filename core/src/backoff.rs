@@ -4,7 +4,10 @@
 // connection loops in recv_verify_stage, as use cases
 // expand more strategies will be added.
 
-use std::cmp::min;
+use {
+    rand::Rng,
+    std::{cmp::min, time::Duration},
+};
 
 const INITIAL_LAST_WAIT: u64 = 0;
 const INITIAL_CUR_WAIT: u64 = 100;
@@ -43,3 +46,85 @@ impl BackoffStrategy {
         self.cur_wait = INITIAL_CUR_WAIT;
     }
 }
+
+/// Doubles the wait after each attempt up to a max, each wait being randomly jittered by up to
+/// [`ExponentialBackoff::JITTER_PERCENT`] either way so that clients disconnected at the same time
+/// don't all retry in lockstep.
+#[derive(Copy, Clone, Debug)]
+pub struct ExponentialBackoff {
+    // Wait times in ms
+    max_wait: u64,
+    cur_wait: u64,
+}
+
+impl ExponentialBackoff {
+    pub const JITTER_PERCENT: u64 = 20;
+
+    pub fn new(max_wait: Duration) -> ExponentialBackoff {
+        let max_wait = max_wait.as_millis() as u64;
+        ExponentialBackoff {
+            max_wait,
+            cur_wait: min(INITIAL_CUR_WAIT, max_wait),
+        }
+    }
+
+    pub fn next_wait(&mut self) -> u64 {
+        self.next_wait_with_rng(&mut rand::thread_rng())
+    }
+
+    pub fn next_wait_with_rng<R: Rng>(&mut self, rng: &mut R) -> u64 {
+        let wait = self.cur_wait;
+        self.cur_wait = min(self.cur_wait.saturating_mul(2), self.max_wait);
+
+        let jitter = wait * Self::JITTER_PERCENT / 100;
+        wait - jitter + rng.gen_range(0, 2 * jitter + 1)
+    }
+
+    pub fn reset(&mut self) {
+        self.cur_wait = min(INITIAL_CUR_WAIT, self.max_wait);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, rand::SeedableRng, rand_chacha::ChaChaRng};
+
+    #[test]
+    fn test_exponential_backoff() {
+        let mut backoff = ExponentialBackoff::new(Duration::from_millis(1000));
+        let mut rng = ChaChaRng::seed_from_u64(7);
+        let waits: Vec<u64> = (0..6)
+            .map(|_| backoff.next_wait_with_rng(&mut rng))
+            .collect();
+
+        // doubles up to the max, within 20% either way
+        for (wait, expected) in waits.iter().zip([100, 200, 400, 800, 1000, 1000]) {
+            assert!(
+                (expected * 4 / 5..=expected * 6 / 5).contains(wait),
+                "{} not within 20% of {}",
+                wait,
+                expected
+            );
+        }
+
+        // the same rng gives the same waits
+        let mut replayed = ExponentialBackoff::new(Duration::from_millis(1000));
+        let mut rng = ChaChaRng::seed_from_u64(7);
+        assert_eq!(
+            (0..6)
+                .map(|_| replayed.next_wait_with_rng(&mut rng))
+                .collect::<Vec<_>>(),
+            waits
+        );
+
+        backoff.reset();
+        let wait = backoff.next_wait_with_rng(&mut rng);
+        assert!((80..=120).contains(&wait), "{}", wait);
+
+        // a max below the initial wait caps it too
+        let mut backoff = ExponentialBackoff::new(Duration::from_millis(50));
+        for _ in 0..3 {
+            assert!(backoff.next_wait_with_rng(&mut rng) <= 60);
+        }
+    }
+}
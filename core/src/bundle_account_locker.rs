@@ -264,14 +264,14 @@ mod tests {
             genesis_config.hash(),
         ));
 
-        let packet_bundle0 = PacketBundle {
-            batch: PacketBatch::new(vec![Packet::from_data(None, &tx0).unwrap()]),
-            uuid: Uuid::new_v4(),
-        };
-        let packet_bundle1 = PacketBundle {
-            batch: PacketBatch::new(vec![Packet::from_data(None, &tx1).unwrap()]),
-            uuid: Uuid::new_v4(),
-        };
+        let packet_bundle0 = PacketBundle::new(
+            PacketBatch::new(vec![Packet::from_data(None, &tx0).unwrap()]),
+            Uuid::new_v4(),
+        );
+        let packet_bundle1 = PacketBundle::new(
+            PacketBatch::new(vec![Packet::from_data(None, &tx1).unwrap()]),
+            Uuid::new_v4(),
+        );
 
         let mut transaction_errors = TransactionErrorMetrics::default();
 
@@ -281,6 +281,7 @@ mod tests {
             &HashSet::default(),
             &HashSet::default(),
             &mut transaction_errors,
+            None,
         )
         .expect("sanitize bundle 0");
         let sanitized_bundle1 = get_sanitized_bundle(
@@ -289,6 +290,7 @@ mod tests {
             &HashSet::default(),
             &HashSet::default(),
             &mut transaction_errors,
+            None,
         )
         .expect("sanitize bundle 1");
 
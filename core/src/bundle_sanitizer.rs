@@ -6,7 +6,7 @@ use {
     solana_perf::sigverify::verify_packet,
     solana_runtime::{bank::Bank, transaction_error_metrics::TransactionErrorMetrics},
     solana_sdk::{
-        bundle::sanitized::SanitizedBundle,
+        bundle::sanitized::{check_lookup_table_limit, SanitizedBundle},
         clock::MAX_PROCESSING_AGE,
         feature_set::FeatureSet,
         pubkey::Pubkey,
@@ -37,6 +37,8 @@ pub enum BundleSanitizerError {
     DuplicateTransaction,
     #[error("Bundle failed check_transactions")]
     FailedCheckTransactions,
+    #[error("Bundle references too many address lookup tables")]
+    TooManyLookupTables,
 }
 
 pub type BundleSanitizationResult<T> = Result<T, BundleSanitizerError>;
@@ -46,6 +48,7 @@ pub type BundleSanitizationResult<T> = Result<T, BundleSanitizerError>;
 ///  Too many packets.
 ///  Packets marked for discard (not sure why someone would do this)
 ///  One of the packets fails signature verification.
+///  References more than `max_lookup_tables` distinct address lookup tables, if set.
 ///  Mentions an account in consensus or blacklisted accounts.
 ///  Contains a packet that failed to serialize to a transaction.
 ///  Contains duplicate transactions within the same bundle.
@@ -58,6 +61,7 @@ pub fn get_sanitized_bundle(
     consensus_accounts_cache: &HashSet<Pubkey>,
     blacklisted_accounts: &HashSet<Pubkey>,
     transaction_error_metrics: &mut TransactionErrorMetrics,
+    max_lookup_tables: Option<usize>,
 ) -> BundleSanitizationResult<SanitizedBundle> {
     if bank.vote_only_bank() {
         return Err(BundleSanitizerError::VoteOnlyMode);
@@ -75,8 +79,20 @@ pub fn get_sanitized_bundle(
     }
 
     let packet_indexes = (0..packet_bundle.batch.len()).collect::<Vec<usize>>();
-    let deserialized_packets = deserialize_packets(&packet_bundle.batch, &packet_indexes);
+    let deserialized_packets: Vec<_> =
+        deserialize_packets(&packet_bundle.batch, &packet_indexes).collect();
+
+    // sanitizing loads every lookup table, so bound the number of tables before doing so
+    check_lookup_table_limit(
+        deserialized_packets
+            .iter()
+            .map(|p| &p.immutable_section().transaction().get_message().message),
+        max_lookup_tables,
+    )
+    .map_err(|_| BundleSanitizerError::TooManyLookupTables)?;
+
     let transactions: Vec<SanitizedTransaction> = deserialized_packets
+        .iter()
         .filter_map(|p| {
             let immutable_packet = p.immutable_section().clone();
             transaction_from_deserialized_packet(
@@ -123,6 +139,7 @@ pub fn get_sanitized_bundle(
     Ok(SanitizedBundle {
         transactions,
         uuid: packet_bundle.uuid,
+        packet_flags: Some(packet_bundle.batch.iter().map(|p| p.meta.flags).collect()),
     })
 }
 
@@ -155,7 +172,9 @@ fn transaction_from_deserialized_packet(
 mod tests {
     use {
         crate::{
-            bundle_sanitizer::{get_sanitized_bundle, MAX_PACKETS_PER_BUNDLE},
+            bundle_sanitizer::{
+                get_sanitized_bundle, BundleSanitizerError, MAX_PACKETS_PER_BUNDLE,
+            },
             packet_bundle::PacketBundle,
             tip_manager::{TipDistributionAccountConfig, TipManager, TipManagerConfig},
         },
@@ -169,7 +188,11 @@ mod tests {
         solana_sdk::{
             hash::Hash,
             instruction::Instruction,
-            packet::Packet,
+            message::{
+                v0::{self, MessageAddressTableLookup},
+                MessageHeader, VersionedMessage,
+            },
+            packet::{Packet, PacketFlags},
             pubkey::Pubkey,
             signature::{Keypair, Signer},
             system_transaction::transfer,
@@ -199,10 +222,7 @@ mod tests {
         ));
         let packet = Packet::from_data(None, &tx).unwrap();
 
-        let packet_bundle = PacketBundle {
-            batch: PacketBatch::new(vec![packet]),
-            uuid: Uuid::new_v4(),
-        };
+        let packet_bundle = PacketBundle::new(PacketBatch::new(vec![packet]), Uuid::new_v4());
 
         let mut transaction_errors = TransactionErrorMetrics::default();
         let sanitized_bundle = get_sanitized_bundle(
@@ -211,6 +231,7 @@ mod tests {
             &HashSet::default(),
             &HashSet::default(),
             &mut transaction_errors,
+            None,
         )
         .unwrap();
         assert_eq!(sanitized_bundle.transactions.len(), 1);
@@ -220,6 +241,48 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_get_sanitized_bundle_keeps_packet_flags() {
+        solana_logger::setup();
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(2);
+        let bank = Arc::new(Bank::new_no_wallclock_throttle_for_tests(&genesis_config));
+
+        let packets: Vec<Packet> = (0..2)
+            .map(|_| {
+                let tx = VersionedTransaction::from(transfer(
+                    &mint_keypair,
+                    &Keypair::new().pubkey(),
+                    1,
+                    genesis_config.hash(),
+                ));
+                Packet::from_data(None, &tx).unwrap()
+            })
+            .collect();
+        let mut packet_bundle = PacketBundle::new(PacketBatch::new(packets), Uuid::new_v4());
+        packet_bundle.batch[1]
+            .meta
+            .flags
+            .insert(PacketFlags::FORWARDED);
+
+        let sanitized_bundle = get_sanitized_bundle(
+            &packet_bundle,
+            &bank,
+            &HashSet::default(),
+            &HashSet::default(),
+            &mut TransactionErrorMetrics::default(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            sanitized_bundle.packet_flags,
+            Some(vec![PacketFlags::empty(), PacketFlags::FORWARDED])
+        );
+    }
+
     #[test]
     fn test_fail_to_sanitize_consensus_account() {
         solana_logger::setup();
@@ -240,10 +303,7 @@ mod tests {
         ));
         let packet = Packet::from_data(None, &tx).unwrap();
 
-        let packet_bundle = PacketBundle {
-            batch: PacketBatch::new(vec![packet]),
-            uuid: Uuid::new_v4(),
-        };
+        let packet_bundle = PacketBundle::new(PacketBatch::new(vec![packet]), Uuid::new_v4());
 
         let consensus_accounts_cache = HashSet::from([kp.pubkey()]);
         let mut transaction_errors = TransactionErrorMetrics::default();
@@ -252,7 +312,8 @@ mod tests {
             &bank,
             &consensus_accounts_cache,
             &HashSet::default(),
-            &mut transaction_errors
+            &mut transaction_errors,
+            None,
         )
         .is_err());
     }
@@ -278,10 +339,10 @@ mod tests {
         let packet = Packet::from_data(None, &tx).unwrap();
 
         // bundle with a duplicate transaction
-        let packet_bundle = PacketBundle {
-            batch: PacketBatch::new(vec![packet.clone(), packet]),
-            uuid: Uuid::new_v4(),
-        };
+        let packet_bundle = PacketBundle::new(
+            PacketBatch::new(vec![packet.clone(), packet]),
+            Uuid::new_v4(),
+        );
 
         // fails to pop because bundle it locks the same transaction twice
         let mut transaction_errors = TransactionErrorMetrics::default();
@@ -290,7 +351,8 @@ mod tests {
             &bank,
             &HashSet::default(),
             &HashSet::default(),
-            &mut transaction_errors
+            &mut transaction_errors,
+            None,
         )
         .is_err());
     }
@@ -311,10 +373,10 @@ mod tests {
             VersionedTransaction::from(transfer(&mint_keypair, &kp.pubkey(), 1, Hash::default()));
         let packet = Packet::from_data(None, &tx).unwrap();
 
-        let packet_bundle = PacketBundle {
-            batch: PacketBatch::new(vec![packet.clone(), packet]),
-            uuid: Uuid::new_v4(),
-        };
+        let packet_bundle = PacketBundle::new(
+            PacketBatch::new(vec![packet.clone(), packet]),
+            Uuid::new_v4(),
+        );
 
         // fails to pop because bundle has bad blockhash
         let mut transaction_errors = TransactionErrorMetrics::default();
@@ -323,7 +385,8 @@ mod tests {
             &bank,
             &HashSet::default(),
             &HashSet::default(),
-            &mut transaction_errors
+            &mut transaction_errors,
+            None,
         )
         .is_err());
     }
@@ -348,10 +411,8 @@ mod tests {
         ));
         let packet = Packet::from_data(None, &tx).unwrap();
 
-        let packet_bundle = PacketBundle {
-            batch: PacketBatch::new(vec![packet.clone()]),
-            uuid: Uuid::new_v4(),
-        };
+        let packet_bundle =
+            PacketBundle::new(PacketBatch::new(vec![packet.clone()]), Uuid::new_v4());
 
         let mut transaction_errors = TransactionErrorMetrics::default();
         let sanitized_bundle = get_sanitized_bundle(
@@ -360,6 +421,7 @@ mod tests {
             &HashSet::default(),
             &HashSet::default(),
             &mut transaction_errors,
+            None,
         )
         .unwrap();
 
@@ -374,17 +436,15 @@ mod tests {
         assert_eq!(results[0], Ok(()));
 
         // try to process the same one again shall fail
-        let packet_bundle = PacketBundle {
-            batch: PacketBatch::new(vec![packet]),
-            uuid: Uuid::new_v4(),
-        };
+        let packet_bundle = PacketBundle::new(PacketBatch::new(vec![packet]), Uuid::new_v4());
 
         assert!(get_sanitized_bundle(
             &packet_bundle,
             &bank,
             &HashSet::default(),
             &HashSet::default(),
-            &mut transaction_errors
+            &mut transaction_errors,
+            None,
         )
         .is_err());
     }
@@ -421,10 +481,7 @@ mod tests {
 
         let packet = Packet::from_data(None, &tx.to_versioned_transaction()).unwrap();
 
-        let packet_bundle = PacketBundle {
-            batch: PacketBatch::new(vec![packet]),
-            uuid: Uuid::new_v4(),
-        };
+        let packet_bundle = PacketBundle::new(PacketBatch::new(vec![packet]), Uuid::new_v4());
 
         // fails to pop because bundle mentions tip program
         let mut transaction_errors = TransactionErrorMetrics::default();
@@ -433,7 +490,8 @@ mod tests {
             &bank,
             &HashSet::default(),
             &HashSet::from_iter([tip_manager.tip_payment_program_id()]),
-            &mut transaction_errors
+            &mut transaction_errors,
+            None,
         )
         .is_err());
     }
@@ -456,10 +514,7 @@ mod tests {
 
         let packet = Packet::from_data(None, &tx.to_versioned_transaction()).unwrap();
 
-        let packet_bundle = PacketBundle {
-            batch: PacketBatch::new(vec![packet]),
-            uuid: Uuid::new_v4(),
-        };
+        let packet_bundle = PacketBundle::new(PacketBatch::new(vec![packet]), Uuid::new_v4());
 
         let mut transaction_errors = TransactionErrorMetrics::default();
         assert!(get_sanitized_bundle(
@@ -467,7 +522,8 @@ mod tests {
             &bank,
             &HashSet::default(),
             &HashSet::default(),
-            &mut transaction_errors
+            &mut transaction_errors,
+            None,
         )
         .is_ok());
     }
@@ -478,10 +534,7 @@ mod tests {
         let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(2);
         let bank = Arc::new(Bank::new_no_wallclock_throttle_for_tests(&genesis_config));
 
-        let packet_bundle = PacketBundle {
-            batch: PacketBatch::new(vec![]),
-            uuid: Uuid::new_v4(),
-        };
+        let packet_bundle = PacketBundle::new(PacketBatch::new(vec![]), Uuid::new_v4());
         // fails to pop because empty bundle
         let mut transaction_errors = TransactionErrorMetrics::default();
         assert!(get_sanitized_bundle(
@@ -489,7 +542,8 @@ mod tests {
             &bank,
             &HashSet::default(),
             &HashSet::default(),
-            &mut transaction_errors
+            &mut transaction_errors,
+            None,
         )
         .is_err());
     }
@@ -515,10 +569,7 @@ mod tests {
             ));
             Packet::from_data(None, &tx).unwrap()
         });
-        let packet_bundle = PacketBundle {
-            batch: PacketBatch::new(packets.collect()),
-            uuid: Uuid::new_v4(),
-        };
+        let packet_bundle = PacketBundle::new(PacketBatch::new(packets.collect()), Uuid::new_v4());
         // fails to pop because too many packets in a bundle
         let mut transaction_errors = TransactionErrorMetrics::default();
         assert!(get_sanitized_bundle(
@@ -526,7 +577,8 @@ mod tests {
             &bank,
             &HashSet::default(),
             &HashSet::default(),
-            &mut transaction_errors
+            &mut transaction_errors,
+            None,
         )
         .is_err());
     }
@@ -552,10 +604,7 @@ mod tests {
         let mut packet = Packet::from_data(None, &tx).unwrap();
         packet.meta.set_discard(true);
 
-        let packet_bundle = PacketBundle {
-            batch: PacketBatch::new(vec![packet]),
-            uuid: Uuid::new_v4(),
-        };
+        let packet_bundle = PacketBundle::new(PacketBatch::new(vec![packet]), Uuid::new_v4());
 
         // fails to pop because one of the packets is marked as discard
         let mut transaction_errors = TransactionErrorMetrics::default();
@@ -564,7 +613,8 @@ mod tests {
             &bank,
             &HashSet::default(),
             &HashSet::default(),
-            &mut transaction_errors
+            &mut transaction_errors,
+            None,
         )
         .is_err());
     }
@@ -597,18 +647,78 @@ mod tests {
 
         let packet = Packet::from_data(None, &tx).unwrap();
 
-        let packet_bundle = PacketBundle {
-            batch: PacketBatch::new(vec![packet]),
-            uuid: Uuid::new_v4(),
-        };
+        let packet_bundle = PacketBundle::new(PacketBatch::new(vec![packet]), Uuid::new_v4());
         let mut transaction_errors = TransactionErrorMetrics::default();
         assert!(get_sanitized_bundle(
             &packet_bundle,
             &bank,
             &HashSet::default(),
             &HashSet::default(),
-            &mut transaction_errors
+            &mut transaction_errors,
+            None,
         )
         .is_err());
     }
+
+    #[test]
+    fn test_get_sanitized_bundle_lookup_table_limit() {
+        solana_logger::setup();
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(2);
+        let bank = Arc::new(Bank::new_no_wallclock_throttle_for_tests(&genesis_config));
+
+        let message = v0::Message {
+            header: MessageHeader {
+                num_required_signatures: 1,
+                num_readonly_signed_accounts: 0,
+                num_readonly_unsigned_accounts: 0,
+            },
+            account_keys: vec![mint_keypair.pubkey()],
+            recent_blockhash: genesis_config.hash(),
+            instructions: vec![],
+            address_table_lookups: (0..2)
+                .map(|_| MessageAddressTableLookup {
+                    account_key: Pubkey::new_unique(),
+                    writable_indexes: vec![0],
+                    readonly_indexes: vec![],
+                })
+                .collect(),
+        };
+        let tx =
+            VersionedTransaction::try_new(VersionedMessage::V0(message), &[&mint_keypair]).unwrap();
+        let packet = Packet::from_data(None, &tx).unwrap();
+        let packet_bundle = PacketBundle::new(PacketBatch::new(vec![packet]), Uuid::new_v4());
+
+        // over the limit the tables are never loaded
+        let mut transaction_errors = TransactionErrorMetrics::default();
+        assert_eq!(
+            get_sanitized_bundle(
+                &packet_bundle,
+                &bank,
+                &HashSet::default(),
+                &HashSet::default(),
+                &mut transaction_errors,
+                Some(1),
+            )
+            .unwrap_err(),
+            BundleSanitizerError::TooManyLookupTables
+        );
+
+        // at the limit the tables are loaded, which fails since they don't exist
+        assert_eq!(
+            get_sanitized_bundle(
+                &packet_bundle,
+                &bank,
+                &HashSet::default(),
+                &HashSet::default(),
+                &mut transaction_errors,
+                Some(2),
+            )
+            .unwrap_err(),
+            BundleSanitizerError::FailedToSerializeTransaction
+        );
+    }
 }
@@ -43,7 +43,7 @@ use {
     solana_sdk::{
         bundle::{
             error::BundleExecutionError, sanitized::SanitizedBundle,
-            utils::check_bundle_lock_results,
+            utils::check_bundle_lock_results, VersionedBundle,
         },
         clock::{Slot, DEFAULT_TICKS_PER_SLOT, MAX_PROCESSING_AGE},
         hash::Hash,
@@ -198,6 +198,8 @@ impl BundleStage {
         bundle_account_locker: BundleAccountLocker,
         block_builder_fee_info: &Arc<Mutex<BlockBuilderFeeInfo>>,
         preallocated_bundle_cost: u64,
+        report_tip_outcomes: bool,
+        max_bundle_lookup_tables: Option<usize>,
     ) -> Self {
         Self::start_bundle_thread(
             cluster_info,
@@ -212,6 +214,8 @@ impl BundleStage {
             MAX_BUNDLE_RETRY_DURATION,
             block_builder_fee_info,
             preallocated_bundle_cost,
+            report_tip_outcomes,
+            max_bundle_lookup_tables,
         )
     }
 
@@ -229,6 +233,8 @@ impl BundleStage {
         max_bundle_retry_duration: Duration,
         block_builder_fee_info: &Arc<Mutex<BlockBuilderFeeInfo>>,
         preallocated_bundle_cost: u64,
+        report_tip_outcomes: bool,
+        max_bundle_lookup_tables: Option<usize>,
     ) -> Self {
         const BUNDLE_STAGE_ID: u32 = 10_000;
         let poh_recorder = poh_recorder.clone();
@@ -252,6 +258,8 @@ impl BundleStage {
                     max_bundle_retry_duration,
                     block_builder_fee_info,
                     preallocated_bundle_cost,
+                    report_tip_outcomes,
+                    max_bundle_lookup_tables,
                 );
             })
             .unwrap();
@@ -982,6 +990,8 @@ impl BundleStage {
         bundle_stage_leader_stats: &mut BundleStageLeaderStats,
         block_builder_fee_info: &Arc<Mutex<BlockBuilderFeeInfo>>,
         reserved_space: &mut BundleReservedSpace,
+        report_tip_outcomes: bool,
+        max_bundle_lookup_tables: Option<usize>,
     ) {
         let (sanitized_bundles, sanitized_bundle_elapsed) = measure!(
             unprocessed_bundles
@@ -994,6 +1004,7 @@ impl BundleStage {
                         consensus_accounts_cache,
                         blacklisted_accounts,
                         bundle_stage_leader_stats.transaction_errors(),
+                        max_bundle_lookup_tables,
                     ) {
                         Ok(sanitized_bundle) => {
                             bundle_stage_leader_stats
@@ -1037,6 +1048,12 @@ impl BundleStage {
                                 .increment_sanitize_transaction_failed_check(1);
                             None
                         }
+                        Err(BundleSanitizerError::TooManyLookupTables) => {
+                            bundle_stage_leader_stats
+                                .bundle_stage_stats()
+                                .increment_sanitize_transaction_too_many_lookup_tables(1);
+                            None
+                        }
                     }
                 })
                 .collect::<VecDeque<(PacketBundle, SanitizedBundle)>>(),
@@ -1090,11 +1107,37 @@ impl BundleStage {
             .bundle_stage_stats()
             .increment_execute_locked_bundles_elapsed_us(execute_locked_bundles_elapsed.as_us());
 
+        if report_tip_outcomes {
+            let tip_accounts = tip_manager.get_tip_accounts();
+            for (bundle_execution_result, (_, sanitized_bundle)) in
+                execution_results.iter().zip(&sanitized_bundles)
+            {
+                if let Some((tip_lamports, committed)) =
+                    Self::tip_outcome(sanitized_bundle, &tip_accounts, bundle_execution_result)
+                {
+                    datapoint_info!(
+                        "bundle_stage-tip_outcome",
+                        ("tip_lamports", tip_lamports, i64),
+                        ("committed", committed, bool),
+                    );
+                }
+            }
+        }
+
         execution_results
             .into_iter()
             .zip(sanitized_bundles.into_iter())
-            .for_each(
-                |(bundle_execution_result, (packet_bundle, _))| match bundle_execution_result {
+            .for_each(|(bundle_execution_result, (packet_bundle, _))| {
+                // bundles that are retried are timed once they reach a final result
+                if Self::is_final_result(&bundle_execution_result) {
+                    bundle_stage_leader_stats
+                        .bundle_stage_stats()
+                        .record_ingest_to_result_us(
+                            packet_bundle.received_at.elapsed().as_micros() as u64,
+                        );
+                }
+
+                match bundle_execution_result {
                     Ok(_) => {
                         bundle_stage_leader_stats
                             .bundle_stage_stats()
@@ -1124,7 +1167,9 @@ impl BundleStage {
                             .bundle_stage_stats()
                             .increment_execution_results_tip_errors(1);
                     }
-                    Err(BundleExecutionError::Shutdown) => {}
+                    // bundle stage doesn't put a deadline on execution, only simulation times out
+                    Err(BundleExecutionError::Shutdown)
+                    | Err(BundleExecutionError::TimedOut { .. }) => {}
                     Err(BundleExecutionError::MaxRetriesExceeded(_)) => {
                         bundle_stage_leader_stats
                             .bundle_stage_stats()
@@ -1135,8 +1180,40 @@ impl BundleStage {
                             .bundle_stage_stats()
                             .increment_execution_results_lock_errors(1);
                     }
-                },
-            );
+                }
+            });
+    }
+
+    /// Returns false for bundles that are retried, which don't have an outcome yet.
+    fn is_final_result(bundle_execution_result: &BundleStageResult<()>) -> bool {
+        !matches!(
+            bundle_execution_result,
+            Err(BundleExecutionError::PohMaxHeightError)
+                | Err(BundleExecutionError::ExceedsCostModel)
+        )
+    }
+
+    /// Returns the lamports a bundle paid to the `tip_accounts`, see
+    /// [`VersionedBundle::tip_lamports`], and whether it was committed, or None if it's retried.
+    fn tip_outcome(
+        sanitized_bundle: &SanitizedBundle,
+        tip_accounts: &HashSet<Pubkey>,
+        bundle_execution_result: &BundleStageResult<()>,
+    ) -> Option<(u64, bool)> {
+        if !Self::is_final_result(bundle_execution_result) {
+            return None;
+        }
+        let bundle = VersionedBundle {
+            transactions: sanitized_bundle
+                .transactions
+                .iter()
+                .map(|tx| tx.to_versioned_transaction())
+                .collect(),
+        };
+        Some((
+            bundle.tip_lamports(tip_accounts),
+            bundle_execution_result.is_ok(),
+        ))
     }
 
     /// This only needs to be done once on program initialization
@@ -1163,6 +1240,7 @@ impl BundleStage {
                 cluster_info,
             )?,
             uuid: Uuid::default(),
+            packet_flags: None,
         };
         if !initialize_tip_accounts_bundle.transactions.is_empty() {
             debug!("initialize tip account");
@@ -1245,6 +1323,7 @@ impl BundleStage {
             let change_tip_receiver_bundle = SanitizedBundle {
                 transactions: vec![change_tip_receiver_tx],
                 uuid: Uuid::default(),
+                packet_flags: None,
             };
             let locked_change_tip_receiver_bundle = bundle_account_locker
                 .prepare_locked_bundle(&change_tip_receiver_bundle, &bank_start.working_bank)
@@ -1411,6 +1490,8 @@ impl BundleStage {
         id: u32,
         block_builder_fee_info: &Arc<Mutex<BlockBuilderFeeInfo>>,
         reserved_space: &mut BundleReservedSpace,
+        report_tip_outcomes: bool,
+        max_bundle_lookup_tables: Option<usize>,
     ) {
         const DROP_BUNDLE_SLOT_OFFSET: u64 = 4;
 
@@ -1476,6 +1557,8 @@ impl BundleStage {
                 bundle_stage_leader_stats.bundle_stage_leader_stats(),
                 block_builder_fee_info,
                 reserved_space,
+                report_tip_outcomes,
+                max_bundle_lookup_tables,
             );
         }
     }
@@ -1495,6 +1578,8 @@ impl BundleStage {
         max_bundle_retry_duration: Duration,
         block_builder_fee_info: Arc<Mutex<BlockBuilderFeeInfo>>,
         preallocated_bundle_cost: u64,
+        report_tip_outcomes: bool,
+        max_bundle_lookup_tables: Option<usize>,
     ) {
         const LOOP_STATS_METRICS_PERIOD: Duration = Duration::from_secs(1);
 
@@ -1553,6 +1638,8 @@ impl BundleStage {
                         id,
                         &block_builder_fee_info,
                         &mut reserved_space,
+                        report_tip_outcomes,
+                        max_bundle_lookup_tables,
                     ),
                     "process_buffered_bundles_elapsed"
                 );
@@ -1763,6 +1850,7 @@ mod tests {
             &HashSet::default(),
             &HashSet::default(),
             bundle_stage_leader_stats.transaction_errors(),
+            None,
         )
         .unwrap();
 
@@ -1800,6 +1888,7 @@ mod tests {
                 &HashSet::default(),
                 &HashSet::default(),
                 bundle_stage_leader_stats.transaction_errors(),
+                None,
             )
             .is_err());
         }
@@ -1874,10 +1963,7 @@ mod tests {
 
         (
             genesis_config,
-            PacketBundle {
-                batch: PacketBatch::new(vec![packet]),
-                uuid: Uuid::new_v4(),
-            },
+            PacketBundle::new(PacketBatch::new(vec![packet]), Uuid::new_v4()),
         )
     }
 
@@ -1901,10 +1987,7 @@ mod tests {
         let tx = Transaction::new(&[&mint_keypair], message, genesis_config.hash());
         let packet = Packet::from_data(None, tx).unwrap();
 
-        let bundle = PacketBundle {
-            batch: PacketBatch::new(vec![packet]),
-            uuid: Uuid::new_v4(),
-        };
+        let bundle = PacketBundle::new(PacketBatch::new(vec![packet]), Uuid::new_v4());
         assert_eq!(
             test_single_bundle(genesis_config, bundle, Some(vec![LowComputeBudget])),
             Err(ExceedsCostModel)
@@ -1934,10 +2017,7 @@ mod tests {
             ),
         )
         .unwrap();
-        let bundle = PacketBundle {
-            batch: PacketBatch::new(vec![packet]),
-            uuid: Uuid::new_v4(),
-        };
+        let bundle = PacketBundle::new(PacketBatch::new(vec![packet]), Uuid::new_v4());
 
         assert_eq!(
             test_single_bundle(genesis_config, bundle, None),
@@ -1968,10 +2048,10 @@ mod tests {
             system_transaction::transfer(&kp_a, &kp_b.pubkey(), 1, genesis_config.hash()),
         )
         .unwrap();
-        let bundle = PacketBundle {
-            batch: PacketBatch::new(vec![successful_packet, failed_packet]),
-            uuid: Uuid::new_v4(),
-        };
+        let bundle = PacketBundle::new(
+            PacketBatch::new(vec![successful_packet, failed_packet]),
+            Uuid::new_v4(),
+        );
 
         assert_eq!(
             test_single_bundle(genesis_config, bundle, Some(vec![AssertZeroedCostModel])),
@@ -1994,10 +2074,7 @@ mod tests {
             system_transaction::transfer(&kp_a, &kp_b.pubkey(), 1, genesis_config.hash()),
         )
         .unwrap();
-        let bundle = PacketBundle {
-            batch: PacketBatch::new(vec![packet]),
-            uuid: Uuid::new_v4(),
-        };
+        let bundle = PacketBundle::new(PacketBatch::new(vec![packet]), Uuid::new_v4());
 
         assert_eq!(
             test_single_bundle(genesis_config, bundle, None),
@@ -2021,10 +2098,7 @@ mod tests {
             system_transaction::transfer(&mint_keypair, &kp_b.pubkey(), 1, genesis_config.hash()),
         )
         .unwrap();
-        let bundle = PacketBundle {
-            batch: PacketBatch::new(vec![packet]),
-            uuid: Uuid::new_v4(),
-        };
+        let bundle = PacketBundle::new(PacketBatch::new(vec![packet]), Uuid::new_v4());
         assert_eq!(
             test_single_bundle(genesis_config, bundle, None),
             Err(PohMaxHeightError)
@@ -2090,10 +2164,10 @@ mod tests {
         let _batch = bank.prepare_sanitized_batch(&sanitized_txs_1);
 
         // push and pop tx0
-        let bundle = PacketBundle {
-            batch: PacketBatch::new(vec![Packet::from_data(None, tx0).unwrap()]),
-            uuid: Uuid::new_v4(),
-        };
+        let bundle = PacketBundle::new(
+            PacketBatch::new(vec![Packet::from_data(None, tx0).unwrap()]),
+            Uuid::new_v4(),
+        );
         info!("test_bundle_max_retries uuid: {:?}", bundle.uuid);
 
         let sanitized_bundle = get_sanitized_bundle(
@@ -2102,6 +2176,7 @@ mod tests {
             &HashSet::default(),
             &HashSet::default(),
             bundle_stage_leader_stats.transaction_errors(),
+            None,
         )
         .unwrap();
 
@@ -2130,4 +2205,47 @@ mod tests {
         exit.store(true, Ordering::Relaxed);
         poh_service.join().unwrap();
     }
+
+    #[test]
+    fn test_tip_outcome() {
+        let tip_account = Pubkey::new_unique();
+        let tip_accounts = HashSet::from([tip_account]);
+        let payer = Keypair::new();
+        let sanitized_bundle = SanitizedBundle {
+            transactions: vec![
+                SanitizedTransaction::from_transaction_for_tests(transfer(
+                    &payer,
+                    &Pubkey::new_unique(),
+                    1_000,
+                    Hash::default(),
+                )),
+                SanitizedTransaction::from_transaction_for_tests(transfer(
+                    &payer,
+                    &tip_account,
+                    5_000,
+                    Hash::default(),
+                )),
+            ],
+            uuid: Uuid::new_v4(),
+            packet_flags: None,
+        };
+
+        assert_eq!(
+            BundleStage::tip_outcome(&sanitized_bundle, &tip_accounts, &Ok(())),
+            Some((5_000, true))
+        );
+        assert_eq!(
+            BundleStage::tip_outcome(
+                &sanitized_bundle,
+                &tip_accounts,
+                &Err(TransactionFailure(AccountNotFound))
+            ),
+            Some((5_000, false))
+        );
+        // retried bundles have no outcome yet
+        assert_eq!(
+            BundleStage::tip_outcome(&sanitized_bundle, &tip_accounts, &Err(PohMaxHeightError)),
+            None
+        );
+    }
 }
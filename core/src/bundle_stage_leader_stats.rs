@@ -81,6 +81,7 @@ pub struct BundleStageStats {
     sanitize_transaction_failed_to_serialize: u64,
     sanitize_transaction_duplicate_transaction: u64,
     sanitize_transaction_failed_check: u64,
+    sanitize_transaction_too_many_lookup_tables: u64,
     sanitize_bundle_elapsed_us: u64,
 
     locked_bundle_elapsed_us: u64,
@@ -107,6 +108,10 @@ pub struct BundleStageStats {
     execution_results_tip_errors: u64,
     execution_results_max_retries: u64,
     execution_results_lock_errors: u64,
+
+    ingest_to_result_us: u64,
+    ingest_to_result_count: u64,
+    max_ingest_to_result_us: u64,
 }
 
 impl BundleStageStats {
@@ -146,6 +151,11 @@ impl BundleStageStats {
                 self.sanitize_transaction_failed_check,
                 i64
             ),
+            (
+                "sanitize_transaction_too_many_lookup_tables",
+                self.sanitize_transaction_too_many_lookup_tables,
+                i64
+            ),
             (
                 "sanitize_bundle_elapsed_us",
                 self.sanitize_bundle_elapsed_us,
@@ -217,6 +227,9 @@ impl BundleStageStats {
                 self.execution_results_lock_errors,
                 i64
             ),
+            ("ingest_to_result_us", self.ingest_to_result_us, i64),
+            ("ingest_to_result_count", self.ingest_to_result_count, i64),
+            ("max_ingest_to_result_us", self.max_ingest_to_result_us, i64),
         );
     }
 
@@ -248,6 +261,10 @@ impl BundleStageStats {
         saturating_add_assign!(self.sanitize_transaction_failed_check, num);
     }
 
+    pub fn increment_sanitize_transaction_too_many_lookup_tables(&mut self, num: u64) {
+        saturating_add_assign!(self.sanitize_transaction_too_many_lookup_tables, num);
+    }
+
     pub fn increment_sanitize_bundle_elapsed_us(&mut self, num: u64) {
         saturating_add_assign!(self.sanitize_bundle_elapsed_us, num);
     }
@@ -323,4 +340,10 @@ impl BundleStageStats {
     pub fn increment_execution_results_lock_errors(&mut self, num: u64) {
         saturating_add_assign!(self.execution_results_lock_errors, num);
     }
+
+    pub fn record_ingest_to_result_us(&mut self, us: u64) {
+        saturating_add_assign!(self.ingest_to_result_us, us);
+        saturating_add_assign!(self.ingest_to_result_count, 1);
+        self.max_ingest_to_result_us = self.max_ingest_to_result_us.max(us);
+    }
 }
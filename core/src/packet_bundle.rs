@@ -1,7 +1,20 @@
-use {solana_perf::packet::PacketBatch, uuid::Uuid};
+use {solana_perf::packet::PacketBatch, std::time::Instant, uuid::Uuid};
 
 #[derive(Clone, Debug)]
 pub struct PacketBundle {
     pub batch: PacketBatch,
     pub uuid: Uuid,
+    /// When the bundle was received by the validator, used to measure ingest-to-result latency.
+    /// Retried bundles keep their original timestamp.
+    pub received_at: Instant,
+}
+
+impl PacketBundle {
+    pub fn new(batch: PacketBatch, uuid: Uuid) -> Self {
+        Self {
+            batch,
+            uuid,
+            received_at: Instant::now(),
+        }
+    }
 }
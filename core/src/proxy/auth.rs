@@ -1,20 +1,32 @@
 use {
-    chrono::Utc,
+    crate::proxy::challenge_signer::{ChallengeSigner, KeypairSigner},
+    chrono::{DateTime, Utc},
     jito_protos::proto::auth::{
         auth_service_client::AuthServiceClient, GenerateAuthChallengeRequest,
         GenerateAuthTokensRequest, RefreshAccessTokenRequest, Role, Token,
     },
     solana_gossip::cluster_info::ClusterInfo,
-    solana_sdk::signature::{Keypair, Signer},
+    solana_sdk::{
+        pubkey::Pubkey,
+        signature::{Signature, Signer},
+    },
     std::{
+        fmt, fs,
+        future::Future,
+        io::{self, Write},
+        path::PathBuf,
         sync::{
-            atomic::{AtomicBool, Ordering},
+            atomic::{AtomicBool, AtomicI64, Ordering},
             Arc, Mutex,
         },
-        time::Duration,
+        time::{Duration, Instant},
     },
     tokio::time::sleep,
-    tonic::{service::Interceptor, transport::Channel, Request, Status},
+    tonic::{
+        service::Interceptor,
+        transport::{Channel, Endpoint},
+        Request, Status,
+    },
 };
 
 /// Interceptor responsible for adding the access token to request headers.
@@ -42,15 +54,302 @@ impl Interceptor for AuthInterceptor {
     }
 }
 
+/// Displays a token for logging without leaking the bearer credential, showing only a short prefix
+/// of its value along with its expiry.
+pub(crate) struct RedactedToken<'a>(pub(crate) &'a Token);
+
+impl RedactedToken<'_> {
+    /// Never shows more than half of the value, so short tokens aren't given away either.
+    const MAX_PREFIX_LEN: usize = 4;
+}
+
+impl fmt::Display for RedactedToken<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let value_len = self.0.value.chars().count();
+        let prefix: String = self
+            .0
+            .value
+            .chars()
+            .take(Self::MAX_PREFIX_LEN.min(value_len / 2))
+            .collect();
+        write!(f, "{}... (", prefix)?;
+        match &self.0.expires_at_utc {
+            Some(expires_at_utc) => write!(f, "expires_at_utc: {}", expires_at_utc.seconds)?,
+            None => write!(f, "no expiry")?,
+        }
+        write!(f, ")")
+    }
+}
+
+/// Returns true if the validator identity changed since the current tokens were generated, in which
+/// case the refresh token can no longer be used and new tokens must be generated with the new key.
+fn identity_rotated(cluster_info: &ClusterInfo, authenticated_identity: Option<&Pubkey>) -> bool {
+    authenticated_identity.map_or(false, |identity| {
+        *identity != cluster_info.keypair().pubkey()
+    })
+}
+
+/// Pauses proactive token refreshes until a point in time, e.g. during a planned auth service
+/// maintenance window, coasting on the current access token instead. Tokens are still regenerated
+/// once the access token actually expires.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct RefreshFreeze {
+    /// Unix timestamp in seconds of the end of the freeze, 0 if there's none.
+    until: Arc<AtomicI64>,
+}
+
+impl RefreshFreeze {
+    pub(crate) fn freeze_until(&self, until: DateTime<Utc>) {
+        self.until.store(until.timestamp(), Ordering::Relaxed);
+    }
+
+    pub(crate) fn clear(&self) {
+        self.until.store(0, Ordering::Relaxed);
+    }
+
+    fn is_frozen(&self, now: i64) -> bool {
+        now < self.until.load(Ordering::Relaxed)
+    }
+}
+
+/// Returns the number of seconds from `now` until the token expires, -1 if it has no expiry.
+pub(crate) fn token_expiry_secs(token: &Token, now: i64) -> i64 {
+    token.expires_at_utc.as_ref().map_or(-1, |expires_at_utc| {
+        expires_at_utc.seconds.saturating_sub(now)
+    })
+}
+
+/// Expiry of a token only held by the auth loop, e.g. the refresh token, so that it can be
+/// reported elsewhere.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct TokenExpiry {
+    /// Unix timestamp in seconds of the expiry, 0 if there's none.
+    expires_at: Arc<AtomicI64>,
+}
+
+impl TokenExpiry {
+    fn set(&self, token: &Token) {
+        let expires_at = token
+            .expires_at_utc
+            .as_ref()
+            .map_or(0, |expires_at_utc| expires_at_utc.seconds);
+        self.expires_at.store(expires_at, Ordering::Relaxed);
+    }
+
+    /// Returns the number of seconds from `now` until the token expires, -1 if it has no expiry.
+    pub(crate) fn secs(&self, now: i64) -> i64 {
+        match self.expires_at.load(Ordering::Relaxed) {
+            0 => -1,
+            expires_at => expires_at.saturating_sub(now),
+        }
+    }
+}
+
+/// Keeps the auth tokens on disk so that a restarted validator can pick up where it left off instead
+/// of going through the challenge round trip again. Tokens are only reused by the identity they were
+/// issued to and while the refresh token is still valid.
+pub(crate) struct TokenCache {
+    path: PathBuf,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedTokens {
+    identity: Pubkey,
+    access_token: String,
+    access_token_expiry: i64,
+    refresh_token: String,
+    refresh_token_expiry: i64,
+}
+
+impl TokenCache {
+    pub(crate) fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// Returns the cached (access_token, refresh_token) if they were issued to `identity` and the
+    /// refresh token hasn't expired by `now`.
+    fn load(&self, identity: &Pubkey, now: i64) -> Option<(Token, Token)> {
+        let cached: CachedTokens = bincode::deserialize(&fs::read(&self.path).ok()?).ok()?;
+        if cached.identity != *identity || cached.refresh_token_expiry <= now {
+            return None;
+        }
+        let token = |value, seconds| Token {
+            value,
+            expires_at_utc: Some(prost_types::Timestamp { seconds, nanos: 0 }),
+        };
+        Some((
+            token(cached.access_token, cached.access_token_expiry),
+            token(cached.refresh_token, cached.refresh_token_expiry),
+        ))
+    }
+
+    /// Atomically replaces the cached tokens, which are only readable by the owner.
+    fn store(
+        &self,
+        identity: &Pubkey,
+        access_token: &Token,
+        refresh_token: &Token,
+    ) -> io::Result<()> {
+        let expiry = |token: &Token| {
+            token
+                .expires_at_utc
+                .as_ref()
+                .map(|ts| ts.seconds)
+                .unwrap_or_default()
+        };
+        let bytes = bincode::serialize(&CachedTokens {
+            identity: *identity,
+            access_token: access_token.value.clone(),
+            access_token_expiry: expiry(access_token),
+            refresh_token: refresh_token.value.clone(),
+            refresh_token_expiry: expiry(refresh_token),
+        })
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+        let tmp_path = self.path.with_extension("tmp");
+        // the mode only applies to new files, so don't write over a leftover with looser permissions
+        let _ = fs::remove_file(&tmp_path);
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(&tmp_path)?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, &self.path)
+    }
+
+    /// Stores the tokens, warning on failure since a stale cache only costs a new challenge.
+    fn store_or_warn(&self, identity: &Pubkey, access_token: &Token, refresh_token: &Token) {
+        if let Err(e) = self.store(identity, access_token, refresh_token) {
+            warn!(
+                "failed to cache auth tokens to {}: {}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Version of the protocol spoken with the auth service and relayer, sent along when generating
+/// tokens so that a server which can't talk to us rejects the handshake upfront.
+pub(crate) const PROTOCOL_VERSION: u32 = 1;
+
+/// Metadata header carrying the protocol version, both on requests and responses.
+const PROTOCOL_VERSION_HEADER: &str = "x-protocol-version";
+
+/// Tokens are refreshed once they're this close to expiring, unless configured otherwise.
+pub(crate) const DEFAULT_REFRESH_LOOKAHEAD: Duration = Duration::from_secs(300);
+
+/// Tokens claiming to expire further out than this are assumed to be garbage.
+const MAX_TOKEN_TTL_SECS: i64 = 30 * 24 * 60 * 60;
+
+/// Warns, at most once per interval, about tokens issued with a TTL so short that they'd be refreshed
+/// again right away, which points to a misconfigured auth service that will cause refresh storms.
+struct ShortTtlTokenWarner {
+    /// Tokens with a TTL up to this are refreshed right away.
+    refresh_lookahead_secs: i64,
+    last_warning: Option<Instant>,
+    num_short_ttl_tokens: u64,
+}
+
+impl ShortTtlTokenWarner {
+    const WARN_INTERVAL: Duration = Duration::from_secs(60);
+
+    fn new(refresh_lookahead: Duration) -> Self {
+        Self {
+            refresh_lookahead_secs: refresh_lookahead.as_secs() as i64,
+            last_warning: None,
+            num_short_ttl_tokens: 0,
+        }
+    }
+
+    /// Returns true if a warning was emitted for the token.
+    fn check(&mut self, url: &str, token_name: &str, token: &Token, now: i64) -> bool {
+        let ttl_secs = token
+            .expires_at_utc
+            .as_ref()
+            .map(|ts| ts.seconds.saturating_sub(now))
+            .unwrap_or_default();
+        if ttl_secs > self.refresh_lookahead_secs {
+            return false;
+        }
+
+        self.num_short_ttl_tokens += 1;
+        if self.last_warning.map_or(false, |last_warning| {
+            last_warning.elapsed() < Self::WARN_INTERVAL
+        }) {
+            return false;
+        }
+        self.last_warning = Some(Instant::now());
+
+        warn!(
+            "received {} {} from {} with a ttl of {}s, expected more than {}s",
+            token_name,
+            RedactedToken(token),
+            url,
+            ttl_secs,
+            self.refresh_lookahead_secs
+        );
+        datapoint_warn!(
+            "relayer_stage-short_ttl_token",
+            ("url", url, String),
+            ("token", token_name, String),
+            ("ttl_secs", ttl_secs, i64),
+            ("count", self.num_short_ttl_tokens, i64),
+        );
+        true
+    }
+}
+
+/// Settings of the auth tokens update loop, along with the handles it shares with the stage.
+pub(crate) struct AuthLoopConfig {
+    /// Access tokens are refreshed through this endpoint if set, otherwise through the auth
+    /// service which generates the tokens.
+    pub(crate) refresh_service_endpoint: Option<Endpoint>,
+    /// Signs auth challenges instead of the identity keypair held by the cluster info if set.
+    pub(crate) challenge_signer: Option<Arc<dyn ChallengeSigner>>,
+    pub(crate) refresh_freeze: RefreshFreeze,
+    /// Updated with the expiry of the refresh token held by the loop.
+    pub(crate) refresh_token_expiry: TokenExpiry,
+    pub(crate) token_cache: Option<TokenCache>,
+    /// Tokens are considered to expire this early in case our clock is behind the auth service's.
+    pub(crate) clock_skew_tolerance: Duration,
+    /// Tokens are refreshed once they're this close to expiring.
+    pub(crate) refresh_lookahead: Duration,
+    /// Requests failing transiently are retried up to this many times.
+    pub(crate) max_auth_retries: u32,
+}
+
+impl Default for AuthLoopConfig {
+    fn default() -> Self {
+        Self {
+            refresh_service_endpoint: None,
+            challenge_signer: None,
+            refresh_freeze: RefreshFreeze::default(),
+            refresh_token_expiry: TokenExpiry::default(),
+            token_cache: None,
+            clock_skew_tolerance: Duration::ZERO,
+            refresh_lookahead: DEFAULT_REFRESH_LOOKAHEAD,
+            max_auth_retries: 0,
+        }
+    }
+}
+
 /// Contains collection of utility functions responsible for generating and refreshing new tokens.
 pub(crate) mod token_manager {
-    use {super::*, crate::proxy::ProxyError, tonic::transport::Endpoint};
+    use {
+        super::*,
+        crate::proxy::ProxyError,
+        tonic::{metadata::MetadataMap, Code},
+    };
 
     /// Control loop responsible for making sure access and refresh tokens are updated.
     pub(crate) async fn auth_tokens_update_loop(
         auth_service_endpoint: Endpoint,
         access_token: Arc<Mutex<Token>>,
         cluster_info: Arc<ClusterInfo>,
+        config: AuthLoopConfig,
         exit: Arc<AtomicBool>,
     ) {
         const RETRY_INTERVAL: Duration = Duration::from_secs(5);
@@ -61,13 +360,30 @@ pub(crate) mod token_manager {
         while !exit.load(Ordering::Relaxed) {
             sleep(RETRY_INTERVAL).await;
 
-            match auth_service_endpoint.connect().await {
-                Ok(channel) => {
+            let channels = async {
+                let auth_channel = auth_service_endpoint.connect().await?;
+                let refresh_channel = match &config.refresh_service_endpoint {
+                    Some(refresh_service_endpoint) => refresh_service_endpoint.connect().await?,
+                    None => auth_channel.clone(),
+                };
+                Ok::<_, tonic::transport::Error>((auth_channel, refresh_channel))
+            };
+            match channels.await {
+                Ok((auth_channel, refresh_channel)) => {
+                    let (refresh_token, cached_identity) = load_cached_tokens(
+                        config.token_cache.as_ref(),
+                        &access_token,
+                        &cluster_info.keypair().pubkey(),
+                    );
                     if let Err(e) = auth_tokens_update_loop_helper(
-                        AuthServiceClient::new(channel),
+                        (
+                            AuthServiceClient::new(auth_channel),
+                            AuthServiceClient::new(refresh_channel),
+                        ),
                         auth_service_endpoint.uri().to_string(),
-                        (access_token.clone(), Token::default()),
+                        (access_token.clone(), refresh_token, cached_identity),
                         cluster_info.clone(),
+                        &config,
                         SLEEP_INTERVAL,
                         exit.clone(),
                     )
@@ -95,18 +411,71 @@ pub(crate) mod token_manager {
         }
     }
 
-    /// Responsible for keeping generating and refreshing the access token.
-    async fn auth_tokens_update_loop_helper(
-        mut auth_service_client: AuthServiceClient<Channel>,
+    /// Returns the cached refresh token along with the identity it was issued to, also picking up
+    /// the cached access token while it's valid, or an empty token if there's nothing usable cached
+    /// so that new tokens are generated.
+    pub(super) fn load_cached_tokens(
+        token_cache: Option<&TokenCache>,
+        access_token: &Mutex<Token>,
+        identity: &Pubkey,
+    ) -> (Token, Option<Pubkey>) {
+        let now = Utc::now().timestamp();
+        match token_cache.and_then(|token_cache| token_cache.load(identity, now)) {
+            Some((cached_access_token, cached_refresh_token)) => {
+                let access_token_expiry = cached_access_token
+                    .expires_at_utc
+                    .as_ref()
+                    .map(|ts| ts.seconds)
+                    .unwrap_or_default();
+                if access_token_expiry > now {
+                    *access_token.lock().unwrap() = cached_access_token;
+                }
+                (cached_refresh_token, Some(*identity))
+            }
+            None => (Token::default(), None),
+        }
+    }
+
+    /// Responsible for keeping generating and refreshing the access token. Tokens are refreshed
+    /// once they're within the configured lookahead of expiring.
+    /// A failed refresh is retried on the next tick as long as the current access token is still
+    /// valid, only an expired access token falls back to re-authenticating from scratch.
+    /// `authenticated_identity` is the identity the passed in tokens were issued to, if known.
+    pub(super) async fn auth_tokens_update_loop_helper(
+        (mut auth_service_client, mut refresh_service_client): (
+            AuthServiceClient<Channel>,
+            AuthServiceClient<Channel>,
+        ),
         url: String,
-        (access_token, mut refresh_token): (Arc<Mutex<Token>>, Token),
+        (access_token, mut refresh_token, mut authenticated_identity): (
+            Arc<Mutex<Token>>,
+            Token,
+            Option<Pubkey>,
+        ),
         cluster_info: Arc<ClusterInfo>,
+        config: &AuthLoopConfig,
         sleep_interval: Duration,
         exit: Arc<AtomicBool>,
     ) -> crate::proxy::Result<()> {
-        const REFRESH_WITHIN_SECS: i64 = 300;
+        let &AuthLoopConfig {
+            refresh_service_endpoint: _,
+            ref challenge_signer,
+            ref refresh_freeze,
+            refresh_token_expiry: ref shared_refresh_token_expiry,
+            ref token_cache,
+            clock_skew_tolerance,
+            refresh_lookahead,
+            max_auth_retries,
+        } = config;
+        let clock_skew_secs = clock_skew_tolerance.as_secs() as i64;
+        let refresh_lookahead_secs = refresh_lookahead.as_secs() as i64;
         let mut num_full_refreshes = 0;
         let mut num_refresh_access_token = 0;
+        let mut num_failed_refresh_access_token = 0;
+        let mut num_identity_rotations = 0;
+        let mut num_frozen_refreshes = 0;
+        let mut short_ttl_token_warner = ShortTtlTokenWarner::new(refresh_lookahead);
+        shared_refresh_token_expiry.set(&refresh_token);
 
         while !exit.load(Ordering::Relaxed) {
             let access_token_expiry: i64 = access_token
@@ -114,34 +483,79 @@ pub(crate) mod token_manager {
                 .unwrap()
                 .expires_at_utc
                 .as_ref()
-                .map(|ts| ts.seconds)
+                .map(|ts| ts.seconds.saturating_sub(clock_skew_secs))
                 .unwrap_or_default();
             let refresh_token_expiry = refresh_token
                 .expires_at_utc
                 .as_ref()
-                .map(|ts| ts.seconds)
+                .map(|ts| ts.seconds.saturating_sub(clock_skew_secs))
                 .unwrap_or_default();
 
             let now = Utc::now().timestamp();
 
+            if refresh_freeze.is_frozen(now) && access_token_expiry > now {
+                num_frozen_refreshes += 1;
+                datapoint_info!(
+                    "auth_tokens_update_loop-refresh_frozen",
+                    ("url", url, String),
+                    ("count", num_frozen_refreshes, i64),
+                );
+                sleep(sleep_interval).await;
+                continue;
+            }
+
             let should_refresh_access = access_token_expiry.checked_sub(now).ok_or_else(|| {
                 ProxyError::InvalidData("Received invalid access_token expiration".to_string())
-            })? <= REFRESH_WITHIN_SECS;
+            })? <= refresh_lookahead_secs;
             let should_generate_new_tokens =
                 refresh_token_expiry.checked_sub(now).ok_or_else(|| {
                     ProxyError::InvalidData("Received invalid refresh_token expiration".to_string())
-                })? <= REFRESH_WITHIN_SECS;
+                })? <= refresh_lookahead_secs;
+
+            let should_generate_new_tokens =
+                if identity_rotated(&cluster_info, authenticated_identity.as_ref()) {
+                    num_identity_rotations += 1;
+                    datapoint_info!(
+                        "auth_tokens_update_loop-identity_rotated",
+                        ("url", url, String),
+                        ("count", num_identity_rotations, i64),
+                    );
+                    true
+                } else {
+                    should_generate_new_tokens
+                };
 
             match (should_refresh_access, should_generate_new_tokens) {
-                // Generate new tokens if the refresh_token is close to being expired.
+                // Generate new tokens if the refresh_token is close to being expired or the identity changed.
                 (_, true) => {
                     let kp = cluster_info.keypair().clone();
+                    let challenge_signer = challenge_signer
+                        .clone()
+                        .unwrap_or_else(|| Arc::new(KeypairSigner(kp.clone())));
 
-                    let (new_access_token, new_refresh_token) =
-                        generate_auth_tokens(&mut auth_service_client, kp.as_ref()).await?;
+                    let (new_access_token, new_refresh_token) = generate_auth_tokens(
+                        &mut auth_service_client,
+                        &kp.pubkey(),
+                        challenge_signer.as_ref(),
+                        clock_skew_tolerance,
+                        max_auth_retries,
+                    )
+                    .await?;
+
+                    short_ttl_token_warner.check(&url, "access_token", &new_access_token, now);
+                    short_ttl_token_warner.check(&url, "refresh_token", &new_refresh_token, now);
 
+                    if let Some(token_cache) = token_cache {
+                        token_cache.store_or_warn(
+                            &kp.pubkey(),
+                            &new_access_token,
+                            &new_refresh_token,
+                        );
+                    }
                     *access_token.lock().unwrap() = new_access_token.clone();
+                    shared_refresh_token_expiry.set(&new_refresh_token);
                     refresh_token = new_refresh_token;
+                    authenticated_identity = Some(kp.pubkey());
 
                     num_full_refreshes += 1;
                     datapoint_info!(
@@ -152,17 +566,59 @@ pub(crate) mod token_manager {
                 }
                 // Invoke the refresh_access_token method if the access_token is close to being expired.
                 (true, _) => {
-                    let new_access_token =
-                        refresh_access_token(&mut auth_service_client, refresh_token.clone())
-                            .await?;
-                    *access_token.lock().unwrap() = new_access_token;
+                    match refresh_access_token(
+                        &mut refresh_service_client,
+                        refresh_token.clone(),
+                        clock_skew_tolerance,
+                        max_auth_retries,
+                    )
+                    .await
+                    {
+                        Ok(new_access_token) => {
+                            short_ttl_token_warner.check(
+                                &url,
+                                "access_token",
+                                &new_access_token,
+                                now,
+                            );
+                            // tokens of an unknown identity are left out rather than risk caching
+                            // them under the wrong one
+                            if let (Some(token_cache), Some(identity)) =
+                                (token_cache, authenticated_identity.as_ref())
+                            {
+                                token_cache.store_or_warn(
+                                    identity,
+                                    &new_access_token,
+                                    &refresh_token,
+                                );
+                            }
+                            *access_token.lock().unwrap() = new_access_token;
 
-                    num_refresh_access_token += 1;
-                    datapoint_info!(
-                        "auth_tokens_update_loop-refresh_access_token",
-                        ("url", url, String),
-                        ("count", num_refresh_access_token, i64),
-                    );
+                            num_refresh_access_token += 1;
+                            datapoint_info!(
+                                "auth_tokens_update_loop-refresh_access_token",
+                                ("url", url, String),
+                                ("count", num_refresh_access_token, i64),
+                            );
+                        }
+                        // The current access token can still be used, so retry later instead of
+                        // tearing everything down over a transient auth service error.
+                        Err(e) if access_token_expiry > now => {
+                            num_failed_refresh_access_token += 1;
+                            warn!(
+                                "failed to refresh access token from {}, retrying: {}",
+                                url, e
+                            );
+                            datapoint_warn!(
+                                "auth_tokens_update_loop-refresh_access_token_failed",
+                                ("url", url, String),
+                                ("count", num_failed_refresh_access_token, i64),
+                                ("error", e.to_string(), String),
+                            );
+                            sleep(sleep_interval).await;
+                        }
+                        Err(e) => return Err(e),
+                    }
                 }
                 // Sleep and do nothing if neither token is close to expired,
                 (false, false) => sleep(sleep_interval).await,
@@ -172,11 +628,72 @@ pub(crate) mod token_manager {
         Ok(())
     }
 
-    /// Invokes the refresh_access_token gRPC method.
+    /// Returns true for errors an immediate retry may get past, such as the auth service
+    /// restarting, as opposed to it rejecting the request.
+    fn is_transient(e: &ProxyError) -> bool {
+        matches!(
+            e,
+            ProxyError::GrpcError(status)
+                if matches!(status.code(), Code::Unavailable | Code::DeadlineExceeded)
+        )
+    }
+
+    /// Runs `request`, retrying it up to `max_retries` times if it fails transiently. The wait
+    /// between attempts doubles from 100ms.
+    async fn retry_transient<T, F>(
+        max_retries: u32,
+        mut request: impl FnMut() -> F,
+    ) -> crate::proxy::Result<T>
+    where
+        F: Future<Output = crate::proxy::Result<T>>,
+    {
+        const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+
+        let mut backoff = INITIAL_BACKOFF;
+        let mut num_retries = 0;
+        loop {
+            match request().await {
+                Err(e) if num_retries < max_retries && is_transient(&e) => {
+                    num_retries += 1;
+                    warn!(
+                        "auth request failed, retrying in {:?} ({}/{}): {}",
+                        backoff, num_retries, max_retries, e
+                    );
+                    sleep(backoff).await;
+                    backoff = backoff.saturating_mul(2);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Invokes the refresh_access_token gRPC method, retrying transient failures.
     /// Returns a new access_token.
     async fn refresh_access_token(
         auth_service_client: &mut AuthServiceClient<Channel>,
         refresh_token: Token,
+        clock_skew_tolerance: Duration,
+        max_retries: u32,
+    ) -> crate::proxy::Result<Token> {
+        retry_transient(max_retries, move || {
+            let mut auth_service_client = auth_service_client.clone();
+            let refresh_token = refresh_token.clone();
+            async move {
+                refresh_access_token_once(
+                    &mut auth_service_client,
+                    refresh_token,
+                    clock_skew_tolerance,
+                )
+                .await
+            }
+        })
+        .await
+    }
+
+    async fn refresh_access_token_once(
+        auth_service_client: &mut AuthServiceClient<Channel>,
+        refresh_token: Token,
+        clock_skew_tolerance: Duration,
     ) -> crate::proxy::Result<Token> {
         match auth_service_client
             .refresh_access_token(RefreshAccessTokenRequest {
@@ -184,16 +701,44 @@ pub(crate) mod token_manager {
             })
             .await
         {
-            Ok(resp) => get_validated_token(resp.into_inner().access_token),
+            Ok(resp) => get_validated_token(resp.into_inner().access_token, clock_skew_tolerance),
             Err(e) => Err(ProxyError::GrpcError(e)),
         }
     }
 
-    /// Generates an auth challenge then generates and returns validated auth tokens.
-    async fn generate_auth_tokens(
+    /// Generates an auth challenge then generates and returns validated auth tokens, retrying
+    /// transient failures.
+    pub(super) async fn generate_auth_tokens(
         auth_service_client: &mut AuthServiceClient<Channel>,
-        // used to sign challenges
-        keypair: &Keypair,
+        identity: &Pubkey,
+        // used to sign challenges, with the identity
+        challenge_signer: &dyn ChallengeSigner,
+        clock_skew_tolerance: Duration,
+        max_retries: u32,
+    ) -> crate::proxy::Result<(
+        Token, /* access_token */
+        Token, /* refresh_token */
+    )> {
+        retry_transient(max_retries, move || {
+            let mut auth_service_client = auth_service_client.clone();
+            async move {
+                generate_auth_tokens_once(
+                    &mut auth_service_client,
+                    identity,
+                    challenge_signer,
+                    clock_skew_tolerance,
+                )
+                .await
+            }
+        })
+        .await
+    }
+
+    async fn generate_auth_tokens_once(
+        auth_service_client: &mut AuthServiceClient<Channel>,
+        identity: &Pubkey,
+        challenge_signer: &dyn ChallengeSigner,
+        clock_skew_tolerance: Duration,
     ) -> crate::proxy::Result<(
         Token, /* access_token */
         Token, /* refresh_token */
@@ -201,47 +746,1086 @@ pub(crate) mod token_manager {
         let challenge_response = auth_service_client
             .generate_auth_challenge(GenerateAuthChallengeRequest {
                 role: Role::Validator as i32,
-                pubkey: keypair.pubkey().as_ref().to_vec(),
+                pubkey: identity.as_ref().to_vec(),
             })
             .await?;
 
-        let formatted_challenge = format!(
-            "{}-{}",
-            keypair.pubkey(),
-            challenge_response.into_inner().challenge
-        );
-        let signed_challenge = keypair
-            .sign_message(formatted_challenge.as_bytes())
-            .as_ref()
-            .to_vec();
+        let formatted_challenge =
+            format!("{}-{}", identity, challenge_response.into_inner().challenge);
+        let signed_challenge = challenge_signer.sign_message(formatted_challenge.as_bytes());
+        verify_signed_challenge(identity, &formatted_challenge, &signed_challenge)?;
 
+        let mut request = Request::new(GenerateAuthTokensRequest {
+            challenge: formatted_challenge,
+            client_pubkey: identity.as_ref().to_vec(),
+            signed_challenge: signed_challenge.as_ref().to_vec(),
+        });
+        request.metadata_mut().insert(
+            PROTOCOL_VERSION_HEADER,
+            PROTOCOL_VERSION.to_string().parse().unwrap(),
+        );
         let auth_tokens = auth_service_client
-            .generate_auth_tokens(GenerateAuthTokensRequest {
-                challenge: formatted_challenge,
-                client_pubkey: keypair.pubkey().as_ref().to_vec(),
-                signed_challenge,
-            })
-            .await?;
+            .generate_auth_tokens(request)
+            .await
+            .map_err(|status| match status.code() {
+                Code::FailedPrecondition => ProxyError::IncompatibleRelayer(format!(
+                    "protocol version {} rejected: {}",
+                    PROTOCOL_VERSION,
+                    status.message()
+                )),
+                _ => ProxyError::GrpcError(status),
+            })?;
+        check_protocol_version(auth_tokens.metadata())?;
 
         let inner = auth_tokens.into_inner();
-        let access_token = get_validated_token(inner.access_token)?;
-        let refresh_token = get_validated_token(inner.refresh_token)?;
+        let access_token = get_validated_token(inner.access_token, clock_skew_tolerance)?;
+        let refresh_token = get_validated_token(inner.refresh_token, clock_skew_tolerance)?;
 
         Ok((access_token, refresh_token))
     }
 
+    /// Servers may report the protocol version they speak, in which case it must match ours.
+    /// Servers that don't report one are assumed to be compatible.
+    pub(super) fn check_protocol_version(metadata: &MetadataMap) -> crate::proxy::Result<()> {
+        let server_version = match metadata.get(PROTOCOL_VERSION_HEADER) {
+            Some(server_version) => server_version,
+            None => return Ok(()),
+        };
+        match server_version
+            .to_str()
+            .ok()
+            .and_then(|v| v.parse::<u32>().ok())
+        {
+            Some(server_version) if server_version == PROTOCOL_VERSION => Ok(()),
+            _ => Err(ProxyError::IncompatibleRelayer(format!(
+                "server speaks protocol version {:?}, expected {}",
+                server_version, PROTOCOL_VERSION
+            ))),
+        }
+    }
+
+    /// Checks the signed challenge locally so that a signing problem surfaces as a clear error
+    /// instead of an opaque rejection from the auth service.
+    pub(super) fn verify_signed_challenge(
+        pubkey: &Pubkey,
+        formatted_challenge: &str,
+        signed_challenge: &Signature,
+    ) -> crate::proxy::Result<()> {
+        if signed_challenge.verify(pubkey.as_ref(), formatted_challenge.as_bytes()) {
+            Ok(())
+        } else {
+            Err(ProxyError::SigningError(format!(
+                "signed challenge doesn't verify against {}",
+                pubkey
+            )))
+        }
+    }
+
     /// An invalid token is one where any of its fields are None or the token itself is None.
     /// Performs the necessary validations on the auth tokens before returning,
     /// i.e. it is safe to call .unwrap() on the token fields from the call-site.
-    fn get_validated_token(maybe_token: Option<Token>) -> crate::proxy::Result<Token> {
+    /// Tokens whose expiry isn't positive or is implausibly far in the future are rejected, allowing
+    /// for our clock being up to `clock_skew_tolerance` behind the auth service's.
+    pub(super) fn get_validated_token(
+        maybe_token: Option<Token>,
+        clock_skew_tolerance: Duration,
+    ) -> crate::proxy::Result<Token> {
         let token = maybe_token
             .ok_or_else(|| ProxyError::InvalidData("received a null token".to_string()))?;
-        if token.expires_at_utc.is_none() {
-            Err(ProxyError::InvalidData(
-                "expires_at_utc field is null".to_string(),
-            ))
-        } else {
-            Ok(token)
+        let expires_at_utc = token
+            .expires_at_utc
+            .as_ref()
+            .ok_or_else(|| ProxyError::InvalidData("expires_at_utc field is null".to_string()))?;
+
+        if expires_at_utc.seconds <= 0 {
+            return Err(ProxyError::BadAuthenticationToken(format!(
+                "expires_at_utc {} is not positive",
+                expires_at_utc.seconds
+            )));
+        }
+        let max_expiry = Utc::now()
+            .timestamp()
+            .saturating_add(MAX_TOKEN_TTL_SECS)
+            .saturating_add(clock_skew_tolerance.as_secs() as i64);
+        if expires_at_utc.seconds > max_expiry {
+            return Err(ProxyError::BadAuthenticationToken(format!(
+                "expires_at_utc {} is more than {}s in the future",
+                expires_at_utc.seconds, MAX_TOKEN_TTL_SECS
+            )));
+        }
+
+        Ok(token)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::proxy::ProxyError,
+        jito_protos::proto::auth::{
+            auth_service_server::{AuthService, AuthServiceServer},
+            GenerateAuthChallengeResponse, GenerateAuthTokensResponse, RefreshAccessTokenResponse,
+        },
+        prost_types::Timestamp,
+        solana_gossip::contact_info::ContactInfo,
+        solana_sdk::signature::Keypair,
+        solana_streamer::socket::SocketAddrSpace,
+        std::sync::atomic::AtomicU64,
+        tokio::net::TcpListener,
+        tonic::{
+            transport::{Endpoint, Server},
+            Code, Response,
+        },
+    };
+
+    const REFRESH_WITHIN_SECS: i64 = DEFAULT_REFRESH_LOOKAHEAD.as_secs() as i64;
+
+    /// Issues access tokens that are about to expire so that they're always due for a refresh.
+    #[derive(Default)]
+    struct MockAuthService {
+        fail_refresh: bool,
+        /// Status codes the first token generations fail with, one per call.
+        generate_failures: Vec<Code>,
+        /// Rejects token requests from clients speaking an older protocol version.
+        min_protocol_version: u32,
+        /// Protocol version reported alongside generated tokens, if any.
+        protocol_version: Option<u32>,
+        num_generate_auth_tokens: Arc<AtomicU64>,
+        num_refresh_access_token: Arc<AtomicU64>,
+    }
+
+    #[tonic::async_trait]
+    impl AuthService for MockAuthService {
+        async fn generate_auth_challenge(
+            &self,
+            _request: Request<GenerateAuthChallengeRequest>,
+        ) -> Result<Response<GenerateAuthChallengeResponse>, Status> {
+            Ok(Response::new(GenerateAuthChallengeResponse {
+                challenge: "challenge".to_string(),
+            }))
+        }
+
+        async fn generate_auth_tokens(
+            &self,
+            request: Request<GenerateAuthTokensRequest>,
+        ) -> Result<Response<GenerateAuthTokensResponse>, Status> {
+            let num_calls = self
+                .num_generate_auth_tokens
+                .fetch_add(1, Ordering::Relaxed);
+            if let Some(code) = self.generate_failures.get(num_calls as usize) {
+                return Err(Status::new(*code, "auth service failure"));
+            }
+            let client_version = request
+                .metadata()
+                .get(PROTOCOL_VERSION_HEADER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse::<u32>().ok())
+                .unwrap_or_default();
+            if client_version < self.min_protocol_version {
+                return Err(Status::failed_precondition(format!(
+                    "protocol version {} is too old",
+                    client_version
+                )));
+            }
+            let now = Utc::now().timestamp();
+            let mut response = Response::new(GenerateAuthTokensResponse {
+                access_token: Some(token_expiring_at(now + REFRESH_WITHIN_SECS / 2)),
+                refresh_token: Some(token_expiring_at(now + 24 * 60 * 60)),
+            });
+            if let Some(protocol_version) = self.protocol_version {
+                response.metadata_mut().insert(
+                    PROTOCOL_VERSION_HEADER,
+                    protocol_version.to_string().parse().unwrap(),
+                );
+            }
+            Ok(response)
+        }
+
+        async fn refresh_access_token(
+            &self,
+            _request: Request<RefreshAccessTokenRequest>,
+        ) -> Result<Response<RefreshAccessTokenResponse>, Status> {
+            self.num_refresh_access_token
+                .fetch_add(1, Ordering::Relaxed);
+            if self.fail_refresh {
+                return Err(Status::unavailable("auth service hiccup"));
+            }
+            Ok(Response::new(RefreshAccessTokenResponse {
+                access_token: Some(token_expiring_at(
+                    Utc::now().timestamp() + REFRESH_WITHIN_SECS / 2,
+                )),
+            }))
+        }
+    }
+
+    /// Serves the auth service on a local port, returning its url.
+    async fn spawn_auth_service(auth_service: MockAuthService) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let incoming = Box::pin(futures::stream::unfold(listener, |listener| async move {
+            let stream = listener.accept().await.map(|(stream, _)| stream);
+            Some((stream, listener))
+        }));
+        tokio::spawn(
+            Server::builder()
+                .add_service(AuthServiceServer::new(auth_service))
+                .serve_with_incoming(incoming),
+        );
+        format!("http://{}", addr)
+    }
+
+    async fn auth_service_client(url: &str) -> AuthServiceClient<Channel> {
+        AuthServiceClient::new(
+            Endpoint::from_shared(url.to_string())
+                .unwrap()
+                .connect()
+                .await
+                .unwrap(),
+        )
+    }
+
+    fn new_cluster_info() -> Arc<ClusterInfo> {
+        let keypair = Arc::new(Keypair::new());
+        Arc::new(ClusterInfo::new(
+            ContactInfo::new_localhost(&keypair.pubkey(), 0),
+            keypair,
+            SocketAddrSpace::Unspecified,
+        ))
+    }
+
+    fn token_expiring_at(seconds: i64) -> Token {
+        Token {
+            value: "token".to_string(),
+            expires_at_utc: Some(Timestamp { seconds, nanos: 0 }),
+        }
+    }
+
+    #[test]
+    fn test_identity_rotated_between_auth_and_refresh() {
+        let keypair = Arc::new(Keypair::new());
+        let cluster_info = ClusterInfo::new(
+            ContactInfo::new_localhost(&keypair.pubkey(), 0),
+            keypair,
+            SocketAddrSpace::Unspecified,
+        );
+
+        // nothing has been authenticated yet
+        assert!(!identity_rotated(&cluster_info, None));
+
+        let authenticated_identity = cluster_info.keypair().pubkey();
+        assert!(!identity_rotated(
+            &cluster_info,
+            Some(&authenticated_identity)
+        ));
+
+        cluster_info.set_keypair(Arc::new(Keypair::new()));
+        assert!(identity_rotated(
+            &cluster_info,
+            Some(&authenticated_identity)
+        ));
+    }
+
+    #[test]
+    fn test_short_ttl_token_warning_is_throttled() {
+        let now = Utc::now().timestamp();
+        let mut warner = ShortTtlTokenWarner::new(DEFAULT_REFRESH_LOOKAHEAD);
+
+        // a healthy token doesn't warn
+        assert!(!warner.check(
+            "http://auth",
+            "access_token",
+            &token_expiring_at(now + 30 * 60),
+            now
+        ));
+
+        let short_ttl_token = token_expiring_at(now + 10);
+        assert!(warner.check("http://auth", "access_token", &short_ttl_token, now));
+        // subsequent short ttl tokens within the interval are counted but not logged
+        assert!(!warner.check("http://auth", "access_token", &short_ttl_token, now));
+        assert!(!warner.check("http://auth", "refresh_token", &short_ttl_token, now));
+        assert_eq!(warner.num_short_ttl_tokens, 3);
+
+        // warns again once the interval elapsed
+        warner.last_warning =
+            Some(Instant::now() - ShortTtlTokenWarner::WARN_INTERVAL - Duration::from_secs(1));
+        assert!(warner.check("http://auth", "access_token", &short_ttl_token, now));
+    }
+
+    #[test]
+    fn test_failed_refresh_keeps_valid_access_token() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let auth_service = MockAuthService {
+                fail_refresh: true,
+                ..MockAuthService::default()
+            };
+            let num_generate_auth_tokens = auth_service.num_generate_auth_tokens.clone();
+            let num_refresh_access_token = auth_service.num_refresh_access_token.clone();
+            let url = spawn_auth_service(auth_service).await;
+
+            let access_token = Arc::new(Mutex::new(Token::default()));
+            let exit = Arc::new(AtomicBool::new(false));
+
+            let stop = async {
+                while num_refresh_access_token.load(Ordering::Relaxed) < 3 {
+                    sleep(Duration::from_millis(10)).await;
+                }
+                exit.store(true, Ordering::Relaxed);
+            };
+            let (result, _) = tokio::join!(
+                token_manager::auth_tokens_update_loop_helper(
+                    (
+                        auth_service_client(&url).await,
+                        auth_service_client(&url).await,
+                    ),
+                    url.clone(),
+                    (access_token.clone(), Token::default(), None),
+                    new_cluster_info(),
+                    &AuthLoopConfig::default(),
+                    Duration::from_millis(10),
+                    exit.clone(),
+                ),
+                stop,
+            );
+
+            // the refresh kept failing but the loop kept going with the still valid access token
+            // instead of bailing out to re-authenticate
+            assert!(result.is_ok());
+            assert_eq!(num_generate_auth_tokens.load(Ordering::Relaxed), 1);
+            assert!(num_refresh_access_token.load(Ordering::Relaxed) >= 3);
+            assert_eq!(access_token.lock().unwrap().value, "token");
+        });
+    }
+
+    #[test]
+    fn test_refresh_skipped_during_freeze() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let auth_service = MockAuthService::default();
+            let num_generate_auth_tokens = auth_service.num_generate_auth_tokens.clone();
+            let num_refresh_access_token = auth_service.num_refresh_access_token.clone();
+            let url = spawn_auth_service(auth_service).await;
+
+            let access_token = Arc::new(Mutex::new(Token::default()));
+            let refresh_freeze = RefreshFreeze::default();
+            refresh_freeze.freeze_until(Utc::now() + chrono::Duration::seconds(2));
+            let exit = Arc::new(AtomicBool::new(false));
+
+            let stop = async {
+                // the access token is due for a refresh, but the freeze holds it off
+                sleep(Duration::from_millis(500)).await;
+                assert_eq!(num_generate_auth_tokens.load(Ordering::Relaxed), 1);
+                assert_eq!(num_refresh_access_token.load(Ordering::Relaxed), 0);
+
+                // and refreshes resume once the window is over
+                while num_refresh_access_token.load(Ordering::Relaxed) == 0 {
+                    sleep(Duration::from_millis(10)).await;
+                }
+                exit.store(true, Ordering::Relaxed);
+            };
+            let (result, _) = tokio::join!(
+                token_manager::auth_tokens_update_loop_helper(
+                    (
+                        auth_service_client(&url).await,
+                        auth_service_client(&url).await,
+                    ),
+                    url.clone(),
+                    (access_token.clone(), Token::default(), None),
+                    new_cluster_info(),
+                    &AuthLoopConfig {
+                        refresh_freeze,
+                        ..AuthLoopConfig::default()
+                    },
+                    Duration::from_millis(10),
+                    exit.clone(),
+                ),
+                stop,
+            );
+            assert!(result.is_ok());
+        });
+    }
+
+    #[test]
+    fn test_expiring_access_token_only_refreshed() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let auth_service = MockAuthService::default();
+            let num_generate_auth_tokens = auth_service.num_generate_auth_tokens.clone();
+            let num_refresh_access_token = auth_service.num_refresh_access_token.clone();
+            let url = spawn_auth_service(auth_service).await;
+
+            let now = Utc::now().timestamp();
+            let access_token = Arc::new(Mutex::new(token_expiring_at(now + 1)));
+            let refresh_token = token_expiring_at(now + 24 * 60 * 60);
+            let exit = Arc::new(AtomicBool::new(false));
+
+            let stop = async {
+                while num_refresh_access_token.load(Ordering::Relaxed) == 0 {
+                    sleep(Duration::from_millis(10)).await;
+                }
+                exit.store(true, Ordering::Relaxed);
+            };
+            let (result, _) = tokio::join!(
+                token_manager::auth_tokens_update_loop_helper(
+                    (
+                        auth_service_client(&url).await,
+                        auth_service_client(&url).await,
+                    ),
+                    url.clone(),
+                    (access_token.clone(), refresh_token, None),
+                    new_cluster_info(),
+                    &AuthLoopConfig::default(),
+                    Duration::from_millis(10),
+                    exit.clone(),
+                ),
+                stop,
+            );
+
+            // the refresh token is still good, so the access token is refreshed without going
+            // through the full challenge flow
+            assert!(result.is_ok());
+            assert_eq!(num_generate_auth_tokens.load(Ordering::Relaxed), 0);
+            assert!(
+                access_token
+                    .lock()
+                    .unwrap()
+                    .expires_at_utc
+                    .as_ref()
+                    .unwrap()
+                    .seconds
+                    > now + 1
+            );
+        });
+    }
+
+    #[test]
+    fn test_refresh_uses_refresh_service() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let auth_service = MockAuthService::default();
+            let num_generate_auth_tokens = auth_service.num_generate_auth_tokens.clone();
+            let num_auth_service_refreshes = auth_service.num_refresh_access_token.clone();
+            let auth_url = spawn_auth_service(auth_service).await;
+
+            let refresh_service = MockAuthService::default();
+            let num_refresh_service_refreshes = refresh_service.num_refresh_access_token.clone();
+            let refresh_url = spawn_auth_service(refresh_service).await;
+
+            let exit = Arc::new(AtomicBool::new(false));
+            let stop = async {
+                while num_refresh_service_refreshes.load(Ordering::Relaxed) < 3 {
+                    sleep(Duration::from_millis(10)).await;
+                }
+                exit.store(true, Ordering::Relaxed);
+            };
+            let (result, _) = tokio::join!(
+                token_manager::auth_tokens_update_loop_helper(
+                    (
+                        auth_service_client(&auth_url).await,
+                        auth_service_client(&refresh_url).await,
+                    ),
+                    auth_url.clone(),
+                    (
+                        Arc::new(Mutex::new(Token::default())),
+                        Token::default(),
+                        None,
+                    ),
+                    new_cluster_info(),
+                    &AuthLoopConfig::default(),
+                    Duration::from_millis(10),
+                    exit.clone(),
+                ),
+                stop,
+            );
+
+            // tokens are generated by the auth service but only ever refreshed by the refresh one
+            assert!(result.is_ok());
+            assert_eq!(num_generate_auth_tokens.load(Ordering::Relaxed), 1);
+            assert_eq!(num_auth_service_refreshes.load(Ordering::Relaxed), 0);
+        });
+    }
+
+    #[test]
+    fn test_incompatible_protocol_version() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let generate_tokens = |auth_service: MockAuthService| async move {
+                let url = spawn_auth_service(auth_service).await;
+                token_manager::auth_tokens_update_loop_helper(
+                    (
+                        auth_service_client(&url).await,
+                        auth_service_client(&url).await,
+                    ),
+                    url.clone(),
+                    (
+                        Arc::new(Mutex::new(Token::default())),
+                        Token::default(),
+                        None,
+                    ),
+                    new_cluster_info(),
+                    &AuthLoopConfig::default(),
+                    Duration::from_millis(10),
+                    Arc::new(AtomicBool::new(false)),
+                )
+                .await
+            };
+
+            // the server rejects our version as too old
+            let result = generate_tokens(MockAuthService {
+                min_protocol_version: PROTOCOL_VERSION + 1,
+                ..MockAuthService::default()
+            })
+            .await;
+            assert!(matches!(result, Err(ProxyError::IncompatibleRelayer(_))));
+
+            // the server accepts the handshake but speaks another version
+            let result = generate_tokens(MockAuthService {
+                min_protocol_version: PROTOCOL_VERSION,
+                protocol_version: Some(PROTOCOL_VERSION + 1),
+                ..MockAuthService::default()
+            })
+            .await;
+            assert!(matches!(result, Err(ProxyError::IncompatibleRelayer(_))));
+        });
+    }
+
+    #[test]
+    fn test_transient_auth_failures_retried() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let generate_tokens = |generate_failures: Vec<Code>, max_retries: u32| async move {
+                let auth_service = MockAuthService {
+                    generate_failures,
+                    ..MockAuthService::default()
+                };
+                let num_generate_auth_tokens = auth_service.num_generate_auth_tokens.clone();
+                let url = spawn_auth_service(auth_service).await;
+                let keypair = Arc::new(Keypair::new());
+                let result = token_manager::generate_auth_tokens(
+                    &mut auth_service_client(&url).await,
+                    &keypair.pubkey(),
+                    &KeypairSigner(keypair.clone()),
+                    Duration::ZERO,
+                    max_retries,
+                )
+                .await;
+                (result, num_generate_auth_tokens.load(Ordering::Relaxed))
+            };
+
+            // fails twice then succeeds
+            let (result, num_calls) =
+                generate_tokens(vec![Code::Unavailable, Code::DeadlineExceeded], 2).await;
+            assert!(result.is_ok());
+            assert_eq!(num_calls, 3);
+
+            // runs out of retries
+            let (result, num_calls) =
+                generate_tokens(vec![Code::Unavailable, Code::DeadlineExceeded], 1).await;
+            assert!(matches!(
+                result,
+                Err(ProxyError::GrpcError(status)) if status.code() == Code::DeadlineExceeded
+            ));
+            assert_eq!(num_calls, 2);
+
+            // rejections aren't retried
+            for code in [Code::Unauthenticated, Code::PermissionDenied] {
+                let (result, num_calls) = generate_tokens(vec![code], 3).await;
+                assert!(matches!(
+                    result,
+                    Err(ProxyError::GrpcError(status)) if status.code() == code
+                ));
+                assert_eq!(num_calls, 1);
+            }
+        });
+    }
+
+    /// Signs with a keypair, recording the messages it was asked to sign.
+    struct RecordingSigner {
+        keypair: Keypair,
+        messages: Mutex<Vec<Vec<u8>>>,
+    }
+
+    impl ChallengeSigner for RecordingSigner {
+        fn sign_message(&self, message: &[u8]) -> Signature {
+            self.messages.lock().unwrap().push(message.to_vec());
+            self.keypair.sign_message(message)
+        }
+    }
+
+    #[test]
+    fn test_challenge_signer() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let url = spawn_auth_service(MockAuthService::default()).await;
+            let signer = RecordingSigner {
+                keypair: Keypair::new(),
+                messages: Mutex::default(),
+            };
+            let identity = signer.keypair.pubkey();
+
+            assert!(token_manager::generate_auth_tokens(
+                &mut auth_service_client(&url).await,
+                &identity,
+                &signer,
+                Duration::ZERO,
+                0,
+            )
+            .await
+            .is_ok());
+            assert_eq!(
+                *signer.messages.lock().unwrap(),
+                vec![format!("{}-challenge", identity).into_bytes()]
+            );
+
+            // the signer doesn't hold the identity
+            assert!(matches!(
+                token_manager::generate_auth_tokens(
+                    &mut auth_service_client(&url).await,
+                    &Pubkey::new_unique(),
+                    &signer,
+                    Duration::ZERO,
+                    0,
+                )
+                .await,
+                Err(ProxyError::SigningError(_))
+            ));
+        });
+    }
+
+    #[test]
+    fn test_check_protocol_version() {
+        let mut metadata = tonic::metadata::MetadataMap::new();
+        assert!(token_manager::check_protocol_version(&metadata).is_ok());
+
+        metadata.insert(
+            PROTOCOL_VERSION_HEADER,
+            PROTOCOL_VERSION.to_string().parse().unwrap(),
+        );
+        assert!(token_manager::check_protocol_version(&metadata).is_ok());
+
+        metadata.insert(PROTOCOL_VERSION_HEADER, "garbage".parse().unwrap());
+        assert!(matches!(
+            token_manager::check_protocol_version(&metadata),
+            Err(ProxyError::IncompatibleRelayer(_))
+        ));
+    }
+
+    #[test]
+    fn test_redacted_token() {
+        let token = Token {
+            value: "eyJhbGciOiJFZERTQSJ9.secret".to_string(),
+            expires_at_utc: Some(Timestamp {
+                seconds: 1_700_000_000,
+                nanos: 0,
+            }),
+        };
+        let redacted = RedactedToken(&token).to_string();
+        assert_eq!(redacted, "eyJh... (expires_at_utc: 1700000000)");
+        assert!(!redacted.contains(&token.value));
+
+        // short values only ever show up to half of their characters
+        for value in ["", "a", "ab", "abcdef"] {
+            let token = Token {
+                value: value.to_string(),
+                expires_at_utc: None,
+            };
+            let redacted = RedactedToken(&token).to_string();
+            assert!(value.is_empty() || !redacted.contains(value));
+            assert!(redacted.ends_with("... (no expiry)"));
+        }
+    }
+
+    #[test]
+    fn test_token_cache_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let token_cache = TokenCache::new(dir.path().join("tokens"));
+        let identity = Pubkey::new_unique();
+        let now = Utc::now().timestamp();
+        assert_eq!(token_cache.load(&identity, now), None);
+
+        let access_token = token_expiring_at(now + 60);
+        let refresh_token = token_expiring_at(now + 60 * 60);
+        token_cache
+            .store(&identity, &access_token, &refresh_token)
+            .unwrap();
+        assert_eq!(
+            token_cache.load(&identity, now),
+            Some((access_token, refresh_token))
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&token_cache.path)
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // tokens of another identity or with an expired refresh token aren't reused
+        assert_eq!(token_cache.load(&Pubkey::new_unique(), now), None);
+        assert_eq!(token_cache.load(&identity, now + 60 * 60), None);
+
+        // a restart picks both tokens up
+        let access_token = Mutex::new(Token::default());
+        let (refresh_token, cached_identity) =
+            token_manager::load_cached_tokens(Some(&token_cache), &access_token, &identity);
+        assert_eq!(refresh_token.expires_at_utc.unwrap().seconds, now + 60 * 60);
+        assert_eq!(cached_identity, Some(identity));
+        assert_eq!(
+            access_token
+                .lock()
+                .unwrap()
+                .expires_at_utc
+                .as_ref()
+                .unwrap()
+                .seconds,
+            now + 60
+        );
+        token_cache
+            .store(
+                &identity,
+                &token_expiring_at(now - 1),
+                &token_expiring_at(now + 60 * 60),
+            )
+            .unwrap();
+        // an expired access token is left out, the refresh token is still picked up
+        let access_token = Mutex::new(Token::default());
+        token_manager::load_cached_tokens(Some(&token_cache), &access_token, &identity);
+        assert_eq!(*access_token.lock().unwrap(), Token::default());
+
+        // nothing usable cached for another identity
+        assert_eq!(
+            token_manager::load_cached_tokens(
+                Some(&token_cache),
+                &access_token,
+                &Pubkey::new_unique()
+            ),
+            (Token::default(), None)
+        );
+    }
+
+    #[test]
+    fn test_tokens_written_back_to_cache() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let dir = tempfile::tempdir().unwrap();
+            let token_cache_path = dir.path().join("tokens");
+            let token_cache = TokenCache::new(token_cache_path.clone());
+            let cluster_info = new_cluster_info();
+            let identity = cluster_info.keypair().pubkey();
+
+            // runs the loop until the access token got refreshed, returning the number of times
+            // tokens were generated
+            let run = |tokens: (Arc<Mutex<Token>>, Token, Option<Pubkey>)| {
+                let token_cache_path = &token_cache_path;
+                let cluster_info = cluster_info.clone();
+                async move {
+                    let auth_service = MockAuthService::default();
+                    let num_generate_auth_tokens = auth_service.num_generate_auth_tokens.clone();
+                    let num_refresh_access_token = auth_service.num_refresh_access_token.clone();
+                    let url = spawn_auth_service(auth_service).await;
+                    let exit = Arc::new(AtomicBool::new(false));
+                    let stop = async {
+                        while num_refresh_access_token.load(Ordering::Relaxed) == 0 {
+                            sleep(Duration::from_millis(10)).await;
+                        }
+                        exit.store(true, Ordering::Relaxed);
+                    };
+                    let (result, _) = tokio::join!(
+                        token_manager::auth_tokens_update_loop_helper(
+                            (
+                                auth_service_client(&url).await,
+                                auth_service_client(&url).await,
+                            ),
+                            url.clone(),
+                            tokens,
+                            cluster_info,
+                            &AuthLoopConfig {
+                                token_cache: Some(TokenCache::new(token_cache_path.clone())),
+                                ..AuthLoopConfig::default()
+                            },
+                            Duration::from_millis(10),
+                            exit.clone(),
+                        ),
+                        stop,
+                    );
+                    assert!(result.is_ok());
+                    num_generate_auth_tokens.load(Ordering::Relaxed)
+                }
+            };
+
+            // nothing cached yet, so tokens are generated then written back along with refreshes
+            let access_token = Arc::new(Mutex::new(Token::default()));
+            assert_eq!(run((access_token.clone(), Token::default(), None)).await, 1);
+            let (cached_access_token, _) =
+                token_cache.load(&identity, Utc::now().timestamp()).unwrap();
+            assert_eq!(cached_access_token, *access_token.lock().unwrap());
+
+            // a restart picks the cached tokens up instead of generating new ones
+            let access_token = Arc::new(Mutex::new(Token::default()));
+            let (refresh_token, cached_identity) =
+                token_manager::load_cached_tokens(Some(&token_cache), &access_token, &identity);
+            assert_eq!(*access_token.lock().unwrap(), cached_access_token);
+            assert_eq!(run((access_token, refresh_token, cached_identity)).await, 0);
+
+            // the identity rotates after the cached tokens were picked up, so they're replaced
+            // by tokens generated for the new identity instead of being refreshed and cached
+            // under it
+            let access_token = Arc::new(Mutex::new(Token::default()));
+            let (refresh_token, cached_identity) =
+                token_manager::load_cached_tokens(Some(&token_cache), &access_token, &identity);
+            cluster_info.set_keypair(Arc::new(Keypair::new()));
+            let new_identity = cluster_info.keypair().pubkey();
+            assert_eq!(
+                run((access_token.clone(), refresh_token, cached_identity)).await,
+                1
+            );
+            let now = Utc::now().timestamp();
+            assert_eq!(token_cache.load(&identity, now), None);
+            let (cached_access_token, _) = token_cache.load(&new_identity, now).unwrap();
+            assert_eq!(cached_access_token, *access_token.lock().unwrap());
+        });
+    }
+
+    #[test]
+    fn test_verify_signed_challenge() {
+        let keypair = Keypair::new();
+        let formatted_challenge = format!("{}-challenge", keypair.pubkey());
+        let signed_challenge = keypair.sign_message(formatted_challenge.as_bytes());
+        assert!(token_manager::verify_signed_challenge(
+            &keypair.pubkey(),
+            &formatted_challenge,
+            &signed_challenge
+        )
+        .is_ok());
+
+        let mut corrupted = signed_challenge.as_ref().to_vec();
+        corrupted[0] ^= 0xff;
+        assert!(matches!(
+            token_manager::verify_signed_challenge(
+                &keypair.pubkey(),
+                &formatted_challenge,
+                &Signature::new(&corrupted)
+            ),
+            Err(ProxyError::SigningError(_))
+        ));
+
+        // signed by another keypair
+        assert!(matches!(
+            token_manager::verify_signed_challenge(
+                &keypair.pubkey(),
+                &formatted_challenge,
+                &Keypair::new().sign_message(formatted_challenge.as_bytes())
+            ),
+            Err(ProxyError::SigningError(_))
+        ));
+    }
+
+    #[test]
+    fn test_get_validated_token() {
+        let now = Utc::now().timestamp();
+        let token = token_expiring_at(now + 30 * 60);
+        assert_eq!(
+            token_manager::get_validated_token(Some(token.clone()), Duration::ZERO).unwrap(),
+            token
+        );
+
+        assert!(matches!(
+            token_manager::get_validated_token(None, Duration::ZERO),
+            Err(ProxyError::InvalidData(_))
+        ));
+        assert!(matches!(
+            token_manager::get_validated_token(
+                Some(Token {
+                    value: "token".to_string(),
+                    expires_at_utc: None,
+                }),
+                Duration::ZERO
+            ),
+            Err(ProxyError::InvalidData(_))
+        ));
+    }
+
+    #[test]
+    fn test_clock_skew_tolerance() {
+        // our clock is behind the auth service's, so its tokens look further out than they are
+        let now = Utc::now().timestamp();
+        let token = token_expiring_at(now + MAX_TOKEN_TTL_SECS + 30);
+        assert!(matches!(
+            token_manager::get_validated_token(Some(token.clone()), Duration::ZERO),
+            Err(ProxyError::BadAuthenticationToken(_))
+        ));
+        assert_eq!(
+            token_manager::get_validated_token(Some(token.clone()), Duration::from_secs(60))
+                .unwrap(),
+            token
+        );
+
+        // our clock is ahead of the auth service's, so its tokens expire sooner than they look
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let num_refreshes = |clock_skew_tolerance: Duration| {
+            rt.block_on(async {
+                let auth_service = MockAuthService::default();
+                let num_generate_auth_tokens = auth_service.num_generate_auth_tokens.clone();
+                let num_refresh_access_token = auth_service.num_refresh_access_token.clone();
+                let url = spawn_auth_service(auth_service).await;
+
+                let now = Utc::now().timestamp();
+                let exit = Arc::new(AtomicBool::new(false));
+                let stop = async {
+                    sleep(Duration::from_millis(200)).await;
+                    exit.store(true, Ordering::Relaxed);
+                };
+                let (result, _) = tokio::join!(
+                    token_manager::auth_tokens_update_loop_helper(
+                        (
+                            auth_service_client(&url).await,
+                            auth_service_client(&url).await,
+                        ),
+                        url.clone(),
+                        (
+                            Arc::new(Mutex::new(token_expiring_at(
+                                now + REFRESH_WITHIN_SECS + 30
+                            ))),
+                            token_expiring_at(now + 24 * 60 * 60),
+                            None,
+                        ),
+                        new_cluster_info(),
+                        &AuthLoopConfig {
+                            clock_skew_tolerance,
+                            ..AuthLoopConfig::default()
+                        },
+                        Duration::from_millis(10),
+                        exit.clone(),
+                    ),
+                    stop,
+                );
+                assert!(result.is_ok());
+                assert_eq!(num_generate_auth_tokens.load(Ordering::Relaxed), 0);
+                num_refresh_access_token.load(Ordering::Relaxed)
+            })
+        };
+        assert_eq!(num_refreshes(Duration::ZERO), 0);
+        assert!(num_refreshes(Duration::from_secs(60)) > 0);
+    }
+
+    #[test]
+    fn test_token_expiry_secs() {
+        let now = 1_700_000_000;
+        let token = token_expiring_at(now + 90);
+        assert_eq!(token_expiry_secs(&token, now), 90);
+        assert_eq!(token_expiry_secs(&token, now + 100), -10);
+        assert_eq!(token_expiry_secs(&Token::default(), now), -1);
+
+        // set by the auth loop, read through a clone by the stage
+        let refresh_token_expiry = TokenExpiry::default();
+        let reported_expiry = refresh_token_expiry.clone();
+        assert_eq!(reported_expiry.secs(now), -1);
+        refresh_token_expiry.set(&token);
+        assert_eq!(reported_expiry.secs(now), 90);
+        refresh_token_expiry.set(&Token::default());
+        assert_eq!(reported_expiry.secs(now), -1);
+    }
+
+    #[test]
+    fn test_refresh_lookahead() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let num_refreshes = |refresh_lookahead: Duration| {
+            rt.block_on(async {
+                let auth_service = MockAuthService::default();
+                let num_refresh_access_token = auth_service.num_refresh_access_token.clone();
+                let url = spawn_auth_service(auth_service).await;
+
+                let now = Utc::now().timestamp();
+                let exit = Arc::new(AtomicBool::new(false));
+                let stop = async {
+                    sleep(Duration::from_millis(200)).await;
+                    exit.store(true, Ordering::Relaxed);
+                };
+                let (result, _) = tokio::join!(
+                    token_manager::auth_tokens_update_loop_helper(
+                        (
+                            auth_service_client(&url).await,
+                            auth_service_client(&url).await,
+                        ),
+                        url.clone(),
+                        (
+                            Arc::new(Mutex::new(token_expiring_at(now + 120))),
+                            token_expiring_at(now + 24 * 60 * 60),
+                            None,
+                        ),
+                        new_cluster_info(),
+                        &AuthLoopConfig {
+                            refresh_lookahead,
+                            ..AuthLoopConfig::default()
+                        },
+                        Duration::from_millis(10),
+                        exit.clone(),
+                    ),
+                    stop,
+                );
+                assert!(result.is_ok());
+                num_refresh_access_token.load(Ordering::Relaxed)
+            })
+        };
+        // the access token is within the default lookahead, but not within a shorter one
+        assert!(num_refreshes(DEFAULT_REFRESH_LOOKAHEAD) > 0);
+        assert_eq!(num_refreshes(Duration::from_secs(60)), 0);
+    }
+
+    #[test]
+    fn test_get_validated_token_rejects_implausible_expiry() {
+        for seconds in [-1, 0, i64::MIN] {
+            assert!(matches!(
+                token_manager::get_validated_token(
+                    Some(token_expiring_at(seconds)),
+                    Duration::ZERO
+                ),
+                Err(ProxyError::BadAuthenticationToken(_))
+            ));
+        }
+
+        let now = Utc::now().timestamp();
+        for seconds in [now + 2 * MAX_TOKEN_TTL_SECS, i64::MAX] {
+            assert!(matches!(
+                token_manager::get_validated_token(
+                    Some(token_expiring_at(seconds)),
+                    Duration::ZERO
+                ),
+                Err(ProxyError::BadAuthenticationToken(_))
+            ));
         }
     }
 }
@@ -10,12 +10,13 @@ use {
         packet_bundle::PacketBundle,
         proto_packet_to_packet,
         proxy::{
-            auth::{token_manager::auth_tokens_update_loop, AuthInterceptor},
+            auth::{token_manager::auth_tokens_update_loop, AuthInterceptor, AuthLoopConfig},
+            bundle_acceptance_policy::{BundleAcceptancePolicy, RejectReason},
             ProxyError,
         },
         sigverify::SigverifyTracerPacketStats,
     },
-    crossbeam_channel::Sender,
+    crossbeam_channel::{bounded, Sender},
     jito_protos::proto::{
         auth::Token,
         block_engine::{
@@ -25,7 +26,12 @@ use {
     },
     solana_gossip::cluster_info::ClusterInfo,
     solana_perf::packet::PacketBatch,
-    solana_sdk::{pubkey::Pubkey, saturating_add_assign},
+    solana_sdk::{
+        bundle::VersionedBundle,
+        pubkey::Pubkey,
+        saturating_add_assign,
+        transaction::{TransactionVersion, VersionedTransaction},
+    },
     std::{
         str::FromStr,
         sync::{
@@ -33,7 +39,7 @@ use {
             Arc, Mutex,
         },
         thread::{self, Builder, JoinHandle},
-        time::Duration,
+        time::{Duration, Instant, SystemTime},
     },
     tokio::time::{interval, sleep},
     tonic::{
@@ -48,22 +54,147 @@ use {
 struct BlockEngineStageStats {
     num_bundles: u64,
     num_bundle_packets: u64,
+    num_legacy_txs: u64,
+    num_v0_txs: u64,
     num_packets: u64,
     num_empty_packets: u64,
+    num_shed_loose_packets: u64,
+    num_stream_recycles: u64,
+    num_rejected_too_many_transactions: u64,
+    num_rejected_vote_transaction: u64,
+    num_rejected_program_not_allowed: u64,
+    /// Bundles forwarded or rejected, with the time from receipt to that decision.
+    num_processed_bundles: u64,
+    total_bundle_processing_ns: u64,
+    max_bundle_processing_ns: u64,
 }
 
 impl BlockEngineStageStats {
+    fn increment_rejected(&mut self, reason: &RejectReason) {
+        match reason {
+            RejectReason::TooManyTransactions { .. } => {
+                saturating_add_assign!(self.num_rejected_too_many_transactions, 1);
+            }
+            RejectReason::ContainsVoteTransaction => {
+                saturating_add_assign!(self.num_rejected_vote_transaction, 1);
+            }
+            RejectReason::ProgramNotAllowed(_) => {
+                saturating_add_assign!(self.num_rejected_program_not_allowed, 1);
+            }
+        }
+    }
+
+    fn record_bundle_processing(&mut self, received_at: Instant) {
+        let processing_ns = received_at.elapsed().as_nanos() as u64;
+        saturating_add_assign!(self.num_processed_bundles, 1);
+        saturating_add_assign!(self.total_bundle_processing_ns, processing_ns);
+        self.max_bundle_processing_ns = self.max_bundle_processing_ns.max(processing_ns);
+    }
+
     pub(crate) fn report(&self) {
+        if self.num_processed_bundles > 0 {
+            datapoint_info!(
+                "block_engine_stage-bundle_processing_ns",
+                ("count", self.num_processed_bundles, i64),
+                (
+                    "mean",
+                    self.total_bundle_processing_ns / self.num_processed_bundles,
+                    i64
+                ),
+                ("max", self.max_bundle_processing_ns, i64),
+            );
+        }
         datapoint_info!(
             "block_engine_stage-stats",
             ("num_bundles", self.num_bundles, i64),
             ("num_bundle_packets", self.num_bundle_packets, i64),
+            ("num_legacy_txs", self.num_legacy_txs, i64),
+            ("num_v0_txs", self.num_v0_txs, i64),
             ("num_packets", self.num_packets, i64),
-            ("num_empty_packets", self.num_empty_packets, i64)
+            ("num_empty_packets", self.num_empty_packets, i64),
+            ("num_shed_loose_packets", self.num_shed_loose_packets, i64),
+            ("num_stream_recycles", self.num_stream_recycles, i64),
+            (
+                "num_rejected_too_many_transactions",
+                self.num_rejected_too_many_transactions,
+                i64
+            ),
+            (
+                "num_rejected_vote_transaction",
+                self.num_rejected_vote_transaction,
+                i64
+            ),
+            (
+                "num_rejected_program_not_allowed",
+                self.num_rejected_program_not_allowed,
+                i64
+            ),
         );
     }
 }
 
+/// Why a bundle or loose packets from the block engine, or packets from the relayer, were dropped
+/// before reaching the bundle stage or sigverify.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DropReason {
+    /// The bundle was missing, its uuid couldn't be parsed or one of its packets couldn't be
+    /// deserialized into a transaction.
+    Malformed,
+    /// The acceptance policy rejected the bundle.
+    Rejected(RejectReason),
+    /// A batch of loose packets was shed under backpressure, see [`LoosePacketShedding`].
+    Shed { num_packets: usize },
+    /// A batch of trusted relayer packets was dropped because the channel past sigverify was full,
+    /// see [`Drop`](crate::proxy::relayer_stage::TrustedPacketBackpressure::Drop) backpressure.
+    Backpressure { num_packets: usize },
+}
+
+/// A bundle or batch of packets dropped by the block engine or relayer stage, see
+/// [`BlockEngineConfig::drop_event_tx`] and
+/// [`RelayerConfig::drop_event_tx`](crate::proxy::relayer_stage::RelayerConfig::drop_event_tx).
+#[derive(Debug, Clone)]
+pub struct DropEvent {
+    /// The uuid the block engine assigned to the bundle, as received. Empty for loose packets.
+    pub bundle_uuid: String,
+    pub reason: DropReason,
+    pub timestamp: SystemTime,
+}
+
+impl DropEvent {
+    /// Capacity of the channel [`Self::spawn_logger`] logs from, events past it being missed.
+    const LOGGER_CAPACITY: usize = 10_000;
+
+    /// Spawns a thread logging every drop event sent on the returned channel, for operators
+    /// without a consumer of their own. The thread exits once every sender is dropped.
+    pub fn spawn_logger() -> Sender<DropEvent> {
+        let (drop_event_tx, drop_event_rx) = bounded(Self::LOGGER_CAPACITY);
+        Builder::new()
+            .name("drop-event-logger".into())
+            .spawn(move || {
+                for drop_event in drop_event_rx {
+                    if drop_event.bundle_uuid.is_empty() {
+                        info!("dropped packets: {:?}", drop_event.reason);
+                    } else {
+                        info!(
+                            "dropped bundle {}: {:?}",
+                            drop_event.bundle_uuid, drop_event.reason
+                        );
+                    }
+                }
+            })
+            .unwrap();
+        drop_event_tx
+    }
+}
+
+/// Sheds loose packets, streamed outside of bundles, once the downstream is saturated so that the
+/// bundles which earn the validator the most keep flowing. Bundles are never shed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LoosePacketShedding {
+    /// Loose packet batches are shed while at least this many are queued downstream.
+    pub max_queued_batches: usize,
+}
+
 pub struct BlockBuilderFeeInfo {
     pub block_builder: Pubkey,
     pub block_builder_commission: u64,
@@ -79,6 +210,37 @@ pub struct BlockEngineConfig {
 
     /// If set then it will be assumed the backend verified packets so signature verification will be bypassed in the validator.
     pub trust_packets: bool,
+
+    /// If set, the bundle and packet streams are closed and re-established once they've been open
+    /// this long, ahead of intermediaries that drop long-lived streams.
+    pub max_stream_lifetime: Option<Duration>,
+
+    /// If set, bundles rejected by the policy are dropped instead of being queued for execution.
+    pub acceptance_policy: Option<Arc<dyn BundleAcceptancePolicy>>,
+
+    /// If set, every dropped bundle and shed batch of loose packets is reported on this channel.
+    /// Events are sent without blocking so a full or disconnected receiver misses events instead
+    /// of holding up ingest, which makes a bounded channel a good fit.
+    pub drop_event_tx: Option<Sender<DropEvent>>,
+
+    /// If set, loose packets are shed ahead of bundles under backpressure.
+    pub loose_packet_shedding: Option<LoosePacketShedding>,
+}
+
+/// What every connection made by [`BlockEngineStage::start`] streams with: the channels bundles
+/// and packets are forwarded to and the settings taken from the [`BlockEngineConfig`].
+struct BlockEngineStreamContext {
+    access_token: Arc<Mutex<Token>>,
+    bundle_tx: Sender<Vec<PacketBundle>>,
+    packet_tx: Sender<PacketBatch>,
+    trust_packets: bool,
+    verified_packet_tx: Sender<(Vec<PacketBatch>, Option<SigverifyTracerPacketStats>)>,
+    max_stream_lifetime: Option<Duration>,
+    acceptance_policy: Option<Arc<dyn BundleAcceptancePolicy>>,
+    drop_event_tx: Option<Sender<DropEvent>>,
+    loose_packet_shedding: Option<LoosePacketShedding>,
+    exit: Arc<AtomicBool>,
+    block_builder_fee_info: Arc<Mutex<BlockBuilderFeeInfo>>,
 }
 
 pub struct BlockEngineStage {
@@ -103,10 +265,26 @@ impl BlockEngineStage {
             auth_service_endpoint,
             backend_endpoint,
             trust_packets,
+            max_stream_lifetime,
+            acceptance_policy,
+            drop_event_tx,
+            loose_packet_shedding,
         } = block_engine_config;
 
         let access_token = Arc::new(Mutex::new(Token::default()));
-        let block_builder_fee_info = block_builder_fee_info.clone();
+        let context = BlockEngineStreamContext {
+            access_token: access_token.clone(),
+            bundle_tx,
+            packet_tx,
+            trust_packets,
+            verified_packet_tx,
+            max_stream_lifetime,
+            acceptance_policy,
+            drop_event_tx,
+            loose_packet_shedding,
+            exit: exit.clone(),
+            block_builder_fee_info: block_builder_fee_info.clone(),
+        };
 
         let thread = Builder::new()
             .name("block-engine-stage".into())
@@ -117,20 +295,12 @@ impl BlockEngineStage {
                     .unwrap();
                 rt.spawn(auth_tokens_update_loop(
                     auth_service_endpoint,
-                    access_token.clone(),
-                    cluster_info.clone(),
-                    exit.clone(),
-                ));
-                rt.block_on(Self::start(
                     access_token,
-                    backend_endpoint,
-                    bundle_tx,
-                    packet_tx,
-                    trust_packets,
-                    verified_packet_tx,
+                    cluster_info.clone(),
+                    AuthLoopConfig::default(),
                     exit,
-                    block_builder_fee_info,
                 ));
+                rt.block_on(Self::start(&context, backend_endpoint));
             })
             .unwrap();
 
@@ -146,25 +316,15 @@ impl BlockEngineStage {
         Ok(())
     }
 
-    #[allow(clippy::too_many_arguments)]
-    async fn start(
-        access_token: Arc<Mutex<Token>>,
-        block_engine_endpoint: Endpoint,
-        bundle_tx: Sender<Vec<PacketBundle>>,
-        packet_tx: Sender<PacketBatch>,
-        trust_packets: bool,
-        verified_packet_tx: Sender<(Vec<PacketBatch>, Option<SigverifyTracerPacketStats>)>,
-        exit: Arc<AtomicBool>,
-        block_builder_fee_info: Arc<Mutex<BlockBuilderFeeInfo>>,
-    ) {
+    async fn start(context: &BlockEngineStreamContext, block_engine_endpoint: Endpoint) {
         const WAIT_FOR_FIRST_AUTH: Duration = Duration::from_secs(5);
 
         let mut num_wait_for_auth: usize = 0;
         let mut num_stream_errors: usize = 0;
         let mut num_connect_errors: usize = 0;
 
-        while access_token.lock().unwrap().value.is_empty() {
-            if exit.load(Ordering::Relaxed) {
+        while context.access_token.lock().unwrap().value.is_empty() {
+            if context.exit.load(Ordering::Relaxed) {
                 return;
             }
             num_wait_for_auth += 1;
@@ -176,21 +336,16 @@ impl BlockEngineStage {
         }
 
         let mut backoff = BackoffStrategy::new();
-        while !exit.load(Ordering::Relaxed) {
+        while !context.exit.load(Ordering::Relaxed) {
             match block_engine_endpoint.connect().await {
                 Ok(channel) => {
                     match Self::start_consuming_block_engine_bundles_and_packets(
+                        context,
                         &mut backoff,
-                        &bundle_tx,
                         BlockEngineValidatorClient::with_interceptor(
                             channel,
-                            AuthInterceptor::new(access_token.clone()),
+                            AuthInterceptor::new(context.access_token.clone()),
                         ),
-                        &packet_tx,
-                        trust_packets,
-                        &verified_packet_tx,
-                        &exit,
-                        &block_builder_fee_info,
                     )
                     .await
                     {
@@ -220,14 +375,9 @@ impl BlockEngineStage {
     }
 
     async fn start_consuming_block_engine_bundles_and_packets(
+        context: &BlockEngineStreamContext,
         backoff: &mut BackoffStrategy,
-        bundle_tx: &Sender<Vec<PacketBundle>>,
         mut client: BlockEngineValidatorClient<InterceptedService<Channel, AuthInterceptor>>,
-        packet_tx: &Sender<PacketBatch>,
-        trust_packets: bool,
-        verified_packet_tx: &Sender<(Vec<PacketBatch>, Option<SigverifyTracerPacketStats>)>,
-        exit: &Arc<AtomicBool>,
-        block_builder_fee_info: &Arc<Mutex<BlockBuilderFeeInfo>>,
     ) -> crate::proxy::Result<()> {
         let subscribe_packets_stream = client
             .subscribe_packets(block_engine::SubscribePacketsRequest {})
@@ -243,7 +393,7 @@ impl BlockEngineStage {
             .await?
             .into_inner();
         {
-            let mut bb_fee = block_builder_fee_info.lock().unwrap();
+            let mut bb_fee = context.block_builder_fee_info.lock().unwrap();
             bb_fee.block_builder_commission = block_builder_info.commission;
             bb_fee.block_builder =
                 Pubkey::from_str(&block_builder_info.pubkey).unwrap_or(bb_fee.block_builder);
@@ -252,30 +402,20 @@ impl BlockEngineStage {
         backoff.reset();
 
         Self::consume_bundle_and_packet_stream(
+            context,
             client,
             (subscribe_bundles_stream, subscribe_packets_stream),
-            bundle_tx,
-            packet_tx,
-            trust_packets,
-            verified_packet_tx,
-            exit,
-            block_builder_fee_info,
         )
         .await
     }
 
     async fn consume_bundle_and_packet_stream(
+        context: &BlockEngineStreamContext,
         mut client: BlockEngineValidatorClient<InterceptedService<Channel, AuthInterceptor>>,
         (mut bundle_stream, mut packet_stream): (
             Streaming<block_engine::SubscribeBundlesResponse>,
             Streaming<block_engine::SubscribePacketsResponse>,
         ),
-        bundle_tx: &Sender<Vec<PacketBundle>>,
-        packet_tx: &Sender<PacketBatch>,
-        trust_packets: bool,
-        verified_packet_tx: &Sender<(Vec<PacketBatch>, Option<SigverifyTracerPacketStats>)>,
-        exit: &Arc<AtomicBool>,
-        block_builder_fee_info: &Arc<Mutex<BlockBuilderFeeInfo>>,
     ) -> crate::proxy::Result<()> {
         const METRICS_TICK: Duration = Duration::from_secs(1);
         const MAINTENANCE_TICK: Duration = Duration::from_secs(10 * 60);
@@ -284,16 +424,19 @@ impl BlockEngineStage {
         let mut metrics_tick = interval(METRICS_TICK);
         let mut maintenance_tick = interval(MAINTENANCE_TICK);
 
+        let stream_expiry = sleep(context.max_stream_lifetime.unwrap_or_default());
+        tokio::pin!(stream_expiry);
+
         info!("connected to packet and bundle stream");
 
-        while !exit.load(Ordering::Relaxed) {
+        while !context.exit.load(Ordering::Relaxed) {
             tokio::select! {
                 maybe_msg = packet_stream.message() => {
                     let resp = maybe_msg?.ok_or(ProxyError::GrpcStreamDisconnected)?;
-                    Self::handle_block_engine_packets(resp, packet_tx, verified_packet_tx, trust_packets, &mut block_engine_stats)?;
+                    Self::handle_block_engine_packets(resp, &context.packet_tx, &context.verified_packet_tx, context.trust_packets, context.loose_packet_shedding, context.drop_event_tx.as_ref(), &mut block_engine_stats)?;
                 }
                 maybe_bundles = bundle_stream.message() => {
-                    Self::handle_block_engine_maybe_bundles(maybe_bundles, bundle_tx, &mut block_engine_stats)?;
+                    Self::handle_block_engine_maybe_bundles(maybe_bundles, &context.bundle_tx, context.acceptance_policy.as_deref(), context.drop_event_tx.as_ref(), &mut block_engine_stats)?;
                 }
                 _ = metrics_tick.tick() => {
                     block_engine_stats.report();
@@ -301,10 +444,16 @@ impl BlockEngineStage {
                 }
                 _ = maintenance_tick.tick() => {
                     let block_builder_info = client.get_block_builder_fee_info(BlockBuilderFeeInfoRequest{}).await?.into_inner();
-                    let mut bb_fee = block_builder_fee_info.lock().unwrap();
+                    let mut bb_fee = context.block_builder_fee_info.lock().unwrap();
                     bb_fee.block_builder_commission = block_builder_info.commission;
                     bb_fee.block_builder = Pubkey::from_str(&block_builder_info.pubkey).unwrap_or(bb_fee.block_builder);
                 }
+                _ = &mut stream_expiry, if context.max_stream_lifetime.is_some() => {
+                    info!("bundle and packet streams reached their max lifetime, reconnecting");
+                    saturating_add_assign!(block_engine_stats.num_stream_recycles, 1);
+                    block_engine_stats.report();
+                    return Ok(());
+                }
             }
         }
 
@@ -314,32 +463,91 @@ impl BlockEngineStage {
     fn handle_block_engine_maybe_bundles(
         maybe_bundles_response: Result<Option<block_engine::SubscribeBundlesResponse>, Status>,
         bundle_sender: &Sender<Vec<PacketBundle>>,
+        acceptance_policy: Option<&dyn BundleAcceptancePolicy>,
+        drop_event_tx: Option<&Sender<DropEvent>>,
         block_engine_stats: &mut BlockEngineStageStats,
     ) -> crate::proxy::Result<()> {
         let bundles_response = maybe_bundles_response?.ok_or(ProxyError::GrpcStreamDisconnected)?;
-        let bundles: Vec<PacketBundle> = bundles_response
+        // bundles with a packet that doesn't deserialize into a transaction are dropped, so the
+        // acceptance policy judges the same transactions as the ones forwarded
+        let mut bundles: Vec<(PacketBundle, VersionedBundle)> = bundles_response
             .bundles
             .into_iter()
             .filter_map(|bundle| {
-                Some(PacketBundle {
-                    batch: PacketBatch::new(
-                        bundle
-                            .bundle?
-                            .packets
-                            .into_iter()
-                            .map(proto_packet_to_packet)
-                            .collect(),
+                let packet_bundle = match (bundle.bundle, Uuid::from_str(&bundle.uuid)) {
+                    (Some(proto_bundle), Ok(uuid)) => PacketBundle::new(
+                        PacketBatch::new(
+                            proto_bundle
+                                .packets
+                                .into_iter()
+                                .map(proto_packet_to_packet)
+                                .collect(),
+                        ),
+                        uuid,
                     ),
-                    uuid: Uuid::from_str(&bundle.uuid).ok()?,
-                })
+                    _ => {
+                        Self::report_drop(drop_event_tx, bundle.uuid, DropReason::Malformed);
+                        return None;
+                    }
+                };
+                match Self::versioned_bundle(&packet_bundle) {
+                    Some(versioned_bundle) => Some((packet_bundle, versioned_bundle)),
+                    None => {
+                        Self::report_drop(drop_event_tx, bundle.uuid, DropReason::Malformed);
+                        None
+                    }
+                }
             })
             .collect();
 
         saturating_add_assign!(block_engine_stats.num_bundles, bundles.len() as u64);
         saturating_add_assign!(
             block_engine_stats.num_bundle_packets,
-            bundles.iter().map(|bundle| bundle.batch.len() as u64).sum()
+            bundles
+                .iter()
+                .map(|(bundle, _)| bundle.batch.len() as u64)
+                .sum()
         );
+        for tx in bundles
+            .iter()
+            .flat_map(|(_, versioned_bundle)| versioned_bundle.transactions.iter())
+        {
+            match tx.version() {
+                TransactionVersion::Legacy(_) => {
+                    saturating_add_assign!(block_engine_stats.num_legacy_txs, 1);
+                }
+                TransactionVersion::Number(0) => {
+                    saturating_add_assign!(block_engine_stats.num_v0_txs, 1);
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(acceptance_policy) = acceptance_policy {
+            bundles.retain(|(bundle, versioned_bundle)| {
+                match acceptance_policy.accept(versioned_bundle) {
+                    Ok(()) => true,
+                    Err(reason) => {
+                        block_engine_stats.increment_rejected(&reason);
+                        block_engine_stats.record_bundle_processing(bundle.received_at);
+                        Self::report_drop(
+                            drop_event_tx,
+                            bundle.uuid.to_string(),
+                            DropReason::Rejected(reason),
+                        );
+                        false
+                    }
+                }
+            });
+        }
+
+        let bundles: Vec<PacketBundle> = bundles
+            .into_iter()
+            .map(|(bundle, _)| {
+                block_engine_stats.record_bundle_processing(bundle.received_at);
+                bundle
+            })
+            .collect();
 
         // NOTE: bundles are sanitized in bundle_sanitizer module
         bundle_sender
@@ -347,11 +555,38 @@ impl BlockEngineStage {
             .map_err(|_| ProxyError::PacketForwardError)
     }
 
+    pub(crate) fn report_drop(
+        drop_event_tx: Option<&Sender<DropEvent>>,
+        bundle_uuid: String,
+        reason: DropReason,
+    ) {
+        if let Some(drop_event_tx) = drop_event_tx {
+            // best effort, dropping the event is better than stalling ingest
+            let _ = drop_event_tx.try_send(DropEvent {
+                bundle_uuid,
+                reason,
+                timestamp: SystemTime::now(),
+            });
+        }
+    }
+
+    /// Returns None if any of the packets fails to deserialize into a transaction.
+    fn versioned_bundle(packet_bundle: &PacketBundle) -> Option<VersionedBundle> {
+        let transactions = packet_bundle
+            .batch
+            .iter()
+            .map(|packet| packet.deserialize_slice::<VersionedTransaction, _>(..).ok())
+            .collect::<Option<_>>()?;
+        Some(VersionedBundle { transactions })
+    }
+
     fn handle_block_engine_packets(
         resp: block_engine::SubscribePacketsResponse,
         packet_tx: &Sender<PacketBatch>,
         verified_packet_tx: &Sender<(Vec<PacketBatch>, Option<SigverifyTracerPacketStats>)>,
         trust_packets: bool,
+        loose_packet_shedding: Option<LoosePacketShedding>,
+        drop_event_tx: Option<&Sender<DropEvent>>,
         block_engine_stats: &mut BlockEngineStageStats,
     ) -> crate::proxy::Result<()> {
         if let Some(batch) = resp.batch {
@@ -365,6 +600,28 @@ impl BlockEngineStage {
 
             saturating_add_assign!(block_engine_stats.num_packets, packet_batch.len() as u64);
 
+            let num_queued_batches = if trust_packets {
+                verified_packet_tx.len()
+            } else {
+                packet_tx.len()
+            };
+            if loose_packet_shedding.map_or(false, |shedding| {
+                num_queued_batches >= shedding.max_queued_batches
+            }) {
+                saturating_add_assign!(
+                    block_engine_stats.num_shed_loose_packets,
+                    packet_batch.len() as u64
+                );
+                Self::report_drop(
+                    drop_event_tx,
+                    String::default(),
+                    DropReason::Shed {
+                        num_packets: packet_batch.len(),
+                    },
+                );
+                return Ok(());
+            }
+
             if trust_packets {
                 verified_packet_tx
                     .send((vec![packet_batch], None))
@@ -381,3 +638,416 @@ impl BlockEngineStage {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::proxy::bundle_acceptance_policy::MaxTransactionsPolicy,
+        crossbeam_channel::{bounded, unbounded},
+        jito_protos::proto::{
+            bundle::{Bundle, BundleUuid},
+            packet::{Meta, Packet, PacketBatch as PacketBatchProto},
+        },
+        solana_sdk::{
+            hash::Hash,
+            message::{v0, VersionedMessage},
+            signature::{Keypair, Signer},
+            system_instruction, system_transaction,
+        },
+        std::time::Instant,
+    };
+
+    #[test]
+    fn test_bundles_timestamped_at_ingest() {
+        let (bundle_tx, bundle_rx) = unbounded();
+        let mut stats = BlockEngineStageStats::default();
+
+        let before_ingest = Instant::now();
+        BlockEngineStage::handle_block_engine_maybe_bundles(
+            Ok(Some(block_engine::SubscribeBundlesResponse {
+                bundles: vec![BundleUuid {
+                    bundle: Some(Bundle::default()),
+                    uuid: Uuid::new_v4().to_string(),
+                }],
+            })),
+            &bundle_tx,
+            None,
+            None,
+            &mut stats,
+        )
+        .unwrap();
+        let after_ingest = Instant::now();
+
+        let bundles = bundle_rx.recv().unwrap();
+        assert_eq!(bundles.len(), 1);
+        let received_at = bundles[0].received_at;
+        assert!(received_at >= before_ingest && received_at <= after_ingest);
+
+        // bundles are cloned and re-queued on retry, which must keep the original timestamp
+        assert_eq!(bundles[0].clone().received_at, received_at);
+    }
+
+    fn proto_packet(tx: &VersionedTransaction) -> Packet {
+        let data = bincode::serialize(tx).unwrap();
+        Packet {
+            meta: Some(Meta {
+                size: data.len() as _,
+                ..Meta::default()
+            }),
+            data,
+        }
+    }
+
+    #[test]
+    fn test_bundle_transaction_versions_counted() {
+        let (bundle_tx, _bundle_rx) = unbounded();
+        let mut stats = BlockEngineStageStats::default();
+
+        let payer = Keypair::new();
+        let legacy_tx = VersionedTransaction::from(system_transaction::transfer(
+            &payer,
+            &Pubkey::new_unique(),
+            1,
+            Hash::default(),
+        ));
+        let v0_tx = VersionedTransaction::try_new(
+            VersionedMessage::V0(
+                v0::Message::try_compile(
+                    &payer.pubkey(),
+                    &[system_instruction::transfer(
+                        &payer.pubkey(),
+                        &Pubkey::new_unique(),
+                        1,
+                    )],
+                    &[],
+                    Hash::default(),
+                )
+                .unwrap(),
+            ),
+            &[&payer],
+        )
+        .unwrap();
+
+        BlockEngineStage::handle_block_engine_maybe_bundles(
+            Ok(Some(block_engine::SubscribeBundlesResponse {
+                bundles: vec![BundleUuid {
+                    bundle: Some(Bundle {
+                        packets: vec![
+                            proto_packet(&legacy_tx),
+                            proto_packet(&v0_tx),
+                            proto_packet(&legacy_tx),
+                        ],
+                        ..Bundle::default()
+                    }),
+                    uuid: Uuid::new_v4().to_string(),
+                }],
+            })),
+            &bundle_tx,
+            None,
+            None,
+            &mut stats,
+        )
+        .unwrap();
+
+        assert_eq!(stats.num_bundle_packets, 3);
+        assert_eq!(stats.num_legacy_txs, 2);
+        assert_eq!(stats.num_v0_txs, 1);
+    }
+
+    #[test]
+    fn test_bundles_rejected_by_acceptance_policy_dropped() {
+        let (bundle_tx, bundle_rx) = unbounded();
+        let mut stats = BlockEngineStageStats::default();
+
+        let payer = Keypair::new();
+        let transfer = VersionedTransaction::from(system_transaction::transfer(
+            &payer,
+            &Pubkey::new_unique(),
+            1,
+            Hash::default(),
+        ));
+        let bundle_uuid = |num_transactions: usize| BundleUuid {
+            bundle: Some(Bundle {
+                packets: vec![proto_packet(&transfer); num_transactions],
+                ..Bundle::default()
+            }),
+            uuid: Uuid::new_v4().to_string(),
+        };
+
+        BlockEngineStage::handle_block_engine_maybe_bundles(
+            Ok(Some(block_engine::SubscribeBundlesResponse {
+                bundles: vec![bundle_uuid(1), bundle_uuid(3), bundle_uuid(2)],
+            })),
+            &bundle_tx,
+            Some(&MaxTransactionsPolicy(2)),
+            None,
+            &mut stats,
+        )
+        .unwrap();
+
+        let bundles = bundle_rx.recv().unwrap();
+        let lens: Vec<usize> = bundles.iter().map(|bundle| bundle.batch.len()).collect();
+        assert_eq!(lens, vec![1, 2]);
+        assert_eq!(stats.num_bundles, 3);
+        assert_eq!(stats.num_rejected_too_many_transactions, 1);
+    }
+
+    #[test]
+    fn test_undecodable_bundles_dropped() {
+        let (bundle_tx, bundle_rx) = unbounded();
+        let mut stats = BlockEngineStageStats::default();
+
+        let transfer = VersionedTransaction::from(system_transaction::transfer(
+            &Keypair::new(),
+            &Pubkey::new_unique(),
+            1,
+            Hash::default(),
+        ));
+        // one of the packets isn't a transaction
+        let undecodable = BundleUuid {
+            bundle: Some(Bundle {
+                packets: vec![
+                    proto_packet(&transfer),
+                    Packet {
+                        meta: Some(Meta {
+                            size: 3,
+                            ..Meta::default()
+                        }),
+                        data: vec![1, 2, 3],
+                    },
+                ],
+                ..Bundle::default()
+            }),
+            uuid: Uuid::new_v4().to_string(),
+        };
+        let valid = BundleUuid {
+            bundle: Some(Bundle {
+                packets: vec![proto_packet(&transfer)],
+                ..Bundle::default()
+            }),
+            uuid: Uuid::new_v4().to_string(),
+        };
+
+        BlockEngineStage::handle_block_engine_maybe_bundles(
+            Ok(Some(block_engine::SubscribeBundlesResponse {
+                bundles: vec![undecodable, valid.clone()],
+            })),
+            &bundle_tx,
+            Some(&MaxTransactionsPolicy(2)),
+            None,
+            &mut stats,
+        )
+        .unwrap();
+
+        let bundles = bundle_rx.recv().unwrap();
+        assert_eq!(bundles.len(), 1);
+        assert_eq!(bundles[0].uuid.to_string(), valid.uuid);
+        assert_eq!(stats.num_bundles, 1);
+        assert_eq!(stats.num_bundle_packets, 1);
+    }
+
+    #[test]
+    fn test_dropped_bundles_reported() {
+        let (bundle_tx, _bundle_rx) = unbounded();
+        let (drop_event_tx, drop_event_rx) = bounded(10);
+        let mut stats = BlockEngineStageStats::default();
+
+        let transfer = VersionedTransaction::from(system_transaction::transfer(
+            &Keypair::new(),
+            &Pubkey::new_unique(),
+            1,
+            Hash::default(),
+        ));
+        let rejected = BundleUuid {
+            bundle: Some(Bundle {
+                packets: vec![proto_packet(&transfer); 3],
+                ..Bundle::default()
+            }),
+            uuid: Uuid::new_v4().to_string(),
+        };
+        let malformed = BundleUuid {
+            bundle: None,
+            uuid: Uuid::new_v4().to_string(),
+        };
+        // one of the packets isn't a transaction
+        let undecodable = BundleUuid {
+            bundle: Some(Bundle {
+                packets: vec![
+                    proto_packet(&transfer),
+                    Packet {
+                        meta: Some(Meta {
+                            size: 3,
+                            ..Meta::default()
+                        }),
+                        data: vec![1, 2, 3],
+                    },
+                ],
+                ..Bundle::default()
+            }),
+            uuid: Uuid::new_v4().to_string(),
+        };
+
+        let before_ingest = SystemTime::now();
+        BlockEngineStage::handle_block_engine_maybe_bundles(
+            Ok(Some(block_engine::SubscribeBundlesResponse {
+                bundles: vec![malformed.clone(), rejected.clone(), undecodable.clone()],
+            })),
+            &bundle_tx,
+            Some(&MaxTransactionsPolicy(2)),
+            Some(&drop_event_tx),
+            &mut stats,
+        )
+        .unwrap();
+
+        let events: Vec<DropEvent> = drop_event_rx.try_iter().collect();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].bundle_uuid, malformed.uuid);
+        assert_eq!(events[0].reason, DropReason::Malformed);
+        assert_eq!(events[1].bundle_uuid, undecodable.uuid);
+        assert_eq!(events[1].reason, DropReason::Malformed);
+        assert_eq!(events[2].bundle_uuid, rejected.uuid);
+        assert_eq!(
+            events[2].reason,
+            DropReason::Rejected(RejectReason::TooManyTransactions { len: 3, max: 2 })
+        );
+        assert!(events[2].timestamp >= before_ingest);
+        assert_eq!(stats.num_bundles, 1);
+    }
+
+    #[test]
+    fn test_bundle_processing_time_recorded() {
+        let (bundle_tx, _bundle_rx) = unbounded();
+        let mut stats = BlockEngineStageStats::default();
+
+        let transfer = VersionedTransaction::from(system_transaction::transfer(
+            &Keypair::new(),
+            &Pubkey::new_unique(),
+            1,
+            Hash::default(),
+        ));
+        let bundle_uuid = |num_transactions: usize| BundleUuid {
+            bundle: Some(Bundle {
+                packets: vec![proto_packet(&transfer); num_transactions],
+                ..Bundle::default()
+            }),
+            uuid: Uuid::new_v4().to_string(),
+        };
+        let malformed = BundleUuid {
+            bundle: None,
+            uuid: Uuid::new_v4().to_string(),
+        };
+
+        let before_ingest = Instant::now();
+        BlockEngineStage::handle_block_engine_maybe_bundles(
+            Ok(Some(block_engine::SubscribeBundlesResponse {
+                bundles: vec![bundle_uuid(1), bundle_uuid(3), malformed],
+            })),
+            &bundle_tx,
+            Some(&MaxTransactionsPolicy(2)),
+            None,
+            &mut stats,
+        )
+        .unwrap();
+        let ingest_ns = before_ingest.elapsed().as_nanos() as u64;
+
+        // the forwarded and the rejected bundles, malformed ones are never decoded
+        assert_eq!(stats.num_processed_bundles, 2);
+        assert!(stats.max_bundle_processing_ns > 0);
+        assert!(stats.max_bundle_processing_ns <= ingest_ns);
+        assert!(stats.total_bundle_processing_ns >= stats.max_bundle_processing_ns);
+        stats.report();
+    }
+
+    #[test]
+    fn test_loose_packets_shed_before_bundles() {
+        let (bundle_tx, bundle_rx) = bounded(2);
+        let (packet_tx, packet_rx) = bounded(2);
+        let (verified_packet_tx, _verified_packet_rx) = unbounded();
+        let (drop_event_tx, drop_event_rx) = unbounded();
+        let mut stats = BlockEngineStageStats::default();
+        let shedding = Some(LoosePacketShedding {
+            max_queued_batches: 1,
+        });
+
+        let transfer = VersionedTransaction::from(system_transaction::transfer(
+            &Keypair::new(),
+            &Pubkey::new_unique(),
+            1,
+            Hash::default(),
+        ));
+        let packets = || block_engine::SubscribePacketsResponse {
+            batch: Some(PacketBatchProto {
+                packets: vec![proto_packet(&transfer); 2],
+            }),
+            ..block_engine::SubscribePacketsResponse::default()
+        };
+        let bundles = || block_engine::SubscribeBundlesResponse {
+            bundles: vec![BundleUuid {
+                bundle: Some(Bundle {
+                    packets: vec![proto_packet(&transfer)],
+                    ..Bundle::default()
+                }),
+                uuid: Uuid::new_v4().to_string(),
+            }],
+        };
+
+        // nothing queued yet, so the first batch goes through
+        BlockEngineStage::handle_block_engine_packets(
+            packets(),
+            &packet_tx,
+            &verified_packet_tx,
+            false,
+            shedding,
+            Some(&drop_event_tx),
+            &mut stats,
+        )
+        .unwrap();
+        assert_eq!(packet_rx.len(), 1);
+        assert_eq!(stats.num_shed_loose_packets, 0);
+
+        // the downstream isn't keeping up: loose packets are shed while bundles still get through
+        BlockEngineStage::handle_block_engine_packets(
+            packets(),
+            &packet_tx,
+            &verified_packet_tx,
+            false,
+            shedding,
+            Some(&drop_event_tx),
+            &mut stats,
+        )
+        .unwrap();
+        BlockEngineStage::handle_block_engine_maybe_bundles(
+            Ok(Some(bundles())),
+            &bundle_tx,
+            None,
+            Some(&drop_event_tx),
+            &mut stats,
+        )
+        .unwrap();
+        assert_eq!(packet_rx.len(), 1);
+        assert_eq!(stats.num_shed_loose_packets, 2);
+        assert_eq!(bundle_rx.try_recv().unwrap().len(), 1);
+        let events: Vec<DropEvent> = drop_event_rx.try_iter().collect();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].reason, DropReason::Shed { num_packets: 2 });
+        assert!(events[0].bundle_uuid.is_empty());
+
+        // once drained, loose packets flow again
+        packet_rx.try_recv().unwrap();
+        BlockEngineStage::handle_block_engine_packets(
+            packets(),
+            &packet_tx,
+            &verified_packet_tx,
+            false,
+            shedding,
+            Some(&drop_event_tx),
+            &mut stats,
+        )
+        .unwrap();
+        assert_eq!(packet_rx.len(), 1);
+        assert_eq!(stats.num_packets, 6);
+        assert_eq!(stats.num_shed_loose_packets, 2);
+        assert!(drop_event_rx.is_empty());
+    }
+}
@@ -0,0 +1,165 @@
+//! Policies deciding which bundles received from the block engine are queued for execution.
+//! Policies are composable so operators can combine the built-in filters or replace them
+//! altogether.
+
+use {
+    solana_sdk::{bundle::VersionedBundle, pubkey::Pubkey},
+    std::{collections::HashSet, fmt::Debug},
+    thiserror::Error,
+};
+
+#[derive(Error, Debug, PartialEq, Eq, Clone)]
+pub enum RejectReason {
+    #[error("bundle has {len} transactions, more than the max of {max}")]
+    TooManyTransactions { len: usize, max: usize },
+
+    #[error("bundle contains a vote transaction")]
+    ContainsVoteTransaction,
+
+    #[error("bundle invokes program {0} which isn't allowed")]
+    ProgramNotAllowed(Pubkey),
+}
+
+pub trait BundleAcceptancePolicy: Debug + Send + Sync {
+    /// Returns why the bundle must be dropped, if it must be.
+    fn accept(&self, bundle: &VersionedBundle) -> Result<(), RejectReason>;
+}
+
+/// Rejects bundles with more than the given number of transactions.
+#[derive(Debug)]
+pub struct MaxTransactionsPolicy(pub usize);
+
+impl BundleAcceptancePolicy for MaxTransactionsPolicy {
+    fn accept(&self, bundle: &VersionedBundle) -> Result<(), RejectReason> {
+        if bundle.transactions.len() > self.0 {
+            return Err(RejectReason::TooManyTransactions {
+                len: bundle.transactions.len(),
+                max: self.0,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Rejects bundles containing vote transactions, which have no business being in a bundle.
+#[derive(Debug)]
+pub struct RejectVotesPolicy;
+
+impl BundleAcceptancePolicy for RejectVotesPolicy {
+    fn accept(&self, bundle: &VersionedBundle) -> Result<(), RejectReason> {
+        if bundle.contains_vote_transaction() {
+            return Err(RejectReason::ContainsVoteTransaction);
+        }
+        Ok(())
+    }
+}
+
+/// Rejects bundles invoking any program outside of the allowlist.
+#[derive(Debug)]
+pub struct ProgramAllowlistPolicy(pub HashSet<Pubkey>);
+
+impl BundleAcceptancePolicy for ProgramAllowlistPolicy {
+    fn accept(&self, bundle: &VersionedBundle) -> Result<(), RejectReason> {
+        for tx in &bundle.transactions {
+            let account_keys = tx.message.static_account_keys();
+            for ix in tx.message.instructions() {
+                match account_keys.get(ix.program_id_index as usize) {
+                    Some(program_id) if !self.0.contains(program_id) => {
+                        return Err(RejectReason::ProgramNotAllowed(*program_id));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Accepts a bundle only if all of its policies do, rejecting with the reason of the first policy
+/// that doesn't.
+#[derive(Debug, Default)]
+pub struct CompositePolicy(pub Vec<Box<dyn BundleAcceptancePolicy>>);
+
+impl BundleAcceptancePolicy for CompositePolicy {
+    fn accept(&self, bundle: &VersionedBundle) -> Result<(), RejectReason> {
+        self.0.iter().try_for_each(|policy| policy.accept(bundle))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_sdk::{
+            hash::Hash,
+            signature::{Keypair, Signer},
+            system_program, system_transaction,
+            transaction::VersionedTransaction,
+        },
+        solana_vote_program::vote_transaction,
+    };
+
+    #[test]
+    fn test_composite_policy() {
+        let policy = CompositePolicy(vec![
+            Box::new(MaxTransactionsPolicy(2)),
+            Box::new(RejectVotesPolicy),
+            Box::new(ProgramAllowlistPolicy(HashSet::from(
+                [system_program::id()],
+            ))),
+        ]);
+
+        let payer = Keypair::new();
+        let transfer = VersionedTransaction::from(system_transaction::transfer(
+            &payer,
+            &Pubkey::new_unique(),
+            1,
+            Hash::default(),
+        ));
+        let vote = VersionedTransaction::from(vote_transaction::new_vote_transaction(
+            vec![0],
+            Hash::default(),
+            Hash::default(),
+            &payer,
+            &Keypair::new(),
+            &Keypair::new(),
+            None,
+        ));
+
+        let bundle = VersionedBundle {
+            transactions: vec![transfer.clone(), transfer.clone()],
+        };
+        assert_eq!(policy.accept(&bundle), Ok(()));
+
+        let bundle = VersionedBundle {
+            transactions: vec![transfer.clone(); 3],
+        };
+        assert_eq!(
+            policy.accept(&bundle),
+            Err(RejectReason::TooManyTransactions { len: 3, max: 2 })
+        );
+
+        let bundle = VersionedBundle {
+            transactions: vec![transfer, vote],
+        };
+        assert_eq!(
+            policy.accept(&bundle),
+            Err(RejectReason::ContainsVoteTransaction)
+        );
+
+        // the vote program isn't allowed either, the first failing policy wins
+        let policy = CompositePolicy(vec![
+            Box::new(ProgramAllowlistPolicy(HashSet::from(
+                [system_program::id()],
+            ))),
+            Box::new(RejectVotesPolicy),
+        ]);
+        assert_eq!(
+            policy.accept(&bundle),
+            Err(RejectReason::ProgramNotAllowed(solana_vote_program::id()))
+        );
+
+        // an empty composite accepts everything
+        assert_eq!(CompositePolicy::default().accept(&bundle), Ok(()));
+    }
+}
@@ -0,0 +1,22 @@
+//! Signers for the challenges handed out by the auth service, proving the validator holds its
+//! identity. Validators keeping their identity keypair in an HSM or behind a remote signer can
+//! plug in their own.
+
+use {
+    solana_sdk::signature::{Keypair, Signature, Signer},
+    std::sync::Arc,
+};
+
+pub trait ChallengeSigner: Send + Sync {
+    /// Signs the message with the validator's identity.
+    fn sign_message(&self, message: &[u8]) -> Signature;
+}
+
+/// Signs with an identity keypair held in memory.
+pub struct KeypairSigner(pub Arc<Keypair>);
+
+impl ChallengeSigner for KeypairSigner {
+    fn sign_message(&self, message: &[u8]) -> Signature {
+        self.0.sign_message(message)
+    }
+}
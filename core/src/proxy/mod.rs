@@ -12,7 +12,10 @@
 
 mod auth;
 pub mod block_engine_stage;
+pub mod bundle_acceptance_policy;
+pub mod challenge_signer;
 pub mod fetch_stage_manager;
+pub mod reconnect_limiter;
 pub mod relayer_stage;
 
 use {
@@ -52,4 +55,28 @@ pub enum ProxyError {
 
     #[error("invalid gRPC data: {0:?}")]
     InvalidData(String),
+
+    #[error("bad authentication token: {0}")]
+    BadAuthenticationToken(String),
+
+    #[error("signing error: {0}")]
+    SigningError(String),
+
+    #[error("connection error: {0}")]
+    ConnectionError(String),
+
+    #[error("timed out connecting to the relayer")]
+    RelayerConnectionTimeout,
+
+    #[error("timed out fetching the tpu configs from the relayer")]
+    TpuConfigTimeout,
+
+    #[error("timed out subscribing to packets from the relayer")]
+    SubscribePacketsTimeout,
+
+    #[error("relayer sent {0} empty messages in a second")]
+    TooManyEmptyMessages(u64),
+
+    #[error("incompatible relayer: {0}")]
+    IncompatibleRelayer(String),
 }
@@ -0,0 +1,99 @@
+//! Token bucket bounding how often connections are (re-)established.
+//! A single limiter can be shared between stages so that stages pointed at relayers that go down
+//! together don't storm the auth service and relayers when reconnecting.
+
+use {
+    std::{
+        sync::Mutex,
+        time::{Duration, Instant},
+    },
+    tokio::time::sleep,
+};
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: u32,
+    last_refill: Instant,
+}
+
+#[derive(Debug)]
+pub struct ReconnectRateLimiter {
+    /// The max number of reconnects allowed in a burst.
+    capacity: u32,
+    /// A token is added to the bucket every interval.
+    refill_interval: Duration,
+    bucket: Mutex<Bucket>,
+}
+
+impl ReconnectRateLimiter {
+    pub fn new(capacity: u32, refill_interval: Duration) -> Self {
+        assert!(capacity > 0, "capacity must be positive");
+        assert!(
+            !refill_interval.is_zero(),
+            "refill_interval must be positive"
+        );
+        Self {
+            capacity,
+            refill_interval,
+            bucket: Mutex::new(Bucket {
+                tokens: capacity,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes a token if one is available, otherwise returns how long to wait for the next one.
+    fn try_acquire(&self, now: Instant) -> Result<(), Duration> {
+        let mut bucket = self.bucket.lock().unwrap();
+
+        let elapsed = now.saturating_duration_since(bucket.last_refill);
+        let num_refills = (elapsed.as_nanos() / self.refill_interval.as_nanos()) as u32;
+        if num_refills > 0 {
+            bucket.tokens = bucket.tokens.saturating_add(num_refills).min(self.capacity);
+            bucket.last_refill += self.refill_interval * num_refills;
+        }
+        if bucket.tokens == self.capacity {
+            // don't bank up time while the bucket is full
+            bucket.last_refill = now;
+        }
+
+        if bucket.tokens > 0 {
+            bucket.tokens -= 1;
+            Ok(())
+        } else {
+            Err((bucket.last_refill + self.refill_interval).saturating_duration_since(now))
+        }
+    }
+
+    /// Waits until a reconnect is allowed.
+    pub async fn acquire(&self) {
+        while let Err(wait) = self.try_acquire(Instant::now()) {
+            sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reconnect_rate_limiter_refills() {
+        let limiter = ReconnectRateLimiter::new(2, Duration::from_secs(1));
+        let start = limiter.bucket.lock().unwrap().last_refill;
+
+        assert_eq!(limiter.try_acquire(start), Ok(()));
+        assert_eq!(limiter.try_acquire(start), Ok(()));
+        assert_eq!(limiter.try_acquire(start), Err(Duration::from_secs(1)));
+
+        let now = start + Duration::from_millis(1_500);
+        assert_eq!(limiter.try_acquire(now), Ok(()));
+        assert_eq!(limiter.try_acquire(now), Err(Duration::from_millis(500)));
+
+        // the bucket never holds more than its capacity
+        let now = start + Duration::from_secs(60);
+        assert_eq!(limiter.try_acquire(now), Ok(()));
+        assert_eq!(limiter.try_acquire(now), Ok(()));
+        assert!(limiter.try_acquire(now).is_err());
+    }
+}
@@ -10,35 +10,56 @@
 
 use {
     crate::{
-        backoff::BackoffStrategy,
+        backoff::{BackoffStrategy, ExponentialBackoff},
         proto_packet_to_packet,
         proxy::{
-            auth::{token_manager::auth_tokens_update_loop, AuthInterceptor},
+            auth::{
+                token_expiry_secs, token_manager::auth_tokens_update_loop, AuthInterceptor,
+                AuthLoopConfig, RefreshFreeze, TokenCache, TokenExpiry, DEFAULT_REFRESH_LOOKAHEAD,
+            },
+            block_engine_stage::{BlockEngineStage, DropEvent, DropReason},
+            challenge_signer::ChallengeSigner,
+            reconnect_limiter::ReconnectRateLimiter,
             HeartbeatEvent, ProxyError,
         },
         sigverify::SigverifyTracerPacketStats,
     },
-    crossbeam_channel::Sender,
+    chrono::{DateTime, Utc},
+    crossbeam_channel::{Receiver, Sender, TrySendError},
+    hyper::client::HttpConnector,
     jito_protos::proto::{
         auth::Token,
         relayer::{self, relayer_client::RelayerClient},
+        shared::Header,
     },
+    rand::Rng,
     solana_gossip::cluster_info::ClusterInfo,
-    solana_perf::packet::PacketBatch,
-    solana_sdk::saturating_add_assign,
+    solana_perf::{
+        packet::{Packet, PacketBatch},
+        sigverify::verify_packet,
+    },
+    solana_sdk::{saturating_add_assign, signature::SIGNATURE_BYTES},
     std::{
+        collections::VecDeque,
+        env,
+        fmt::Display,
         net::{IpAddr, Ipv4Addr, SocketAddr},
+        path::PathBuf,
         sync::{
-            atomic::{AtomicBool, Ordering},
+            atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
             Arc, Mutex,
         },
         thread::{self, Builder, JoinHandle},
-        time::{Duration, Instant},
+        time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+    },
+    thiserror::Error,
+    tokio::{
+        sync::{oneshot, Notify},
+        time::{interval, sleep, timeout},
     },
-    tokio::time::{interval, sleep},
     tonic::{
         codegen::InterceptedService,
-        transport::{Channel, Endpoint},
+        transport::{Channel, ClientTlsConfig, Endpoint},
         Streaming,
     },
 };
@@ -47,316 +68,3337 @@ use {
 struct RelayerStageStats {
     num_empty_messages: u64,
     num_packets: u64,
+    num_trusted_packets: u64,
+    num_untrusted_packets: u64,
     num_heartbeats: u64,
+    num_stream_recycles: u64,
+    num_trust_audited_packets: u64,
+    num_trust_violations: u64,
+    num_trusted_dropped: u64,
+    packet_latency_us_hist: histogram::Histogram, // time since the relayer stamped each batch
 }
 
 impl RelayerStageStats {
-    pub(crate) fn report(&self) {
+    pub(crate) fn report(&self, connection_id: &str) {
         datapoint_info!(
             "relayer_stage-stats",
+            "connection_id" => connection_id,
             ("num_empty_messages", self.num_empty_messages, i64),
             ("num_packets", self.num_packets, i64),
+            ("num_trusted_packets", self.num_trusted_packets, i64),
+            ("num_untrusted_packets", self.num_untrusted_packets, i64),
             ("num_heartbeats", self.num_heartbeats, i64),
+            ("num_stream_recycles", self.num_stream_recycles, i64),
+            ("num_trust_audited_packets", self.num_trust_audited_packets, i64),
+            ("num_trust_violations", self.num_trust_violations, i64),
+            ("num_trusted_dropped", self.num_trusted_dropped, i64),
+            (
+                "packet_latency_us_min",
+                self.packet_latency_us_hist.minimum().unwrap_or(0),
+                i64
+            ),
+            (
+                "packet_latency_us_max",
+                self.packet_latency_us_hist.maximum().unwrap_or(0),
+                i64
+            ),
+            (
+                "packet_latency_us_p50",
+                self.packet_latency_us_hist.percentile(50.0).unwrap_or(0),
+                i64
+            ),
+            (
+                "packet_latency_us_p99",
+                self.packet_latency_us_hist.percentile(99.0).unwrap_or(0),
+                i64
+            ),
         );
     }
 }
 
-#[derive(Clone, Debug)]
-pub struct RelayerConfig {
-    /// Address to the external auth-service responsible for generating access tokens.
-    pub auth_service_endpoint: Endpoint,
+/// Rolling counts of the packets that bypassed sigverify because they were trusted versus the ones
+/// that went through it, to quantify what trusting the relayer saves.
+struct TrustedPacketWindow {
+    /// (trusted, untrusted) packet counts of each of the last metrics ticks, oldest first.
+    samples: VecDeque<(u64, u64)>,
+}
 
-    /// Primary backend endpoint.
-    pub backend_endpoint: Endpoint,
+impl TrustedPacketWindow {
+    /// Number of metrics ticks the ratio is computed over.
+    const NUM_SAMPLES: usize = 60;
 
-    /// Interval at which heartbeats are expected.
-    pub expected_heartbeat_interval: Duration,
+    fn new() -> Self {
+        Self {
+            samples: VecDeque::with_capacity(Self::NUM_SAMPLES),
+        }
+    }
 
-    /// The max tolerable age of the last heartbeat.
-    pub oldest_allowed_heartbeat: Duration,
+    fn record(&mut self, stats: &RelayerStageStats) {
+        if self.samples.len() == Self::NUM_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples
+            .push_back((stats.num_trusted_packets, stats.num_untrusted_packets));
+    }
 
-    /// If set then it will be assumed the backend verified packets so signature verification will be bypassed in the validator.
-    pub trust_packets: bool,
+    /// Returns the (trusted, untrusted) packet counts over the window.
+    fn totals(&self) -> (u64, u64) {
+        self.samples.iter().fold(
+            (0, 0),
+            |(trusted, untrusted), (sample_trusted, sample_untrusted)| {
+                (
+                    trusted.saturating_add(*sample_trusted),
+                    untrusted.saturating_add(*sample_untrusted),
+                )
+            },
+        )
+    }
+
+    /// Returns the share of the window's packets that were trusted, None if there were none.
+    fn trusted_ratio(&self) -> Option<f64> {
+        let (trusted, untrusted) = self.totals();
+        let total = trusted.saturating_add(untrusted);
+        if total == 0 {
+            None
+        } else {
+            Some(trusted as f64 / total as f64)
+        }
+    }
+
+    fn report(&self, connection_id: &str) {
+        let (trusted, untrusted) = self.totals();
+        if let Some(trusted_ratio) = self.trusted_ratio() {
+            datapoint_info!(
+                "relayer_stage-trusted_packet_ratio",
+                "connection_id" => connection_id,
+                ("num_trusted_packets", trusted, i64),
+                ("num_untrusted_packets", untrusted, i64),
+                ("trusted_ratio", trusted_ratio, f64),
+            );
+        }
+    }
 }
 
-pub struct RelayerStage {
-    t_hdls: Vec<JoinHandle<()>>,
+/// The stage of the connection to the relayer, see [`RelayerStage::state`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum RelayerConnectionState {
+    /// Establishing the gRPC channel to the relayer.
+    Connecting,
+    /// Waiting for the auth service to issue the first access token.
+    Authenticating,
+    /// Fetching the TPU configs and subscribing to the packet stream.
+    Subscribing,
+    /// Receiving packets and heartbeats from the relayer.
+    Streaming,
+    /// Waiting before reconnecting after a failure.
+    Backoff,
+    /// The packets can no longer be forwarded so the stage stopped and won't reconnect.
+    Fatal,
 }
 
-impl RelayerStage {
-    pub fn new(
-        relayer_config: RelayerConfig,
-        // The keypair stored here is used to sign auth challenges.
-        cluster_info: Arc<ClusterInfo>,
-        // Channel that server-sent heartbeats are piped through.
-        heartbeat_tx: Sender<HeartbeatEvent>,
-        // Channel that non-trusted streamed packets are piped through.
-        packet_tx: Sender<PacketBatch>,
-        // Channel that trusted streamed packets are piped through.
-        verified_packet_tx: Sender<(Vec<PacketBatch>, Option<SigverifyTracerPacketStats>)>,
-        exit: Arc<AtomicBool>,
-    ) -> Self {
-        let RelayerConfig {
-            auth_service_endpoint,
-            backend_endpoint,
-            expected_heartbeat_interval,
-            oldest_allowed_heartbeat,
-            trust_packets,
-        } = relayer_config;
+/// A transition of the connection to the relayer, streamed to the sender passed to
+/// [`RelayerStage::new`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RelayerStateChange {
+    pub from: RelayerConnectionState,
+    pub to: RelayerConnectionState,
+    /// Id of the connection attempt the transition happened in.
+    pub connection_id: String,
+    pub timestamp: SystemTime,
+}
 
-        let access_token = Arc::new(Mutex::new(Token::default()));
-        let thread = Builder::new()
-            .name("relayer-stage".into())
-            .spawn(move || {
-                let rt = tokio::runtime::Builder::new_multi_thread()
-                    .enable_all()
-                    .build()
-                    .unwrap();
+/// Snapshot of the relayer subsystem, see [`RelayerStage::health`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct RelayerHealth {
+    pub connection_state: RelayerConnectionState,
+    /// Time since the last heartbeat, None if no heartbeat was received yet.
+    pub last_heartbeat_age_ms: Option<u64>,
+    /// Endpoint of the current or last connection, the primary one until connected.
+    pub endpoint: String,
+    /// Seconds until the access token expires, None if no token was issued yet.
+    pub access_token_ttl_secs: Option<i64>,
+    pub num_connect_errors: u64,
+    pub num_stream_errors: u64,
+    /// Reconnects on which the relayer advertised other TPU sockets than on the previous connection.
+    pub num_tpu_changes: u64,
+    /// State changes dropped because the channel passed to [`RelayerStage::new`] was full.
+    pub num_dropped_state_changes: u64,
+}
 
-                rt.spawn(auth_tokens_update_loop(
-                    auth_service_endpoint,
-                    access_token.clone(),
-                    cluster_info.clone(),
-                    exit.clone(),
-                ));
-                rt.block_on(Self::start(
-                    access_token,
-                    heartbeat_tx,
-                    expected_heartbeat_interval,
-                    oldest_allowed_heartbeat,
-                    packet_tx,
-                    backend_endpoint,
-                    verified_packet_tx,
-                    trust_packets,
-                    exit,
-                ));
-            })
-            .unwrap();
+/// Holds the current connection state, logging and reporting every transition the same way.
+/// Also keeps track of the heartbeats and errors reported by [`RelayerStage::health`].
+#[derive(Clone)]
+struct RelayerConnectionStateTracker {
+    state: Arc<Mutex<RelayerConnectionState>>,
+    /// When the current state was entered.
+    state_since: Arc<Mutex<Instant>>,
+    /// Random id of the current connection attempt, tagged on its logs and datapoints.
+    connection_id: Arc<Mutex<String>>,
+    active_endpoint: Arc<Mutex<String>>,
+    last_heartbeat: Arc<Mutex<Option<Instant>>>,
+    /// TPU and TPU forward sockets the relayer advertised on the last connection.
+    last_tpu_sockets: Arc<Mutex<Option<HeartbeatEvent>>>,
+    num_connect_errors: Arc<AtomicU64>,
+    num_stream_errors: Arc<AtomicU64>,
+    state_change_tx: Option<Sender<RelayerStateChange>>,
+    num_dropped_state_changes: Arc<AtomicU64>,
+    num_tpu_changes: Arc<AtomicU64>,
+}
 
+impl RelayerConnectionStateTracker {
+    fn new(initial_state: RelayerConnectionState) -> Self {
         Self {
-            t_hdls: vec![thread],
+            state: Arc::new(Mutex::new(initial_state)),
+            state_since: Arc::new(Mutex::new(Instant::now())),
+            connection_id: Arc::new(Mutex::new(String::default())),
+            active_endpoint: Arc::new(Mutex::new(String::default())),
+            last_heartbeat: Arc::new(Mutex::new(None)),
+            last_tpu_sockets: Arc::new(Mutex::new(None)),
+            num_connect_errors: Arc::new(AtomicU64::new(0)),
+            num_stream_errors: Arc::new(AtomicU64::new(0)),
+            state_change_tx: None,
+            num_dropped_state_changes: Arc::new(AtomicU64::new(0)),
+            num_tpu_changes: Arc::new(AtomicU64::new(0)),
         }
     }
 
-    pub fn join(self) -> thread::Result<()> {
-        for t in self.t_hdls {
-            t.join()?;
-        }
-        Ok(())
+    /// Streams every transition to `state_change_tx`. Sending never blocks, transitions that
+    /// don't fit in the channel are dropped and counted.
+    fn with_state_change_tx(mut self, state_change_tx: Option<Sender<RelayerStateChange>>) -> Self {
+        self.state_change_tx = state_change_tx;
+        self
     }
 
-    #[allow(clippy::too_many_arguments)]
-    async fn start(
-        access_token: Arc<Mutex<Token>>,
-        heartbeat_tx: Sender<HeartbeatEvent>,
-        expected_heartbeat_interval: Duration,
-        oldest_allowed_heartbeat: Duration,
-        packet_tx: Sender<PacketBatch>,
-        relayer_endpoint: Endpoint,
-        verified_packet_tx: Sender<(Vec<PacketBatch>, Option<SigverifyTracerPacketStats>)>,
-        trust_packets: bool,
-        exit: Arc<AtomicBool>,
-    ) {
-        const WAIT_FOR_FIRST_AUTH: Duration = Duration::from_secs(5);
+    fn with_active_endpoint(self, endpoint: String) -> Self {
+        *self.active_endpoint.lock().unwrap() = endpoint;
+        self
+    }
 
-        let mut wait_count: usize = 0;
-        let mut stream_error_count: usize = 0;
-        let mut connect_error_count: usize = 0;
-        while access_token.lock().unwrap().value.is_empty() {
-            if exit.load(Ordering::Relaxed) {
-                return;
-            }
-            wait_count += 1;
-            datapoint_info!(
-                "relayer_stage-wait_for_auth",
-                ("wait_count", wait_count, i64)
-            );
-            sleep(WAIT_FOR_FIRST_AUTH).await;
+    fn active_endpoint(&self) -> String {
+        self.active_endpoint.lock().unwrap().clone()
+    }
+
+    /// Records the endpoint, the `index`th in order of preference, a connection was established to.
+    fn set_active_endpoint(&self, index: usize, endpoint: &Endpoint) {
+        let endpoint = endpoint.uri().to_string();
+        let connection_id = self.connection_id();
+        info!(
+            "[{}] connected to relayer endpoint {} ({})",
+            connection_id, endpoint, index
+        );
+        datapoint_info!(
+            "relayer_stage-active_endpoint",
+            "connection_id" => connection_id,
+            ("endpoint", endpoint.clone(), String),
+            ("index", index, i64),
+        );
+        *self.active_endpoint.lock().unwrap() = endpoint;
+    }
+
+    /// Mints a new id for the connection attempt about to start.
+    fn new_connection_id(&self) -> String {
+        let connection_id = format!("{:08x}", rand::random::<u32>());
+        *self.connection_id.lock().unwrap() = connection_id.clone();
+        connection_id
+    }
+
+    fn connection_id(&self) -> String {
+        self.connection_id.lock().unwrap().clone()
+    }
+
+    fn get(&self) -> RelayerConnectionState {
+        *self.state.lock().unwrap()
+    }
+
+    fn state_since(&self) -> Instant {
+        *self.state_since.lock().unwrap()
+    }
+
+    fn record_heartbeat(&self) {
+        *self.last_heartbeat.lock().unwrap() = Some(Instant::now());
+    }
+
+    fn last_heartbeat_age(&self) -> Option<Duration> {
+        self.last_heartbeat
+            .lock()
+            .unwrap()
+            .map(|last_heartbeat| last_heartbeat.elapsed())
+    }
+
+    /// Records the TPU and TPU forward sockets advertised by the relayer on a new connection,
+    /// reporting them if they differ from the previous connection's so that a relayer silently
+    /// redirecting traffic is noticed.
+    fn record_tpu_sockets(&self, tpu_sockets: HeartbeatEvent) {
+        let old_tpu_sockets = self.last_tpu_sockets.lock().unwrap().replace(tpu_sockets);
+        let (old_tpu, old_tpu_forward) = match old_tpu_sockets {
+            Some(old_tpu_sockets) if old_tpu_sockets != tpu_sockets => old_tpu_sockets,
+            _ => return,
+        };
+        self.num_tpu_changes.fetch_add(1, Ordering::Relaxed);
+        let (new_tpu, new_tpu_forward) = tpu_sockets;
+        let connection_id = self.connection_id();
+        warn!(
+            "[{}] relayer changed the tpu from {} to {} and the tpu forward from {} to {}",
+            connection_id, old_tpu, new_tpu, old_tpu_forward, new_tpu_forward
+        );
+        datapoint_warn!(
+            "relayer_stage-tpu_changed",
+            "connection_id" => connection_id,
+            ("old_tpu", old_tpu.to_string(), String),
+            ("new_tpu", new_tpu.to_string(), String),
+            ("old_tpu_forward", old_tpu_forward.to_string(), String),
+            ("new_tpu_forward", new_tpu_forward.to_string(), String),
+        );
+    }
+
+    /// Returns the updated count.
+    fn record_connect_error(&self) -> u64 {
+        self.num_connect_errors.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Returns the updated count.
+    fn record_stream_error(&self) -> u64 {
+        self.num_stream_errors.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    fn transition(&self, new_state: RelayerConnectionState) {
+        let old_state = std::mem::replace(&mut *self.state.lock().unwrap(), new_state);
+        if old_state == new_state {
+            return;
         }
+        *self.state_since.lock().unwrap() = Instant::now();
 
-        let mut backoff = BackoffStrategy::new();
-        while !exit.load(Ordering::Relaxed) {
-            match relayer_endpoint.connect().await {
-                Ok(channel) => {
-                    match Self::start_consuming_relayer_packets(
-                        &mut backoff,
-                        RelayerClient::with_interceptor(
-                            channel,
-                            AuthInterceptor::new(access_token.clone()),
-                        ),
-                        &heartbeat_tx,
-                        expected_heartbeat_interval,
-                        oldest_allowed_heartbeat,
-                        &packet_tx,
-                        &verified_packet_tx,
-                        trust_packets,
-                        &exit,
-                    )
-                    .await
-                    {
-                        Ok(_) => {}
-                        Err(e) => {
-                            stream_error_count += 1;
-                            datapoint_warn!(
-                                "relayer_stage-stream_error",
-                                ("count", stream_error_count, i64),
-                                ("error", e.to_string(), String),
-                            );
-                        }
-                    }
-                }
-                Err(e) => {
-                    connect_error_count += 1;
-                    datapoint_error!(
-                        "relayer_stage-connect_error",
-                        ("count", connect_error_count, i64),
-                        ("error", e.to_string(), String),
-                    );
-                }
+        let connection_id = self.connection_id();
+        info!(
+            "[{}] relayer connection state changed from {:?} to {:?}",
+            connection_id, old_state, new_state
+        );
+        datapoint_info!(
+            "relayer_stage-connection_state",
+            "connection_id" => connection_id,
+            ("from", format!("{:?}", old_state), String),
+            ("to", format!("{:?}", new_state), String),
+        );
+        if let Some(state_change_tx) = &self.state_change_tx {
+            let state_change = RelayerStateChange {
+                from: old_state,
+                to: new_state,
+                connection_id,
+                timestamp: SystemTime::now(),
+            };
+            if state_change_tx.try_send(state_change).is_err() {
+                self.num_dropped_state_changes
+                    .fetch_add(1, Ordering::Relaxed);
             }
-            sleep(Duration::from_millis(backoff.next_wait())).await;
         }
     }
+}
 
-    async fn start_consuming_relayer_packets(
-        backoff: &mut BackoffStrategy,
-        mut client: RelayerClient<InterceptedService<Channel, AuthInterceptor>>,
-        heartbeat_tx: &Sender<HeartbeatEvent>,
-        expected_heartbeat_interval: Duration,
-        oldest_allowed_heartbeat: Duration,
-        packet_tx: &Sender<PacketBatch>,
-        verified_packet_tx: &Sender<(Vec<PacketBatch>, Option<SigverifyTracerPacketStats>)>,
-        trust_packets: bool,
-        exit: &Arc<AtomicBool>,
-    ) -> crate::proxy::Result<()> {
-        let heartbeat_event: HeartbeatEvent = {
-            let tpu_config = client
-                .get_tpu_configs(relayer::GetTpuConfigsRequest {})
-                .await?
-                .into_inner();
-            let tpu_addr = tpu_config
-                .tpu
-                .ok_or_else(|| ProxyError::MissingTpuSocket("tpu".into()))?;
-            let tpu_forward_addr = tpu_config
-                .tpu_forward
-                .ok_or_else(|| ProxyError::MissingTpuSocket("tpu_fwd".into()))?;
+/// Cheap to clone handle on the connection state, for tooling such as an admin RPC that can't hold on
+/// to the [`RelayerStage`] itself. See [`RelayerStage::state_handle`].
+#[derive(Clone)]
+pub struct RelayerStateHandle {
+    connection_state: RelayerConnectionStateTracker,
+}
 
-            let tpu_ip = IpAddr::from(tpu_addr.ip.parse::<Ipv4Addr>()?);
-            let tpu_forward_ip = IpAddr::from(tpu_forward_addr.ip.parse::<Ipv4Addr>()?);
+impl RelayerStateHandle {
+    /// Returns the current state of the connection to the relayer.
+    pub fn state(&self) -> RelayerConnectionState {
+        self.connection_state.get()
+    }
 
-            let tpu_socket = SocketAddr::new(tpu_ip, tpu_addr.port as u16);
-            let tpu_forward_socket = SocketAddr::new(tpu_forward_ip, tpu_forward_addr.port as u16);
-            (tpu_socket, tpu_forward_socket)
+    /// Returns when the connection entered its current state.
+    pub fn state_since(&self) -> Instant {
+        self.connection_state.state_since()
+    }
+
+    /// Returns true if packets are currently being streamed from the relayer.
+    pub fn is_connected(&self) -> bool {
+        self.state() == RelayerConnectionState::Streaming
+    }
+}
+
+/// Goes through the relayer endpoints in order of preference, moving on to the next one when a
+/// connection fails or drops early and back to the primary once a connection proved stable.
+struct EndpointFailover {
+    endpoints: Vec<Endpoint>,
+    current: usize,
+    /// How long a connection must last for the next attempt to start over from the primary.
+    min_uptime: Duration,
+}
+
+impl From<Endpoint> for EndpointFailover {
+    fn from(endpoint: Endpoint) -> Self {
+        Self::new(vec![endpoint], Self::MIN_UPTIME)
+    }
+}
+
+impl EndpointFailover {
+    const MIN_UPTIME: Duration = Duration::from_secs(60);
+
+    fn new(endpoints: Vec<Endpoint>, min_uptime: Duration) -> Self {
+        assert!(
+            !endpoints.is_empty(),
+            "at least one relayer endpoint is required"
+        );
+        Self {
+            endpoints,
+            current: 0,
+            min_uptime,
+        }
+    }
+
+    /// Returns the endpoint to connect to next along with its index.
+    fn current(&self) -> (usize, &Endpoint) {
+        (self.current, &self.endpoints[self.current])
+    }
+
+    /// Picks the endpoint of the next attempt given how long the last connection lasted, None if
+    /// it couldn't be established.
+    fn on_disconnect(&mut self, uptime: Option<Duration>) {
+        self.current = match uptime {
+            Some(uptime) if uptime >= self.min_uptime => 0,
+            _ => (self.current + 1) % self.endpoints.len(),
         };
+    }
+}
 
-        let packet_stream = client
-            .subscribe_packets(relayer::SubscribePacketsRequest {})
-            .await?
-            .into_inner();
+/// What to do with trusted packets when the downstream channel is full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrustedPacketBackpressure {
+    /// Wait for room in the channel, slowing down the stream from the relayer so no packet is lost.
+    Block,
+    /// Drop the batch and count it, keeping the stream and heartbeats flowing under load.
+    Drop,
+}
 
-        // assume it's all good here
-        backoff.reset();
+impl Default for TrustedPacketBackpressure {
+    fn default() -> Self {
+        Self::Block
+    }
+}
 
-        Self::consume_packet_stream(
-            heartbeat_event,
-            heartbeat_tx,
-            expected_heartbeat_interval,
-            oldest_allowed_heartbeat,
-            packet_stream,
-            packet_tx,
-            trust_packets,
-            verified_packet_tx,
-            exit,
-        )
-        .await
+/// Rate of empty messages above which the relayer is considered degraded: it's still connected and
+/// heartbeating but wasting the stream, typically because of a bug on its side.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EmptyMessageThreshold {
+    /// Max empty messages tolerated per second, counted over each metrics window.
+    pub max_per_sec: u64,
+    /// Whether to reconnect, possibly to another endpoint, once exceeded rather than only warn.
+    pub reconnect: bool,
+}
+
+/// How untrusted packets are spread over the senders of a [`PacketDistributor`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PacketDistribution {
+    /// Whole batches go to each sender in turn, which balances the load best.
+    RoundRobin,
+    /// Packets are routed by their first signature so copies of the same transaction always land
+    /// on the same sender.
+    BySignature,
+}
+
+impl Default for PacketDistribution {
+    fn default() -> Self {
+        Self::RoundRobin
     }
+}
 
-    async fn consume_packet_stream(
-        heartbeat_event: HeartbeatEvent,
-        heartbeat_tx: &Sender<HeartbeatEvent>,
-        expected_heartbeat_interval: Duration,
-        oldest_allowed_heartbeat: Duration,
-        mut packet_stream: Streaming<relayer::SubscribePacketsResponse>,
-        packet_tx: &Sender<PacketBatch>,
-        trust_packets: bool,
-        verified_packet_tx: &Sender<(Vec<PacketBatch>, Option<SigverifyTracerPacketStats>)>,
-        exit: &Arc<AtomicBool>,
-    ) -> crate::proxy::Result<()> {
-        const METRICS_TICK: Duration = Duration::from_secs(1);
+/// Spreads untrusted packets over one or more senders, each feeding its own sigverify worker, so
+/// sigverify can scale past a single stage under load.
+pub struct PacketDistributor {
+    senders: Vec<Sender<PacketBatch>>,
+    distribution: PacketDistribution,
+    next_sender: AtomicUsize,
+}
 
-        let mut relayer_stats = RelayerStageStats::default();
-        let mut metrics_tick = interval(METRICS_TICK);
+impl From<Sender<PacketBatch>> for PacketDistributor {
+    fn from(sender: Sender<PacketBatch>) -> Self {
+        Self::new(vec![sender], PacketDistribution::default())
+    }
+}
 
-        let mut heartbeat_check_interval = interval(expected_heartbeat_interval);
-        let mut last_heartbeat_ts = Instant::now();
+impl PacketDistributor {
+    pub fn new(senders: Vec<Sender<PacketBatch>>, distribution: PacketDistribution) -> Self {
+        assert!(
+            !senders.is_empty(),
+            "at least one packet sender is required"
+        );
+        Self {
+            senders,
+            distribution,
+            next_sender: AtomicUsize::new(0),
+        }
+    }
 
-        info!("connected to packet stream");
+    fn send(&self, packet_batch: PacketBatch) -> crate::proxy::Result<()> {
+        if self.senders.len() == 1 {
+            return self.senders[0]
+                .send(packet_batch)
+                .map_err(|_| ProxyError::PacketForwardError);
+        }
 
-        while !exit.load(Ordering::Relaxed) {
-            tokio::select! {
-                maybe_msg = packet_stream.message() => {
-                    let resp = maybe_msg?.ok_or(ProxyError::GrpcStreamDisconnected)?;
-                    Self::handle_relayer_packets(resp, heartbeat_event, heartbeat_tx, &mut last_heartbeat_ts, packet_tx, trust_packets, verified_packet_tx, &mut relayer_stats)?;
-                }
-                _ = heartbeat_check_interval.tick() => {
-                    if last_heartbeat_ts.elapsed() > oldest_allowed_heartbeat {
-                        return Err(ProxyError::HeartbeatExpired);
-                    }
-                }
-                _ = metrics_tick.tick() => {
-                    relayer_stats.report();
-                    relayer_stats = RelayerStageStats::default();
+        match self.distribution {
+            PacketDistribution::RoundRobin => {
+                let index = self.next_sender.fetch_add(1, Ordering::Relaxed) % self.senders.len();
+                self.senders[index]
+                    .send(packet_batch)
+                    .map_err(|_| ProxyError::PacketForwardError)
+            }
+            PacketDistribution::BySignature => {
+                let mut packets: Vec<Vec<Packet>> = vec![Vec::new(); self.senders.len()];
+                for packet in packet_batch.iter() {
+                    packets[self.sender_index(packet)].push(packet.clone());
                 }
+                self.senders
+                    .iter()
+                    .zip(packets)
+                    .filter(|(_, packets)| !packets.is_empty())
+                    .try_for_each(|(sender, packets)| {
+                        sender
+                            .send(PacketBatch::new(packets))
+                            .map_err(|_| ProxyError::PacketForwardError)
+                    })
             }
         }
+    }
 
-        Ok(())
+    /// Signatures are random, so a few of their bytes are as good as a hash. Packets too short to
+    /// hold a signature go to the first sender and get discarded by sigverify.
+    fn sender_index(&self, packet: &Packet) -> usize {
+        // the signatures follow their compact length prefix, a single byte for any count that
+        // fits in a packet
+        packet
+            .data(1..1 + SIGNATURE_BYTES)
+            .map(|signature| {
+                let mut prefix = [0u8; 8];
+                prefix.copy_from_slice(&signature[..8]);
+                (u64::from_le_bytes(prefix) % self.senders.len() as u64) as usize
+            })
+            .unwrap_or_default()
     }
+}
 
-    fn handle_relayer_packets(
-        subscribe_packets_resp: relayer::SubscribePacketsResponse,
-        heartbeat_event: HeartbeatEvent,
-        heartbeat_tx: &Sender<HeartbeatEvent>,
-        last_heartbeat_ts: &mut Instant,
-        packet_tx: &Sender<PacketBatch>,
-        trust_packets: bool,
-        verified_packet_tx: &Sender<(Vec<PacketBatch>, Option<SigverifyTracerPacketStats>)>,
-        relayer_stats: &mut RelayerStageStats,
-    ) -> crate::proxy::Result<()> {
-        match subscribe_packets_resp.msg {
-            None => {
-                saturating_add_assign!(relayer_stats.num_empty_messages, 1);
-            }
-            Some(relayer::subscribe_packets_response::Msg::Batch(proto_batch)) => {
-                let packet_batch = PacketBatch::new(
-                    proto_batch
-                        .packets
-                        .into_iter()
-                        .map(proto_packet_to_packet)
-                        .collect(),
-                );
+/// Keeps the last packet batches received from the relayer in memory, so operators can inspect what
+/// the validator was sent around a suspected bad block, see [`RelayerStage::recent_batches`].
+pub struct RecentPacketBatches {
+    capacity: usize,
+    batches: Mutex<VecDeque<PacketBatch>>,
+}
 
-                saturating_add_assign!(relayer_stats.num_packets, packet_batch.len() as u64);
+impl RecentPacketBatches {
+    /// Upper bound on the number of batches kept, whatever the configured capacity.
+    pub const MAX_CAPACITY: usize = 1_024;
 
-                if trust_packets {
-                    verified_packet_tx
-                        .send((vec![packet_batch], None))
-                        .map_err(|_| ProxyError::PacketForwardError)?;
-                } else {
-                    packet_tx
-                        .send(packet_batch)
-                        .map_err(|_| ProxyError::PacketForwardError)?;
-                }
-            }
-            Some(relayer::subscribe_packets_response::Msg::Heartbeat(_)) => {
-                saturating_add_assign!(relayer_stats.num_heartbeats, 1);
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.min(Self::MAX_CAPACITY);
+        Self {
+            capacity,
+            batches: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
 
-                *last_heartbeat_ts = Instant::now();
-                heartbeat_tx
-                    .send(heartbeat_event)
-                    .map_err(|_| ProxyError::HeartbeatChannelError)?;
-            }
+    fn push(&self, packet_batch: &PacketBatch) {
+        if self.capacity == 0 {
+            return;
         }
-        Ok(())
+        let mut batches = self.batches.lock().unwrap();
+        if batches.len() == self.capacity {
+            batches.pop_front();
+        }
+        batches.push_back(packet_batch.clone());
+    }
+
+    /// Returns the batches kept, oldest first.
+    pub fn batches(&self) -> Vec<PacketBatch> {
+        self.batches.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+/// Logs reconnect errors without flooding the logs while the relayer is down: the first few
+/// occurrences of an error are logged, then at most one per interval along with the number of
+/// occurrences suppressed in between. A different error is logged right away, after a summary of
+/// how many times the previous one was suppressed since it was last logged.
+struct ReconnectErrorLogger {
+    last_error: String,
+    num_occurrences: u64,
+    num_suppressed: u64,
+    last_logged: Option<Instant>,
+}
+
+impl ReconnectErrorLogger {
+    const NUM_UNTHROTTLED: u64 = 3;
+    const LOG_INTERVAL: Duration = Duration::from_secs(60);
+
+    fn new() -> Self {
+        Self {
+            last_error: String::default(),
+            num_occurrences: 0,
+            num_suppressed: 0,
+            last_logged: None,
+        }
+    }
+
+    /// Returns true if the error was logged.
+    fn log(&mut self, connection_id: &str, error: &impl Display, now: Instant) -> bool {
+        let messages = self.messages(error.to_string(), now);
+        for message in &messages {
+            error!("[{}] {}", connection_id, message);
+        }
+        !messages.is_empty()
+    }
+
+    /// Returns the lines to log for the error, none if it's throttled.
+    fn messages(&mut self, error: String, now: Instant) -> Vec<String> {
+        let mut messages = Vec::new();
+        if error != self.last_error {
+            if self.num_suppressed > 0 {
+                messages.push(format!(
+                    "previous relayer proxy error repeated {} times: {}",
+                    self.num_suppressed, self.last_error
+                ));
+            }
+            self.last_error = error;
+            self.num_occurrences = 0;
+            self.num_suppressed = 0;
+        }
+        self.num_occurrences += 1;
+
+        let throttled = self.num_occurrences > Self::NUM_UNTHROTTLED
+            && self.last_logged.map_or(false, |last_logged| {
+                now.saturating_duration_since(last_logged) < Self::LOG_INTERVAL
+            });
+        if throttled {
+            self.num_suppressed += 1;
+            return messages;
+        }
+
+        if self.num_suppressed > 0 {
+            messages.push(format!(
+                "relayer proxy error: {} ({} identical errors suppressed)",
+                self.last_error, self.num_suppressed
+            ));
+        } else {
+            messages.push(format!("relayer proxy error: {}", self.last_error));
+        }
+        self.num_suppressed = 0;
+        self.last_logged = Some(now);
+        messages
+    }
+}
+
+/// TCP socket options applied to the connection to the relayer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RelayerSocketConfig {
+    /// Disables Nagle's algorithm so packet stream messages aren't held back to be batched.
+    pub tcp_nodelay: bool,
+
+    /// Size of the socket send buffer in bytes, the OS default is used if unset.
+    pub send_buffer_size: Option<usize>,
+
+    /// Size of the socket receive buffer in bytes, the OS default is used if unset.
+    pub recv_buffer_size: Option<usize>,
+}
+
+impl Default for RelayerSocketConfig {
+    fn default() -> Self {
+        Self {
+            tcp_nodelay: true,
+            send_buffer_size: None,
+            recv_buffer_size: None,
+        }
+    }
+}
+
+impl RelayerSocketConfig {
+    /// Builds the connector used to establish the TCP connection to the relayer.
+    /// TLS is layered on top of it by the endpoint when configured.
+    pub(crate) fn connector(&self) -> HttpConnector {
+        let mut connector = HttpConnector::new();
+        connector.enforce_http(false);
+        connector.set_nodelay(self.tcp_nodelay);
+        connector.set_send_buffer_size(self.send_buffer_size);
+        connector.set_recv_buffer_size(self.recv_buffer_size);
+        connector
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RelayerConfig {
+    /// Address to the external auth-service responsible for generating access tokens.
+    pub auth_service_endpoint: Endpoint,
+
+    /// Address to the service refreshing access tokens, if it's separate from the auth service.
+    pub refresh_service_endpoint: Option<Endpoint>,
+
+    /// If set, auth tokens are cached in this file, only readable by its owner, so that a restart
+    /// can reuse them rather than go through the auth challenge again.
+    pub token_cache_path: Option<PathBuf>,
+
+    /// How far our clock may be off from the auth service's. Tokens are refreshed this much
+    /// earlier and their expiry is given this much leeway when validated, see
+    /// [`RelayerConfig::DEFAULT_CLOCK_SKEW_TOLERANCE`].
+    pub clock_skew_tolerance: Duration,
+
+    /// How long before they expire auth tokens get refreshed. Relayers issuing short-lived tokens
+    /// need a larger lookahead, see [`RelayerConfig::DEFAULT_AUTH_REFRESH_LOOKAHEAD`].
+    pub auth_refresh_lookahead: Duration,
+
+    /// How many times auth requests failing transiently, e.g. with `Unavailable`, are retried
+    /// before giving up on the connection to the auth service, see
+    /// [`RelayerConfig::DEFAULT_MAX_AUTH_RETRIES`].
+    pub max_auth_retries: u32,
+
+    /// Backend endpoints in order of preference, the first one being the primary. Connections fail
+    /// over to the next endpoint and go back to the primary once connected for a while.
+    pub backend_endpoints: Vec<Endpoint>,
+
+    /// Interval at which heartbeats are expected.
+    pub expected_heartbeat_interval: Duration,
+
+    /// The max tolerable age of the last heartbeat.
+    pub oldest_allowed_heartbeat: Duration,
+
+    /// If set then it will be assumed the backend verified packets so signature verification will be bypassed in the validator.
+    pub trust_packets: bool,
+
+    /// If set while trusting packets, one in this many trusted packets is still sigverified as a
+    /// spot check of the relayer, with failures reported as trust violations.
+    pub trust_audit_sample_rate: Option<u32>,
+
+    /// Whether trusted packets wait for or are dropped by a full downstream channel.
+    pub trusted_backpressure: TrustedPacketBackpressure,
+
+    /// If set, every batch of trusted packets dropped under [`TrustedPacketBackpressure::Drop`] is
+    /// reported on this channel. Events are sent without blocking, a full or disconnected receiver
+    /// missing them, so the channel can be shared with the block engine stage's.
+    pub drop_event_tx: Option<Sender<DropEvent>>,
+
+    /// TCP socket options for the connection to the relayer.
+    pub socket_config: RelayerSocketConfig,
+
+    /// How long connecting and subscribing to the relayer may take before the attempt is given up,
+    /// see [`RelayerConfig::DEFAULT_CONNECTION_TIMEOUT`].
+    pub connection_timeout: Duration,
+
+    /// If set, a spare connection to the relayer is kept subscribed in the background, its packets
+    /// discarded, and streamed from when the active one fails, skipping the reconnect.
+    pub warm_standby: bool,
+
+    /// Cap on the wait between reconnect attempts, which doubles from 100ms after each failure and
+    /// is jittered by 20% either way, see [`RelayerConfig::DEFAULT_MAX_RECONNECT_BACKOFF`].
+    pub max_reconnect_backoff: Duration,
+
+    /// If set, bounds the rate of connection attempts. Share it between stages to bound their
+    /// aggregate reconnect rate.
+    pub reconnect_limiter: Option<Arc<ReconnectRateLimiter>>,
+
+    /// If set, the packet stream is closed and re-established once it's been open this long, ahead
+    /// of intermediaries that drop long-lived streams.
+    pub max_stream_lifetime: Option<Duration>,
+
+    /// If set, a relayer sending empty messages faster than this is warned about and optionally
+    /// reconnected to.
+    pub empty_message_threshold: Option<EmptyMessageThreshold>,
+
+    /// If set, the last batches received are kept in memory for post-incident analysis, up to
+    /// [`RecentPacketBatches::MAX_CAPACITY`].
+    pub recent_batches_capacity: Option<usize>,
+
+    /// How untrusted packets are spread when there are several packet senders.
+    pub packet_distribution: PacketDistribution,
+}
+
+/// Why a [`RelayerConfig`] couldn't be built from the environment, naming the offending variable.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    #[error("missing environment variable {0}")]
+    MissingVar(&'static str),
+
+    #[error("invalid value {value:?} for environment variable {name}: {reason}")]
+    InvalidVar {
+        name: &'static str,
+        value: String,
+        reason: String,
+    },
+}
+
+impl RelayerConfig {
+    pub const DEFAULT_CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+    pub const DEFAULT_MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(1);
+    pub const DEFAULT_CLOCK_SKEW_TOLERANCE: Duration = Duration::from_secs(5);
+    pub const DEFAULT_AUTH_REFRESH_LOOKAHEAD: Duration = DEFAULT_REFRESH_LOOKAHEAD;
+    pub const DEFAULT_MAX_AUTH_RETRIES: u32 = 3;
+
+    /// Relayer url, used for both the auth service and the packet stream. Required.
+    pub const RELAYER_URL_VAR: &'static str = "RELAYER_URL";
+    /// Auth service url, if it's served separately from the relayer.
+    pub const AUTH_SERVICE_URL_VAR: &'static str = "RELAYER_AUTH_SERVICE_URL";
+    /// Interval at which heartbeats are expected, defaults to 500ms.
+    pub const EXPECTED_HEARTBEAT_INTERVAL_MS_VAR: &'static str =
+        "RELAYER_EXPECTED_HEARTBEAT_INTERVAL_MS";
+    /// Heartbeats missed before the relayer is considered gone, defaults to 3.
+    pub const MAX_FAILED_HEARTBEATS_VAR: &'static str = "RELAYER_MAX_FAILED_HEARTBEATS";
+    /// Whether to trust the relayer's packets, defaults to false.
+    pub const TRUST_PACKETS_VAR: &'static str = "RELAYER_TRUST_PACKETS";
+    /// Max lifetime of the packet stream in seconds, unbounded if unset.
+    pub const MAX_STREAM_LIFETIME_SECS_VAR: &'static str = "RELAYER_MAX_STREAM_LIFETIME_SECS";
+    /// Timeout for connecting and subscribing in milliseconds, defaults to 10s.
+    pub const CONNECTION_TIMEOUT_MS_VAR: &'static str = "RELAYER_CONNECTION_TIMEOUT_MS";
+
+    /// Builds the config from environment variables for containerized deployments, with the same
+    /// defaults as the validator's relayer arguments. Options without a variable keep their
+    /// defaults.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::from_vars(|name| env::var(name).ok())
+    }
+
+    fn from_vars(var: impl Fn(&'static str) -> Option<String>) -> Result<Self, ConfigError> {
+        fn parse<T: std::str::FromStr>(
+            name: &'static str,
+            value: Option<String>,
+        ) -> Result<Option<T>, ConfigError>
+        where
+            T::Err: Display,
+        {
+            value
+                .map(|value| {
+                    value.parse().map_err(|e: T::Err| ConfigError::InvalidVar {
+                        name,
+                        reason: e.to_string(),
+                        value,
+                    })
+                })
+                .transpose()
+        }
+
+        fn endpoint(name: &'static str, url: String) -> Result<Endpoint, ConfigError> {
+            let invalid = |reason: String| ConfigError::InvalidVar {
+                name,
+                value: url.clone(),
+                reason,
+            };
+            let endpoint =
+                Endpoint::from_shared(url.clone()).map_err(|e| invalid(e.to_string()))?;
+            if url.contains("https") {
+                endpoint
+                    .tls_config(ClientTlsConfig::new())
+                    .map_err(|e| invalid(e.to_string()))
+            } else {
+                Ok(endpoint)
+            }
+        }
+
+        let relayer_url =
+            var(Self::RELAYER_URL_VAR).ok_or(ConfigError::MissingVar(Self::RELAYER_URL_VAR))?;
+        let auth_service_endpoint = match var(Self::AUTH_SERVICE_URL_VAR) {
+            Some(url) => endpoint(Self::AUTH_SERVICE_URL_VAR, url)?,
+            None => endpoint(Self::RELAYER_URL_VAR, relayer_url.clone())?,
+        };
+        let backend_endpoint = endpoint(Self::RELAYER_URL_VAR, relayer_url)?;
+
+        let expected_heartbeat_interval_ms: u64 = parse(
+            Self::EXPECTED_HEARTBEAT_INTERVAL_MS_VAR,
+            var(Self::EXPECTED_HEARTBEAT_INTERVAL_MS_VAR),
+        )?
+        .unwrap_or(500);
+        let max_failed_heartbeats: u64 = parse(
+            Self::MAX_FAILED_HEARTBEATS_VAR,
+            var(Self::MAX_FAILED_HEARTBEATS_VAR),
+        )?
+        .unwrap_or(3);
+        let connection_timeout_ms: u64 = parse(
+            Self::CONNECTION_TIMEOUT_MS_VAR,
+            var(Self::CONNECTION_TIMEOUT_MS_VAR),
+        )?
+        .unwrap_or(Self::DEFAULT_CONNECTION_TIMEOUT.as_millis() as u64);
+        for (name, value) in [
+            (
+                Self::EXPECTED_HEARTBEAT_INTERVAL_MS_VAR,
+                expected_heartbeat_interval_ms,
+            ),
+            (Self::MAX_FAILED_HEARTBEATS_VAR, max_failed_heartbeats),
+            (Self::CONNECTION_TIMEOUT_MS_VAR, connection_timeout_ms),
+        ] {
+            if value == 0 {
+                return Err(ConfigError::InvalidVar {
+                    name,
+                    value: value.to_string(),
+                    reason: "must be greater than zero".to_string(),
+                });
+            }
+        }
+        let trust_packets =
+            parse(Self::TRUST_PACKETS_VAR, var(Self::TRUST_PACKETS_VAR))?.unwrap_or(false);
+        let max_stream_lifetime = parse(
+            Self::MAX_STREAM_LIFETIME_SECS_VAR,
+            var(Self::MAX_STREAM_LIFETIME_SECS_VAR),
+        )?
+        .map(Duration::from_secs);
+
+        Ok(Self {
+            auth_service_endpoint,
+            refresh_service_endpoint: None,
+            token_cache_path: None,
+            clock_skew_tolerance: Self::DEFAULT_CLOCK_SKEW_TOLERANCE,
+            auth_refresh_lookahead: Self::DEFAULT_AUTH_REFRESH_LOOKAHEAD,
+            max_auth_retries: Self::DEFAULT_MAX_AUTH_RETRIES,
+            backend_endpoints: vec![backend_endpoint],
+            expected_heartbeat_interval: Duration::from_millis(expected_heartbeat_interval_ms),
+            oldest_allowed_heartbeat: Duration::from_millis(
+                max_failed_heartbeats.saturating_mul(expected_heartbeat_interval_ms),
+            ),
+            trust_packets,
+            trust_audit_sample_rate: None,
+            trusted_backpressure: TrustedPacketBackpressure::default(),
+            drop_event_tx: None,
+            socket_config: RelayerSocketConfig::default(),
+            connection_timeout: Duration::from_millis(connection_timeout_ms),
+            warm_standby: false,
+            max_reconnect_backoff: Self::DEFAULT_MAX_RECONNECT_BACKOFF,
+            reconnect_limiter: None,
+            max_stream_lifetime,
+            empty_message_threshold: None,
+            recent_batches_capacity: None,
+            packet_distribution: PacketDistribution::default(),
+        })
+    }
+}
+
+/// Holds on to the latest [`RelayerConfig`] sent to a running stage until the next connection
+/// applies it. Changing the auth service or backend endpoints tears the current stream down right
+/// away, other changes, e.g. to `trust_packets`, only take effect on the next connection.
+struct RelayerConfigUpdates {
+    config_rx: Receiver<RelayerConfig>,
+    /// Endpoints of the config in use.
+    endpoints: Vec<String>,
+    pending: Option<RelayerConfig>,
+}
+
+impl RelayerConfigUpdates {
+    fn new(config_rx: Receiver<RelayerConfig>, relayer_config: &RelayerConfig) -> Self {
+        Self {
+            config_rx,
+            endpoints: Self::endpoints(relayer_config),
+            pending: None,
+        }
+    }
+
+    fn endpoints(relayer_config: &RelayerConfig) -> Vec<String> {
+        std::iter::once(&relayer_config.auth_service_endpoint)
+            .chain(&relayer_config.refresh_service_endpoint)
+            .chain(&relayer_config.backend_endpoints)
+            .map(|endpoint| endpoint.uri().to_string())
+            .collect()
+    }
+
+    /// Receives the latest config, returning true if the pending config changes endpoints so the
+    /// stream must reconnect now.
+    fn poll(&mut self) -> bool {
+        if let Some(relayer_config) = self.config_rx.try_iter().last() {
+            self.pending = Some(relayer_config);
+        }
+        self.pending.as_ref().map_or(false, |relayer_config| {
+            Self::endpoints(relayer_config) != self.endpoints
+        })
+    }
+
+    /// Returns the config to apply to the next connection, if one was received.
+    fn take_pending(&mut self) -> Option<RelayerConfig> {
+        let relayer_config = self.pending.take()?;
+        self.endpoints = Self::endpoints(&relayer_config);
+        Some(relayer_config)
+    }
+}
+
+/// What every connection made by [`RelayerStage::start`] streams with: the auth tokens, the
+/// channels heartbeats and packets are forwarded to, and the settings taken from the
+/// [`RelayerConfig`].
+struct RelayerStreamContext {
+    access_token: Arc<Mutex<Token>>,
+    /// Expiry of the refresh token held by the auth loop, for reporting.
+    refresh_token_expiry: TokenExpiry,
+    heartbeat_tx: Sender<HeartbeatEvent>,
+    expected_heartbeat_interval: Duration,
+    oldest_allowed_heartbeat: Duration,
+    packet_tx: Arc<PacketDistributor>,
+    verified_packet_tx: Sender<(Vec<PacketBatch>, Option<SigverifyTracerPacketStats>)>,
+    trust_packets: bool,
+    trust_audit_sample_rate: Option<u32>,
+    trusted_backpressure: TrustedPacketBackpressure,
+    drop_event_tx: Option<Sender<DropEvent>>,
+    socket_config: RelayerSocketConfig,
+    connection_timeout: Duration,
+    warm_standby: bool,
+    max_reconnect_backoff: Duration,
+    reconnect_limiter: Option<Arc<ReconnectRateLimiter>>,
+    max_stream_lifetime: Option<Duration>,
+    empty_message_threshold: Option<EmptyMessageThreshold>,
+    recent_batches: Option<Arc<RecentPacketBatches>>,
+    connection_state: RelayerConnectionStateTracker,
+    exit: Arc<AtomicBool>,
+}
+
+/// A connection to the relayer that fetched its TPU configs and subscribed to packets.
+struct SubscribedConnection {
+    /// Kept alongside the stream so the connection lives as long as it.
+    _client: RelayerClient<InterceptedService<Channel, AuthInterceptor>>,
+    heartbeat_event: HeartbeatEvent,
+    packet_stream: Streaming<relayer::SubscribePacketsResponse>,
+}
+
+/// A connection to the relayer ready to be streamed from, see [`RelayerStage::start`].
+enum RelayerConnection {
+    /// A new connection, to subscribe on.
+    Connected(Channel),
+    /// A promoted [`WarmStandby`], already subscribed.
+    Subscribed(SubscribedConnection),
+}
+
+/// A spare connection to one of the relayer endpoints, authenticated and subscribed in the
+/// background so that when the active connection fails the stage streams from it right away. What
+/// the relayer streams to the spare while it's idle is discarded rather than forwarded, so there
+/// are no stale packets to flush once it's promoted. The spare is re-established, and so
+/// re-authenticated with the current access token, once it's [`WarmStandby::MAX_AGE`] old or its
+/// stream fails, and right after being promoted.
+struct WarmStandby {
+    endpoint_index: usize,
+    state: Arc<Mutex<StandbyState>>,
+    promotion_requested: Arc<Notify>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+#[derive(Default)]
+struct StandbyState {
+    /// Whether a subscribed connection is idle and can be promoted.
+    ready: bool,
+    /// Where the connection is handed over to once promoted.
+    promotion_tx: Option<oneshot::Sender<SubscribedConnection>>,
+}
+
+impl WarmStandby {
+    const MAX_AGE: Duration = Duration::from_secs(60);
+
+    fn spawn(endpoint_index: usize, endpoint: Endpoint, context: &RelayerStreamContext) -> Self {
+        let state = Arc::new(Mutex::new(StandbyState::default()));
+        let promotion_requested = Arc::new(Notify::new());
+        let task = tokio::spawn({
+            let state = state.clone();
+            let promotion_requested = promotion_requested.clone();
+            let access_token = context.access_token.clone();
+            let socket_config = context.socket_config;
+            let connection_timeout = context.connection_timeout;
+            let exit = context.exit.clone();
+            async move {
+                let mut backoff = BackoffStrategy::new();
+                while !exit.load(Ordering::Relaxed) {
+                    let connection = async {
+                        let channel =
+                            RelayerStage::connect(&endpoint, &socket_config, connection_timeout)
+                                .await?;
+                        RelayerStage::subscribe(
+                            RelayerClient::with_interceptor(
+                                channel,
+                                AuthInterceptor::new(access_token.clone()),
+                            ),
+                            connection_timeout,
+                        )
+                        .await
+                    }
+                    .await;
+                    match connection {
+                        Ok(connection) => {
+                            debug!("standby relayer connection to {} ready", endpoint.uri());
+                            backoff.reset();
+                            state.lock().unwrap().ready = true;
+                            Self::idle(connection, &state, &promotion_requested).await;
+                        }
+                        Err(e) => {
+                            warn!(
+                                "error establishing standby relayer connection to {}: {}",
+                                endpoint.uri(),
+                                e
+                            );
+                            sleep(Duration::from_millis(backoff.next_wait())).await;
+                        }
+                    }
+                }
+            }
+        });
+        Self {
+            endpoint_index,
+            state,
+            promotion_requested,
+            task,
+        }
+    }
+
+    /// Discards whatever is streamed to the connection until it's promoted, its stream fails or
+    /// it's too old.
+    async fn idle(
+        mut connection: SubscribedConnection,
+        state: &Mutex<StandbyState>,
+        promotion_requested: &Notify,
+    ) {
+        let expiry = sleep(Self::MAX_AGE);
+        tokio::pin!(expiry);
+        loop {
+            tokio::select! {
+                _ = promotion_requested.notified() => {
+                    let mut state = state.lock().unwrap();
+                    if let Some(promotion_tx) = state.promotion_tx.take() {
+                        state.ready = false;
+                        // if the stage stopped waiting, the connection is dropped and replaced
+                        let _ = promotion_tx.send(connection);
+                        return;
+                    }
+                }
+                maybe_msg = connection.packet_stream.message() => {
+                    match maybe_msg {
+                        Ok(Some(_)) => {}
+                        Ok(None) => {
+                            debug!("standby relayer packet stream closed");
+                            break;
+                        }
+                        Err(e) => {
+                            debug!("standby relayer packet stream failed: {}", e);
+                            break;
+                        }
+                    }
+                }
+                _ = &mut expiry => break,
+            }
+        }
+        // under the same lock as the promotion is requested with, so it's either handed the
+        // connection or gives up
+        let mut state = state.lock().unwrap();
+        state.ready = false;
+        state.promotion_tx = None;
+    }
+
+    fn is_ready(&self) -> bool {
+        self.state.lock().unwrap().ready
+    }
+
+    /// Hands the subscribed standby connection over to be promoted, None if it isn't ready.
+    async fn take(&self) -> Option<SubscribedConnection> {
+        let (promotion_tx, promotion_rx) = oneshot::channel();
+        {
+            let mut state = self.state.lock().unwrap();
+            if !state.ready {
+                return None;
+            }
+            state.promotion_tx = Some(promotion_tx);
+        }
+        self.promotion_requested.notify_one();
+        promotion_rx.await.ok()
+    }
+}
+
+impl Drop for WarmStandby {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+pub struct RelayerStage {
+    t_hdls: Vec<JoinHandle<()>>,
+    connection_state: RelayerConnectionStateTracker,
+    access_token: Arc<Mutex<Token>>,
+    recent_batches: Option<Arc<RecentPacketBatches>>,
+    refresh_freeze: RefreshFreeze,
+}
+
+impl RelayerStage {
+    pub fn new(
+        relayer_config: RelayerConfig,
+        // The keypair stored here is used to sign auth challenges unless `challenge_signer` is set.
+        cluster_info: Arc<ClusterInfo>,
+        // Signs auth challenges with the identity in place of the keypair above, e.g. a remote
+        // signer.
+        challenge_signer: Option<Arc<dyn ChallengeSigner>>,
+        // Channel that server-sent heartbeats are piped through.
+        heartbeat_tx: Sender<HeartbeatEvent>,
+        // Channels that non-trusted streamed packets are spread over, one per sigverify worker.
+        packet_txs: Vec<Sender<PacketBatch>>,
+        // Channel that trusted streamed packets are piped through.
+        verified_packet_tx: Sender<(Vec<PacketBatch>, Option<SigverifyTracerPacketStats>)>,
+        // Channel that connection state changes are streamed to, e.g. for a live dashboard.
+        state_change_tx: Option<Sender<RelayerStateChange>>,
+        // Channel that config updates are received through, see `RelayerConfigUpdates`. The packet
+        // distribution and recent batches capacity can't be changed.
+        config_rx: Option<Receiver<RelayerConfig>>,
+        exit: Arc<AtomicBool>,
+    ) -> Self {
+        let access_token = Arc::new(Mutex::new(Token::default()));
+        let connection_state =
+            RelayerConnectionStateTracker::new(RelayerConnectionState::Authenticating)
+                .with_state_change_tx(state_change_tx)
+                .with_active_endpoint(
+                    relayer_config
+                        .backend_endpoints
+                        .first()
+                        .map(|endpoint| endpoint.uri().to_string())
+                        .unwrap_or_default(),
+                );
+        let recent_batches = relayer_config
+            .recent_batches_capacity
+            .map(|capacity| Arc::new(RecentPacketBatches::new(capacity)));
+
+        let refresh_freeze = RefreshFreeze::default();
+        let packet_tx = Arc::new(PacketDistributor::new(
+            packet_txs,
+            relayer_config.packet_distribution,
+        ));
+        let mut config_updates =
+            config_rx.map(|config_rx| RelayerConfigUpdates::new(config_rx, &relayer_config));
+
+        let thread_access_token = access_token.clone();
+        let thread_refresh_freeze = refresh_freeze.clone();
+        let refresh_token_expiry = TokenExpiry::default();
+        let thread_connection_state = connection_state.clone();
+        let thread_recent_batches = recent_batches.clone();
+        let thread = Builder::new()
+            .name("relayer-stage".into())
+            .spawn(move || {
+                let rt = tokio::runtime::Builder::new_multi_thread()
+                    .enable_all()
+                    .build()
+                    .unwrap();
+
+                let mut relayer_config = relayer_config;
+                loop {
+                    let RelayerConfig {
+                        auth_service_endpoint,
+                        refresh_service_endpoint,
+                        token_cache_path,
+                        clock_skew_tolerance,
+                        auth_refresh_lookahead,
+                        max_auth_retries,
+                        backend_endpoints,
+                        expected_heartbeat_interval,
+                        oldest_allowed_heartbeat,
+                        trust_packets,
+                        trust_audit_sample_rate,
+                        trusted_backpressure,
+                        drop_event_tx,
+                        socket_config,
+                        connection_timeout,
+                        warm_standby,
+                        max_reconnect_backoff,
+                        reconnect_limiter,
+                        max_stream_lifetime,
+                        empty_message_threshold,
+                        recent_batches_capacity: _,
+                        packet_distribution: _,
+                    } = relayer_config;
+
+                    let auth_task = rt.spawn(auth_tokens_update_loop(
+                        auth_service_endpoint,
+                        thread_access_token.clone(),
+                        cluster_info.clone(),
+                        AuthLoopConfig {
+                            refresh_service_endpoint,
+                            challenge_signer: challenge_signer.clone(),
+                            refresh_freeze: thread_refresh_freeze.clone(),
+                            refresh_token_expiry: refresh_token_expiry.clone(),
+                            token_cache: token_cache_path.map(TokenCache::new),
+                            clock_skew_tolerance,
+                            refresh_lookahead: auth_refresh_lookahead,
+                            max_auth_retries,
+                        },
+                        exit.clone(),
+                    ));
+                    let context = RelayerStreamContext {
+                        access_token: thread_access_token.clone(),
+                        refresh_token_expiry: refresh_token_expiry.clone(),
+                        heartbeat_tx: heartbeat_tx.clone(),
+                        expected_heartbeat_interval,
+                        oldest_allowed_heartbeat,
+                        packet_tx: packet_tx.clone(),
+                        verified_packet_tx: verified_packet_tx.clone(),
+                        trust_packets,
+                        trust_audit_sample_rate,
+                        trusted_backpressure,
+                        drop_event_tx,
+                        socket_config,
+                        connection_timeout,
+                        warm_standby,
+                        max_reconnect_backoff,
+                        reconnect_limiter,
+                        max_stream_lifetime,
+                        empty_message_threshold,
+                        recent_batches: thread_recent_batches.clone(),
+                        connection_state: thread_connection_state.clone(),
+                        exit: exit.clone(),
+                    };
+                    let updated_config = rt.block_on(Self::start(
+                        &context,
+                        EndpointFailover::new(backend_endpoints, EndpointFailover::MIN_UPTIME),
+                        config_updates.as_mut(),
+                    ));
+                    auth_task.abort();
+
+                    match updated_config {
+                        Some(updated_config) => {
+                            info!("applying updated relayer config");
+                            relayer_config = updated_config;
+                        }
+                        None => break,
+                    }
+                }
+            })
+            .unwrap();
+
+        Self {
+            t_hdls: vec![thread],
+            connection_state,
+            access_token,
+            recent_batches,
+            refresh_freeze,
+        }
+    }
+
+    /// Pauses proactive auth token refreshes until `until`, e.g. over a planned auth service
+    /// maintenance window. Tokens are only regenerated in the meantime if the access token expires.
+    pub fn freeze_auth_refresh_until(&self, until: DateTime<Utc>) {
+        self.refresh_freeze.freeze_until(until);
+    }
+
+    /// Lifts a freeze set by [`Self::freeze_auth_refresh_until`] ahead of its end.
+    pub fn clear_auth_refresh_freeze(&self) {
+        self.refresh_freeze.clear();
+    }
+
+    /// Returns the last packet batches received from the relayer, oldest first. Empty unless
+    /// [`RelayerConfig::recent_batches_capacity`] is set.
+    pub fn recent_batches(&self) -> Vec<PacketBatch> {
+        self.recent_batches
+            .as_ref()
+            .map(|recent_batches| recent_batches.batches())
+            .unwrap_or_default()
+    }
+
+    /// Returns the current state of the connection to the relayer.
+    pub fn state(&self) -> RelayerConnectionState {
+        self.connection_state.get()
+    }
+
+    /// Returns a handle on the connection state that outlives borrows of the stage.
+    pub fn state_handle(&self) -> RelayerStateHandle {
+        RelayerStateHandle {
+            connection_state: self.connection_state.clone(),
+        }
+    }
+
+    /// Returns the time since the relayer last sent a heartbeat, None if it never did.
+    pub fn last_heartbeat_age(&self) -> Option<Duration> {
+        self.connection_state.last_heartbeat_age()
+    }
+
+    /// Returns the number of seconds until the access token expires, None if there's no token yet.
+    pub fn access_token_ttl_secs(&self) -> Option<i64> {
+        self.access_token
+            .lock()
+            .unwrap()
+            .expires_at_utc
+            .as_ref()
+            .map(|expires_at_utc| {
+                expires_at_utc
+                    .seconds
+                    .saturating_sub(Utc::now().timestamp())
+            })
+    }
+
+    /// Returns everything about the relayer subsystem in one struct, suitable for admin RPC.
+    pub fn health(&self) -> RelayerHealth {
+        RelayerHealth {
+            connection_state: self.state(),
+            last_heartbeat_age_ms: self.last_heartbeat_age().map(|age| age.as_millis() as u64),
+            endpoint: self.connection_state.active_endpoint(),
+            access_token_ttl_secs: self.access_token_ttl_secs(),
+            num_connect_errors: self
+                .connection_state
+                .num_connect_errors
+                .load(Ordering::Relaxed),
+            num_stream_errors: self
+                .connection_state
+                .num_stream_errors
+                .load(Ordering::Relaxed),
+            num_tpu_changes: self
+                .connection_state
+                .num_tpu_changes
+                .load(Ordering::Relaxed),
+            num_dropped_state_changes: self
+                .connection_state
+                .num_dropped_state_changes
+                .load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn join(self) -> thread::Result<()> {
+        for t in self.t_hdls {
+            t.join()?;
+        }
+        Ok(())
+    }
+
+    async fn start(
+        context: &RelayerStreamContext,
+        mut relayer_endpoints: EndpointFailover,
+        mut config_updates: Option<&mut RelayerConfigUpdates>,
+    ) -> Option<RelayerConfig> {
+        const WAIT_FOR_FIRST_AUTH: Duration = Duration::from_secs(5);
+
+        let connection_state = &context.connection_state;
+
+        let mut wait_count: usize = 0;
+        while context.access_token.lock().unwrap().value.is_empty() {
+            if context.exit.load(Ordering::Relaxed) {
+                return None;
+            }
+            wait_count += 1;
+            datapoint_info!(
+                "relayer_stage-wait_for_auth",
+                ("wait_count", wait_count, i64)
+            );
+            sleep(WAIT_FOR_FIRST_AUTH).await;
+        }
+
+        let mut backoff = ExponentialBackoff::new(context.max_reconnect_backoff);
+        let mut error_logger = ReconnectErrorLogger::new();
+        let mut standby: Option<WarmStandby> = None;
+        while !context.exit.load(Ordering::Relaxed) {
+            if let Some(config_updates) = config_updates.as_deref_mut() {
+                config_updates.poll();
+                if let Some(relayer_config) = config_updates.take_pending() {
+                    return Some(relayer_config);
+                }
+            }
+            let (endpoint_index, relayer_endpoint) = relayer_endpoints.current();
+            let standby_connection = match standby
+                .as_ref()
+                .filter(|standby| standby.endpoint_index == endpoint_index)
+            {
+                Some(standby) => standby.take().await,
+                None => None,
+            };
+            if standby_connection.is_none() {
+                if let Some(reconnect_limiter) = &context.reconnect_limiter {
+                    reconnect_limiter.acquire().await;
+                }
+            }
+            let connection_id = connection_state.new_connection_id();
+
+            let connection = match standby_connection {
+                Some(connection) => {
+                    info!(
+                        "[{}] promoting the standby relayer connection",
+                        connection_id
+                    );
+                    datapoint_info!(
+                        "relayer_stage-standby_promoted",
+                        "connection_id" => connection_id,
+                        ("endpoint_index", endpoint_index, i64),
+                    );
+                    Ok(RelayerConnection::Subscribed(connection))
+                }
+                None => {
+                    connection_state.transition(RelayerConnectionState::Connecting);
+                    Self::connect(
+                        relayer_endpoint,
+                        &context.socket_config,
+                        context.connection_timeout,
+                    )
+                    .await
+                    .map(RelayerConnection::Connected)
+                }
+            };
+            let stream_result = match connection {
+                Ok(connection) => {
+                    connection_state.set_active_endpoint(endpoint_index, relayer_endpoint);
+                    let connected_at = Instant::now();
+                    if context.warm_standby
+                        && standby
+                            .as_ref()
+                            .map_or(true, |standby| standby.endpoint_index != endpoint_index)
+                    {
+                        standby = Some(WarmStandby::spawn(
+                            endpoint_index,
+                            relayer_endpoint.clone(),
+                            context,
+                        ));
+                    }
+                    let result = match connection {
+                        RelayerConnection::Connected(channel) => {
+                            connection_state.transition(RelayerConnectionState::Subscribing);
+                            Self::start_consuming_relayer_packets(
+                                context,
+                                &mut backoff,
+                                RelayerClient::with_interceptor(
+                                    channel,
+                                    AuthInterceptor::new(context.access_token.clone()),
+                                ),
+                                config_updates.as_deref_mut(),
+                            )
+                            .await
+                        }
+                        RelayerConnection::Subscribed(connection) => {
+                            Self::stream_subscribed(
+                                context,
+                                &mut backoff,
+                                connection,
+                                config_updates.as_deref_mut(),
+                            )
+                            .await
+                        }
+                    };
+                    Some((connected_at, result))
+                }
+                Err(e) => {
+                    error_logger.log(&connection_id, &e, Instant::now());
+                    let connect_error_count = connection_state.record_connect_error();
+                    datapoint_error!(
+                        "relayer_stage-connect_error",
+                        "connection_id" => connection_id,
+                        ("count", connect_error_count, i64),
+                        ("error", e.to_string(), String),
+                    );
+                    relayer_endpoints.on_disconnect(None);
+                    None
+                }
+            };
+
+            if let Some((connected_at, result)) = stream_result {
+                match result {
+                    Ok(_) => {}
+                    Err(e @ ProxyError::PacketForwardError)
+                    | Err(e @ ProxyError::HeartbeatChannelError) => {
+                        connection_state.transition(RelayerConnectionState::Fatal);
+                        datapoint_error!(
+                            "relayer_stage-fatal_error",
+                            "connection_id" => connection_id,
+                            ("error", e.to_string(), String),
+                        );
+                        return None;
+                    }
+                    Err(e) => {
+                        error_logger.log(&connection_id, &e, Instant::now());
+                        let stream_error_count = connection_state.record_stream_error();
+                        datapoint_warn!(
+                            "relayer_stage-stream_error",
+                            "connection_id" => connection_id,
+                            ("count", stream_error_count, i64),
+                            ("error", e.to_string(), String),
+                        );
+                    }
+                }
+                relayer_endpoints.on_disconnect(Some(connected_at.elapsed()));
+            }
+            connection_state.transition(RelayerConnectionState::Backoff);
+            // a ready standby takes over right away, as long as it's for the endpoint connected to
+            // next
+            let next_endpoint_index = relayer_endpoints.current().0;
+            if !standby.as_ref().map_or(false, |standby| {
+                standby.endpoint_index == next_endpoint_index && standby.is_ready()
+            }) {
+                sleep(Duration::from_millis(backoff.next_wait())).await;
+            }
+        }
+        None
+    }
+
+    /// Connects to the relayer, giving up after `connection_timeout`.
+    async fn connect(
+        endpoint: &Endpoint,
+        socket_config: &RelayerSocketConfig,
+        connection_timeout: Duration,
+    ) -> crate::proxy::Result<Channel> {
+        timeout(
+            connection_timeout,
+            endpoint.connect_with_connector(socket_config.connector()),
+        )
+        .await
+        .map_err(|_| ProxyError::RelayerConnectionTimeout)?
+        .map_err(|e| ProxyError::ConnectionError(e.to_string()))
+    }
+
+    async fn start_consuming_relayer_packets(
+        context: &RelayerStreamContext,
+        backoff: &mut ExponentialBackoff,
+        client: RelayerClient<InterceptedService<Channel, AuthInterceptor>>,
+        config_updates: Option<&mut RelayerConfigUpdates>,
+    ) -> crate::proxy::Result<()> {
+        let connection = Self::subscribe(client, context.connection_timeout).await?;
+        Self::stream_subscribed(context, backoff, connection, config_updates).await
+    }
+
+    /// Streams from a connection that's already subscribed.
+    async fn stream_subscribed(
+        context: &RelayerStreamContext,
+        backoff: &mut ExponentialBackoff,
+        connection: SubscribedConnection,
+        config_updates: Option<&mut RelayerConfigUpdates>,
+    ) -> crate::proxy::Result<()> {
+        context
+            .connection_state
+            .record_tpu_sockets(connection.heartbeat_event);
+
+        // assume it's all good here
+        backoff.reset();
+        context
+            .connection_state
+            .transition(RelayerConnectionState::Streaming);
+
+        Self::consume_packet_stream(
+            context,
+            connection.heartbeat_event,
+            connection.packet_stream,
+            config_updates,
+        )
+        .await
+    }
+
+    /// Fetches the TPU configs and subscribes to packets, giving up on either after
+    /// `connection_timeout`.
+    async fn subscribe(
+        mut client: RelayerClient<InterceptedService<Channel, AuthInterceptor>>,
+        connection_timeout: Duration,
+    ) -> crate::proxy::Result<SubscribedConnection> {
+        let heartbeat_event: HeartbeatEvent = {
+            let tpu_config = timeout(
+                connection_timeout,
+                client.get_tpu_configs(relayer::GetTpuConfigsRequest {}),
+            )
+            .await
+            .map_err(|_| ProxyError::TpuConfigTimeout)??
+            .into_inner();
+            let tpu_addr = tpu_config
+                .tpu
+                .ok_or_else(|| ProxyError::MissingTpuSocket("tpu".into()))?;
+            let tpu_forward_addr = tpu_config
+                .tpu_forward
+                .ok_or_else(|| ProxyError::MissingTpuSocket("tpu_fwd".into()))?;
+
+            let tpu_ip = IpAddr::from(tpu_addr.ip.parse::<Ipv4Addr>()?);
+            let tpu_forward_ip = IpAddr::from(tpu_forward_addr.ip.parse::<Ipv4Addr>()?);
+
+            let tpu_socket = SocketAddr::new(tpu_ip, tpu_addr.port as u16);
+            let tpu_forward_socket = SocketAddr::new(tpu_forward_ip, tpu_forward_addr.port as u16);
+            (tpu_socket, tpu_forward_socket)
+        };
+
+        let packet_stream = timeout(
+            connection_timeout,
+            client.subscribe_packets(relayer::SubscribePacketsRequest {}),
+        )
+        .await
+        .map_err(|_| ProxyError::SubscribePacketsTimeout)??
+        .into_inner();
+
+        Ok(SubscribedConnection {
+            _client: client,
+            heartbeat_event,
+            packet_stream,
+        })
+    }
+
+    async fn consume_packet_stream(
+        context: &RelayerStreamContext,
+        heartbeat_event: HeartbeatEvent,
+        mut packet_stream: Streaming<relayer::SubscribePacketsResponse>,
+        mut config_updates: Option<&mut RelayerConfigUpdates>,
+    ) -> crate::proxy::Result<()> {
+        const METRICS_TICK: Duration = Duration::from_secs(1);
+        const CONFIG_UPDATES_TICK: Duration = Duration::from_secs(1);
+
+        let connection_state = &context.connection_state;
+        let connection_id = connection_state.connection_id();
+        let mut relayer_stats = RelayerStageStats::default();
+        let mut trusted_packet_window = TrustedPacketWindow::new();
+        let mut metrics_tick = interval(METRICS_TICK);
+        let mut config_updates_tick = interval(CONFIG_UPDATES_TICK);
+
+        let stream_expiry = sleep(context.max_stream_lifetime.unwrap_or_default());
+        tokio::pin!(stream_expiry);
+
+        let mut heartbeat_check_interval = interval(context.expected_heartbeat_interval);
+        let mut last_heartbeat_ts = Instant::now();
+
+        info!("[{}] connected to packet stream", connection_id);
+
+        while !context.exit.load(Ordering::Relaxed) {
+            tokio::select! {
+                maybe_msg = packet_stream.message() => {
+                    let resp = maybe_msg?.ok_or(ProxyError::GrpcStreamDisconnected)?;
+                    Self::handle_relayer_packets(context, resp, heartbeat_event, &mut last_heartbeat_ts, &mut relayer_stats)?;
+                    if let Some(empty_message_threshold) = context.empty_message_threshold {
+                        Self::check_empty_messages(empty_message_threshold, &relayer_stats, &connection_id)?;
+                    }
+                }
+                _ = heartbeat_check_interval.tick() => {
+                    if last_heartbeat_ts.elapsed() > context.oldest_allowed_heartbeat {
+                        return Err(ProxyError::HeartbeatExpired);
+                    }
+                }
+                _ = metrics_tick.tick() => {
+                    relayer_stats.report(&connection_id);
+                    trusted_packet_window.record(&relayer_stats);
+                    trusted_packet_window.report(&connection_id);
+                    Self::report_token_expiry(&connection_id, &context.access_token, &context.refresh_token_expiry);
+                    relayer_stats = RelayerStageStats::default();
+                }
+                _ = config_updates_tick.tick(), if config_updates.is_some() => {
+                    if config_updates.as_deref_mut().map_or(false, RelayerConfigUpdates::poll) {
+                        info!(
+                            "[{}] relayer endpoints changed, reconnecting",
+                            connection_id
+                        );
+                        relayer_stats.report(&connection_id);
+                        return Ok(());
+                    }
+                }
+                _ = &mut stream_expiry, if context.max_stream_lifetime.is_some() => {
+                    info!(
+                        "[{}] packet stream reached its max lifetime, reconnecting",
+                        connection_id
+                    );
+                    saturating_add_assign!(relayer_stats.num_stream_recycles, 1);
+                    relayer_stats.report(&connection_id);
+                    return Ok(());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reports how long until the auth tokens expire, an early warning of a stuck refresh loop.
+    /// Tokens without an expiry, e.g. before the first auth, are reported as -1.
+    fn report_token_expiry(
+        connection_id: &str,
+        access_token: &Mutex<Token>,
+        refresh_token_expiry: &TokenExpiry,
+    ) {
+        let now = Utc::now().timestamp();
+        datapoint_info!(
+            "relayer_stage-token_expiry",
+            "connection_id" => connection_id,
+            (
+                "access_token_secs",
+                token_expiry_secs(&access_token.lock().unwrap(), now),
+                i64
+            ),
+            ("refresh_token_secs", refresh_token_expiry.secs(now), i64),
+        );
+    }
+
+    fn handle_relayer_packets(
+        context: &RelayerStreamContext,
+        subscribe_packets_resp: relayer::SubscribePacketsResponse,
+        heartbeat_event: HeartbeatEvent,
+        last_heartbeat_ts: &mut Instant,
+        relayer_stats: &mut RelayerStageStats,
+    ) -> crate::proxy::Result<()> {
+        match subscribe_packets_resp.msg {
+            None => {
+                saturating_add_assign!(relayer_stats.num_empty_messages, 1);
+            }
+            Some(relayer::subscribe_packets_response::Msg::Batch(proto_batch)) => {
+                if let Some(latency) =
+                    Self::packet_latency(subscribe_packets_resp.header.as_ref(), SystemTime::now())
+                {
+                    // only fails for latencies past the histogram's max, which are skipped
+                    let _ = relayer_stats
+                        .packet_latency_us_hist
+                        .increment(latency.as_micros() as u64);
+                }
+                let packet_batch = PacketBatch::new(
+                    proto_batch
+                        .packets
+                        .into_iter()
+                        .map(proto_packet_to_packet)
+                        .collect(),
+                );
+
+                saturating_add_assign!(relayer_stats.num_packets, packet_batch.len() as u64);
+                if let Some(recent_batches) = &context.recent_batches {
+                    recent_batches.push(&packet_batch);
+                }
+
+                if context.trust_packets {
+                    saturating_add_assign!(
+                        relayer_stats.num_trusted_packets,
+                        packet_batch.len() as u64
+                    );
+                    if let Some(sample_rate) = context.trust_audit_sample_rate {
+                        Self::audit_trusted_packets(
+                            &packet_batch,
+                            sample_rate,
+                            &context.connection_state,
+                            relayer_stats,
+                        );
+                    }
+                    Self::forward_trusted_packets(
+                        packet_batch,
+                        context.trusted_backpressure,
+                        &context.verified_packet_tx,
+                        context.drop_event_tx.as_ref(),
+                        relayer_stats,
+                    )?;
+                } else {
+                    saturating_add_assign!(
+                        relayer_stats.num_untrusted_packets,
+                        packet_batch.len() as u64
+                    );
+                    context.packet_tx.send(packet_batch)?;
+                }
+            }
+            Some(relayer::subscribe_packets_response::Msg::Heartbeat(_)) => {
+                saturating_add_assign!(relayer_stats.num_heartbeats, 1);
+
+                *last_heartbeat_ts = Instant::now();
+                context.connection_state.record_heartbeat();
+                context
+                    .heartbeat_tx
+                    .send(heartbeat_event)
+                    .map_err(|_| ProxyError::HeartbeatChannelError)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns how long before `now` the relayer stamped a message, None if it isn't stamped or the
+    /// stamp is ahead of the local clock.
+    fn packet_latency(header: Option<&Header>, now: SystemTime) -> Option<Duration> {
+        let ts = header?.ts.as_ref()?;
+        let sent_at = UNIX_EPOCH.checked_add(Duration::new(
+            u64::try_from(ts.seconds).ok()?,
+            u32::try_from(ts.nanos).ok()?,
+        ))?;
+        now.duration_since(sent_at).ok()
+    }
+
+    /// Warns once more empty messages than the threshold allows were received since the last
+    /// metrics tick, failing the stream so it reconnects if the threshold says to.
+    fn check_empty_messages(
+        empty_message_threshold: EmptyMessageThreshold,
+        relayer_stats: &RelayerStageStats,
+        connection_id: &str,
+    ) -> crate::proxy::Result<()> {
+        if relayer_stats.num_empty_messages != empty_message_threshold.max_per_sec.saturating_add(1)
+        {
+            return Ok(());
+        }
+        warn!(
+            "[{}] relayer sent more than {} empty messages in a second",
+            connection_id, empty_message_threshold.max_per_sec
+        );
+        datapoint_warn!(
+            "relayer_stage-empty_message_threshold_exceeded",
+            "connection_id" => connection_id,
+            ("max_per_sec", empty_message_threshold.max_per_sec, i64),
+            ("reconnect", empty_message_threshold.reconnect, bool),
+        );
+        if empty_message_threshold.reconnect {
+            return Err(ProxyError::TooManyEmptyMessages(
+                relayer_stats.num_empty_messages,
+            ));
+        }
+        Ok(())
+    }
+
+    fn forward_trusted_packets(
+        packet_batch: PacketBatch,
+        trusted_backpressure: TrustedPacketBackpressure,
+        verified_packet_tx: &Sender<(Vec<PacketBatch>, Option<SigverifyTracerPacketStats>)>,
+        drop_event_tx: Option<&Sender<DropEvent>>,
+        relayer_stats: &mut RelayerStageStats,
+    ) -> crate::proxy::Result<()> {
+        match trusted_backpressure {
+            TrustedPacketBackpressure::Block => verified_packet_tx
+                .send((vec![packet_batch], None))
+                .map_err(|_| ProxyError::PacketForwardError),
+            TrustedPacketBackpressure::Drop => {
+                let num_packets = packet_batch.len();
+                match verified_packet_tx.try_send((vec![packet_batch], None)) {
+                    Ok(()) => Ok(()),
+                    Err(TrySendError::Full(_)) => {
+                        saturating_add_assign!(
+                            relayer_stats.num_trusted_dropped,
+                            num_packets as u64
+                        );
+                        BlockEngineStage::report_drop(
+                            drop_event_tx,
+                            String::default(),
+                            DropReason::Backpressure { num_packets },
+                        );
+                        Ok(())
+                    }
+                    Err(TrySendError::Disconnected(_)) => Err(ProxyError::PacketForwardError),
+                }
+            }
+        }
+    }
+
+    /// Sigverifies a random sample of one in `sample_rate` trusted packets, a failure meaning the
+    /// relayer forwarded a packet it shouldn't have.
+    fn audit_trusted_packets(
+        packet_batch: &PacketBatch,
+        sample_rate: u32,
+        connection_state: &RelayerConnectionStateTracker,
+        relayer_stats: &mut RelayerStageStats,
+    ) {
+        let mut rng = rand::thread_rng();
+        for packet in packet_batch.iter() {
+            if !rng.gen_ratio(1, sample_rate.max(1)) {
+                continue;
+            }
+            saturating_add_assign!(relayer_stats.num_trust_audited_packets, 1);
+
+            if !verify_packet(&mut packet.clone(), false) {
+                saturating_add_assign!(relayer_stats.num_trust_violations, 1);
+                error!(
+                    "[{}] trusted packet from the relayer failed sigverify",
+                    connection_state.connection_id()
+                );
+                datapoint_error!(
+                    "relayer_stage-trust_violation",
+                    "connection_id" => connection_state.connection_id(),
+                    ("count", relayer_stats.num_trust_violations, i64),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crossbeam_channel::{bounded, unbounded, Receiver},
+        hyper::service::Service,
+        jito_protos::proto::{
+            packet as proto_packet,
+            relayer::relayer_server::{Relayer, RelayerServer},
+            shared::Socket,
+        },
+        solana_perf::packet::Packet,
+        solana_sdk::{hash::Hash, pubkey::Pubkey, signature::Keypair, system_transaction},
+        std::{collections::HashMap, pin::Pin},
+        tokio::net::TcpListener,
+        tokio_stream::{Stream, StreamExt},
+        tonic::{transport::Server, Request, Response, Status},
+    };
+
+    /// Serves the TPU configs, advertising `tpu_port` or 8000 if unset, and a packet stream with a
+    /// single message, which then either ends or stays open. The message is empty unless
+    /// `tag_batches` is set, in which case it's a batch of one packet holding the number of earlier
+    /// subscriptions. Either call can be made to never answer.
+    #[derive(Default)]
+    struct MockRelayer {
+        keep_stream_open: bool,
+        tpu_port: Option<u16>,
+        stall_tpu_configs: bool,
+        stall_subscribe_packets: bool,
+        tag_batches: bool,
+        num_subscriptions: Arc<AtomicUsize>,
+    }
+
+    #[tonic::async_trait]
+    impl Relayer for MockRelayer {
+        async fn get_tpu_configs(
+            &self,
+            _request: Request<relayer::GetTpuConfigsRequest>,
+        ) -> Result<Response<relayer::GetTpuConfigsResponse>, Status> {
+            if self.stall_tpu_configs {
+                futures::future::pending::<()>().await;
+            }
+            let socket = Socket {
+                ip: Ipv4Addr::LOCALHOST.to_string(),
+                port: self.tpu_port.unwrap_or(8000).into(),
+            };
+            Ok(Response::new(relayer::GetTpuConfigsResponse {
+                tpu: Some(socket.clone()),
+                tpu_forward: Some(socket),
+            }))
+        }
+
+        type SubscribePacketsStream = Pin<
+            Box<
+                dyn Stream<Item = Result<relayer::SubscribePacketsResponse, Status>>
+                    + Send
+                    + Sync
+                    + 'static,
+            >,
+        >;
+
+        async fn subscribe_packets(
+            &self,
+            _request: Request<relayer::SubscribePacketsRequest>,
+        ) -> Result<Response<Self::SubscribePacketsStream>, Status> {
+            if self.stall_subscribe_packets {
+                futures::future::pending::<()>().await;
+            }
+            let subscription = self.num_subscriptions.fetch_add(1, Ordering::Relaxed);
+            let message = if self.tag_batches {
+                relayer::SubscribePacketsResponse {
+                    msg: Some(relayer::subscribe_packets_response::Msg::Batch(
+                        proto_packet::PacketBatch {
+                            packets: vec![proto_packet::Packet {
+                                data: vec![subscription as u8],
+                                meta: Some(proto_packet::Meta {
+                                    size: 1,
+                                    ..proto_packet::Meta::default()
+                                }),
+                            }],
+                        },
+                    )),
+                    ..relayer::SubscribePacketsResponse::default()
+                }
+            } else {
+                relayer::SubscribePacketsResponse::default()
+            };
+            let stream = tokio_stream::iter(vec![Ok(message)]);
+            if self.keep_stream_open {
+                Ok(Response::new(Box::pin(
+                    stream.chain(tokio_stream::pending()),
+                )))
+            } else {
+                Ok(Response::new(Box::pin(stream)))
+            }
+        }
+    }
+
+    /// Serves `relayer` on a local port, returning the endpoint it's reachable at.
+    async fn spawn_mock_relayer(relayer: MockRelayer) -> Endpoint {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint =
+            Endpoint::from_shared(format!("http://{}", listener.local_addr().unwrap())).unwrap();
+        let incoming = Box::pin(futures::stream::unfold(listener, |listener| async move {
+            let stream = listener.accept().await.map(|(stream, _)| stream);
+            Some((stream, listener))
+        }));
+        tokio::spawn(
+            Server::builder()
+                .add_service(RelayerServer::new(relayer))
+                .serve_with_incoming(incoming),
+        );
+        endpoint
+    }
+
+    /// Streams with the default settings, which tests override through `context`, to channels
+    /// kept open as long as the stage lives.
+    struct TestStage {
+        context: RelayerStreamContext,
+        _heartbeat_rx: Receiver<HeartbeatEvent>,
+        packet_rx: Receiver<PacketBatch>,
+        verified_packet_rx: Receiver<(Vec<PacketBatch>, Option<SigverifyTracerPacketStats>)>,
+    }
+
+    impl TestStage {
+        fn new(connection_state: RelayerConnectionStateTracker) -> Self {
+            let (heartbeat_tx, heartbeat_rx) = unbounded();
+            let (packet_tx, packet_rx) = unbounded();
+            let (verified_packet_tx, verified_packet_rx) = unbounded();
+            Self {
+                context: RelayerStreamContext {
+                    access_token: Arc::new(Mutex::new(Token {
+                        value: "token".to_string(),
+                        expires_at_utc: None,
+                    })),
+                    refresh_token_expiry: TokenExpiry::default(),
+                    heartbeat_tx,
+                    expected_heartbeat_interval: Duration::from_millis(500),
+                    oldest_allowed_heartbeat: Duration::from_secs(1),
+                    packet_tx: Arc::new(packet_tx.into()),
+                    verified_packet_tx,
+                    trust_packets: false,
+                    trust_audit_sample_rate: None,
+                    trusted_backpressure: TrustedPacketBackpressure::default(),
+                    drop_event_tx: None,
+                    socket_config: RelayerSocketConfig::default(),
+                    connection_timeout: RelayerConfig::DEFAULT_CONNECTION_TIMEOUT,
+                    warm_standby: false,
+                    max_reconnect_backoff: RelayerConfig::DEFAULT_MAX_RECONNECT_BACKOFF,
+                    reconnect_limiter: None,
+                    max_stream_lifetime: None,
+                    empty_message_threshold: None,
+                    recent_batches: None,
+                    connection_state,
+                    exit: Arc::new(AtomicBool::new(false)),
+                },
+                _heartbeat_rx: heartbeat_rx,
+                packet_rx,
+                verified_packet_rx,
+            }
+        }
+
+        /// Runs [`RelayerStage::start`] without config updates until stopped.
+        async fn start(&self, relayer_endpoints: impl Into<EndpointFailover>) {
+            RelayerStage::start(&self.context, relayer_endpoints.into(), None).await;
+        }
+
+        fn stop(&self) {
+            self.context.exit.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Collects the state changes streamed by a [`RelayerConnectionStateTracker`], for tests to
+    /// follow the states it went through.
+    struct StateChanges {
+        initial_state: RelayerConnectionState,
+        state_change_rx: Receiver<RelayerStateChange>,
+        received: Mutex<Vec<RelayerStateChange>>,
+    }
+
+    impl StateChanges {
+        /// Returns a tracker starting in `initial_state` along with the collector of its state
+        /// changes.
+        fn track(initial_state: RelayerConnectionState) -> (RelayerConnectionStateTracker, Self) {
+            let (state_change_tx, state_change_rx) = unbounded();
+            let connection_state = RelayerConnectionStateTracker::new(initial_state)
+                .with_state_change_tx(Some(state_change_tx));
+            let state_changes = Self {
+                initial_state,
+                state_change_rx,
+                received: Mutex::new(vec![]),
+            };
+            (connection_state, state_changes)
+        }
+
+        /// Returns every state the tracker went through so far, starting with the initial one.
+        fn states(&self) -> Vec<RelayerConnectionState> {
+            let mut received = self.received.lock().unwrap();
+            received.extend(self.state_change_rx.try_iter());
+            std::iter::once(self.initial_state)
+                .chain(received.iter().map(|state_change| state_change.to))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_socket_config_applied_to_connector() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+
+            for tcp_nodelay in [true, false] {
+                let mut connector = RelayerSocketConfig {
+                    tcp_nodelay,
+                    send_buffer_size: Some(64 * 1024),
+                    recv_buffer_size: Some(64 * 1024),
+                }
+                .connector();
+                // https endpoints must be accepted since TLS is layered on top of the connector
+                let stream = connector
+                    .call(format!("https://{}", addr).parse().unwrap())
+                    .await
+                    .unwrap();
+                assert_eq!(stream.nodelay().unwrap(), tcp_nodelay);
+            }
+        });
+    }
+
+    #[test]
+    fn test_socket_config_default_enables_nodelay() {
+        assert!(RelayerSocketConfig::default().tcp_nodelay);
+    }
+
+    #[test]
+    fn test_relayer_config_from_vars() {
+        let from_vars = |vars: &HashMap<&str, &str>| {
+            RelayerConfig::from_vars(|name| vars.get(name).map(|value| value.to_string()))
+        };
+        let mut vars = HashMap::from([
+            (RelayerConfig::RELAYER_URL_VAR, "http://relayer:11226"),
+            (RelayerConfig::MAX_FAILED_HEARTBEATS_VAR, "5"),
+            (RelayerConfig::TRUST_PACKETS_VAR, "true"),
+            (RelayerConfig::MAX_STREAM_LIFETIME_SECS_VAR, "600"),
+        ]);
+        assert_eq!(
+            from_vars(&vars).unwrap().connection_timeout,
+            RelayerConfig::DEFAULT_CONNECTION_TIMEOUT
+        );
+        vars.insert(RelayerConfig::CONNECTION_TIMEOUT_MS_VAR, "2500");
+        let config = from_vars(&vars).unwrap();
+        assert_eq!(
+            config.auth_service_endpoint.uri().to_string(),
+            "http://relayer:11226/"
+        );
+        assert_eq!(
+            config.backend_endpoints[0].uri().to_string(),
+            "http://relayer:11226/"
+        );
+        assert_eq!(
+            config.expected_heartbeat_interval,
+            Duration::from_millis(500)
+        );
+        assert_eq!(
+            config.oldest_allowed_heartbeat,
+            Duration::from_millis(2_500)
+        );
+        assert!(config.trust_packets);
+        assert_eq!(config.max_stream_lifetime, Some(Duration::from_secs(600)));
+        assert_eq!(config.connection_timeout, Duration::from_millis(2_500));
+
+        vars.insert(RelayerConfig::CONNECTION_TIMEOUT_MS_VAR, "0");
+        assert!(matches!(
+            from_vars(&vars),
+            Err(ConfigError::InvalidVar { name, .. })
+                if name == RelayerConfig::CONNECTION_TIMEOUT_MS_VAR
+        ));
+        vars.remove(RelayerConfig::CONNECTION_TIMEOUT_MS_VAR);
+
+        vars.insert(RelayerConfig::TRUST_PACKETS_VAR, "yes");
+        assert!(matches!(
+            from_vars(&vars),
+            Err(ConfigError::InvalidVar { name, .. }) if name == RelayerConfig::TRUST_PACKETS_VAR
+        ));
+
+        vars.remove(RelayerConfig::RELAYER_URL_VAR);
+        assert_eq!(
+            from_vars(&vars).unwrap_err(),
+            ConfigError::MissingVar(RelayerConfig::RELAYER_URL_VAR)
+        );
+    }
+
+    #[test]
+    fn test_packet_distributor_spreads_packets() {
+        let (senders, receivers): (Vec<_>, Vec<_>) = (0..3).map(|_| unbounded()).unzip();
+        let packet = || {
+            let tx = system_transaction::transfer(
+                &Keypair::new(),
+                &Pubkey::new_unique(),
+                1,
+                Hash::default(),
+            );
+            Packet::from_data(None, tx).unwrap()
+        };
+
+        let distributor = PacketDistributor::new(senders.clone(), PacketDistribution::RoundRobin);
+        for _ in 0..6 {
+            distributor.send(PacketBatch::new(vec![packet()])).unwrap();
+        }
+        for receiver in &receivers {
+            assert_eq!(receiver.try_iter().count(), 2);
+        }
+
+        let distributor = PacketDistributor::new(senders, PacketDistribution::BySignature);
+        let packets: Vec<Packet> = (0..64).map(|_| packet()).collect();
+        distributor.send(PacketBatch::new(packets.clone())).unwrap();
+        // the same packets are routed the same way
+        distributor.send(PacketBatch::new(packets)).unwrap();
+        let received: Vec<Vec<Packet>> = receivers
+            .iter()
+            .map(|receiver| {
+                receiver
+                    .try_iter()
+                    .flat_map(|batch| batch.iter().cloned().collect::<Vec<_>>())
+                    .collect()
+            })
+            .collect();
+        assert_eq!(received.iter().map(Vec::len).sum::<usize>(), 128);
+        for packets in &received {
+            assert!(!packets.is_empty());
+            let (first, second) = packets.split_at(packets.len() / 2);
+            assert_eq!(first, second);
+        }
+
+        // a single sender gets everything whatever the distribution
+        let (sender, receiver) = unbounded();
+        let distributor = PacketDistributor::new(vec![sender], PacketDistribution::BySignature);
+        distributor
+            .send(PacketBatch::new(vec![packet(), packet()]))
+            .unwrap();
+        assert_eq!(receiver.recv().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_trusted_packet_ratio() {
+        let mut window = TrustedPacketWindow::new();
+        assert_eq!(window.trusted_ratio(), None);
+
+        let stats = |num_trusted_packets, num_untrusted_packets| RelayerStageStats {
+            num_trusted_packets,
+            num_untrusted_packets,
+            ..RelayerStageStats::default()
+        };
+        window.record(&stats(30, 10));
+        window.record(&stats(50, 10));
+        assert_eq!(window.totals(), (80, 20));
+        assert_eq!(window.trusted_ratio(), Some(0.8));
+
+        // the oldest samples fall out of the window
+        for _ in 0..TrustedPacketWindow::NUM_SAMPLES {
+            window.record(&stats(1, 3));
+        }
+        assert_eq!(window.trusted_ratio(), Some(0.25));
+    }
+
+    #[test]
+    fn test_trust_violation_detected_by_audit() {
+        let mut stage = TestStage::new(RelayerConnectionStateTracker::new(
+            RelayerConnectionState::Streaming,
+        ));
+        stage.context.trust_packets = true;
+        stage.context.trust_audit_sample_rate = Some(1);
+        let mut relayer_stats = RelayerStageStats::default();
+
+        let proto_packet = |data: Vec<u8>| proto_packet::Packet {
+            meta: Some(proto_packet::Meta {
+                size: data.len() as _,
+                ..proto_packet::Meta::default()
+            }),
+            data,
+        };
+        let tx = system_transaction::transfer(
+            &Keypair::new(),
+            &Pubkey::new_unique(),
+            1,
+            Hash::default(),
+        );
+        let valid = bincode::serialize(&tx).unwrap();
+        // flip a bit of the signature, which starts right after its 1 byte length prefix
+        let mut invalid = valid.clone();
+        invalid[1] ^= 1;
+
+        let resp = relayer::SubscribePacketsResponse {
+            msg: Some(relayer::subscribe_packets_response::Msg::Batch(
+                proto_packet::PacketBatch {
+                    packets: vec![proto_packet(valid), proto_packet(invalid)],
+                },
+            )),
+            ..relayer::SubscribePacketsResponse::default()
+        };
+        RelayerStage::handle_relayer_packets(
+            &stage.context,
+            resp,
+            (
+                SocketAddr::from(([127, 0, 0, 1], 8000)),
+                SocketAddr::from(([127, 0, 0, 1], 8001)),
+            ),
+            &mut Instant::now(),
+            &mut relayer_stats,
+        )
+        .unwrap();
+
+        assert_eq!(relayer_stats.num_trusted_packets, 2);
+        assert_eq!(relayer_stats.num_untrusted_packets, 0);
+        assert_eq!(relayer_stats.num_trust_audited_packets, 2);
+        assert_eq!(relayer_stats.num_trust_violations, 1);
+        // auditing doesn't change where trusted packets go
+        assert!(stage.packet_rx.is_empty());
+        let (batches, _) = stage.verified_packet_rx.recv().unwrap();
+        assert_eq!(batches[0].len(), 2);
+    }
+
+    #[test]
+    fn test_trusted_backpressure() {
+        let batch = || PacketBatch::new(vec![Packet::default(); 2]);
+        let mut relayer_stats = RelayerStageStats::default();
+
+        // dropping sheds the batches that don't fit, reporting each one
+        let (verified_packet_tx, verified_packet_rx) = bounded(1);
+        let (drop_event_tx, drop_event_rx) = unbounded();
+        for _ in 0..3 {
+            RelayerStage::forward_trusted_packets(
+                batch(),
+                TrustedPacketBackpressure::Drop,
+                &verified_packet_tx,
+                Some(&drop_event_tx),
+                &mut relayer_stats,
+            )
+            .unwrap();
+        }
+        assert_eq!(relayer_stats.num_trusted_dropped, 4);
+        assert_eq!(verified_packet_rx.len(), 1);
+        let events: Vec<DropEvent> = drop_event_rx.try_iter().collect();
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|event| event.bundle_uuid.is_empty()
+            && event.reason == DropReason::Backpressure { num_packets: 2 }));
+
+        // blocking waits for the slow consumer to make room
+        let consumer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            verified_packet_rx.iter().take(2).count()
+        });
+        RelayerStage::forward_trusted_packets(
+            batch(),
+            TrustedPacketBackpressure::Block,
+            &verified_packet_tx,
+            Some(&drop_event_tx),
+            &mut relayer_stats,
+        )
+        .unwrap();
+        assert_eq!(relayer_stats.num_trusted_dropped, 4);
+        assert_eq!(consumer.join().unwrap(), 2);
+        assert!(drop_event_rx.is_empty());
+
+        // both fail once the consumer is gone
+        for trusted_backpressure in [
+            TrustedPacketBackpressure::Block,
+            TrustedPacketBackpressure::Drop,
+        ] {
+            assert!(matches!(
+                RelayerStage::forward_trusted_packets(
+                    batch(),
+                    trusted_backpressure,
+                    &verified_packet_tx,
+                    Some(&drop_event_tx),
+                    &mut relayer_stats,
+                ),
+                Err(ProxyError::PacketForwardError)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_empty_message_threshold() {
+        let stage = TestStage::new(RelayerConnectionStateTracker::new(
+            RelayerConnectionState::Streaming,
+        ));
+
+        // feeds a burst of empty messages, returning the check result after each one
+        let burst = |num_messages: usize, empty_message_threshold: EmptyMessageThreshold| {
+            let mut relayer_stats = RelayerStageStats::default();
+            (0..num_messages)
+                .map(|_| {
+                    RelayerStage::handle_relayer_packets(
+                        &stage.context,
+                        relayer::SubscribePacketsResponse::default(),
+                        (
+                            SocketAddr::from(([127, 0, 0, 1], 8000)),
+                            SocketAddr::from(([127, 0, 0, 1], 8001)),
+                        ),
+                        &mut Instant::now(),
+                        &mut relayer_stats,
+                    )
+                    .unwrap();
+                    RelayerStage::check_empty_messages(
+                        empty_message_threshold,
+                        &relayer_stats,
+                        "id",
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let results = burst(
+            5,
+            EmptyMessageThreshold {
+                max_per_sec: 3,
+                reconnect: true,
+            },
+        );
+        assert!(results[..3].iter().all(Result::is_ok));
+        assert!(matches!(
+            results[3],
+            Err(ProxyError::TooManyEmptyMessages(4))
+        ));
+        // fires once per window
+        assert!(results[4].is_ok());
+
+        // only warns unless asked to reconnect
+        let results = burst(
+            5,
+            EmptyMessageThreshold {
+                max_per_sec: 3,
+                reconnect: false,
+            },
+        );
+        assert!(results.iter().all(Result::is_ok));
+    }
+
+    #[test]
+    fn test_packet_latency() {
+        let now = SystemTime::now();
+        let header = |sent_at: SystemTime| Header {
+            ts: Some(prost_types::Timestamp::from(sent_at)),
+        };
+
+        assert_eq!(
+            RelayerStage::packet_latency(Some(&header(now - Duration::from_millis(5))), now),
+            Some(Duration::from_millis(5))
+        );
+        // unstamped messages and stamps ahead of the local clock are skipped
+        assert_eq!(RelayerStage::packet_latency(None, now), None);
+        assert_eq!(
+            RelayerStage::packet_latency(Some(&Header { ts: None }), now),
+            None
+        );
+        assert_eq!(
+            RelayerStage::packet_latency(Some(&header(now + Duration::from_millis(5))), now),
+            None
+        );
+
+        let stage = TestStage::new(RelayerConnectionStateTracker::new(
+            RelayerConnectionState::Streaming,
+        ));
+        let mut relayer_stats = RelayerStageStats::default();
+        for header in [
+            Some(header(SystemTime::now() - Duration::from_millis(10))),
+            Some(header(SystemTime::now() - Duration::from_millis(20))),
+            None,
+        ] {
+            RelayerStage::handle_relayer_packets(
+                &stage.context,
+                relayer::SubscribePacketsResponse {
+                    header,
+                    msg: Some(relayer::subscribe_packets_response::Msg::Batch(
+                        proto_packet::PacketBatch { packets: vec![] },
+                    )),
+                },
+                (
+                    SocketAddr::from(([127, 0, 0, 1], 8000)),
+                    SocketAddr::from(([127, 0, 0, 1], 8001)),
+                ),
+                &mut Instant::now(),
+                &mut relayer_stats,
+            )
+            .unwrap();
+        }
+
+        // the unstamped batch wasn't recorded, the histogram being precise to 0.1%
+        assert_eq!(relayer_stats.packet_latency_us_hist.entries(), 2);
+        assert!(relayer_stats.packet_latency_us_hist.minimum().unwrap() >= 9_990);
+        assert!(relayer_stats.packet_latency_us_hist.maximum().unwrap() >= 19_980);
+    }
+
+    #[test]
+    fn test_recent_batches_keeps_last_batches() {
+        let batch = |num_packets: usize| PacketBatch::new(vec![Packet::default(); num_packets]);
+
+        let recent_batches = RecentPacketBatches::new(3);
+        for num_packets in 1..=5 {
+            recent_batches.push(&batch(num_packets));
+        }
+        let lens: Vec<usize> = recent_batches
+            .batches()
+            .iter()
+            .map(|batch| batch.len())
+            .collect();
+        assert_eq!(lens, vec![3, 4, 5]);
+
+        let recent_batches = RecentPacketBatches::new(0);
+        recent_batches.push(&batch(1));
+        assert!(recent_batches.batches().is_empty());
+
+        let recent_batches = RecentPacketBatches::new(usize::MAX);
+        for _ in 0..RecentPacketBatches::MAX_CAPACITY + 1 {
+            recent_batches.push(&batch(1));
+        }
+        assert_eq!(
+            recent_batches.batches().len(),
+            RecentPacketBatches::MAX_CAPACITY
+        );
+    }
+
+    #[test]
+    fn test_reconnect_errors_logged_at_throttled_rate() {
+        let mut logger = ReconnectErrorLogger::new();
+        let start = Instant::now();
+        let error = ProxyError::GrpcStreamDisconnected;
+
+        // the first few errors are logged, then the rest of the interval is suppressed
+        let num_logged = (0..10).filter(|_| logger.log("id", &error, start)).count();
+        assert_eq!(num_logged, ReconnectErrorLogger::NUM_UNTHROTTLED as usize);
+        assert!(!logger.log("id", &error, start + Duration::from_secs(30)));
+        assert_eq!(logger.num_suppressed, 8);
+
+        // then once per interval
+        let next_interval = start + ReconnectErrorLogger::LOG_INTERVAL;
+        assert!(logger.log("id", &error, next_interval));
+        assert_eq!(logger.num_suppressed, 0);
+        assert!(!logger.log("id", &error, next_interval + Duration::from_secs(1)));
+
+        // a different error is logged right away
+        assert!(logger.log(
+            "id",
+            &ProxyError::HeartbeatExpired,
+            next_interval + Duration::from_secs(1)
+        ));
+    }
+
+    #[test]
+    fn test_reconnect_error_change_reports_suppressed_repeats() {
+        let mut logger = ReconnectErrorLogger::new();
+        let start = Instant::now();
+        let error = ProxyError::GrpcStreamDisconnected.to_string();
+        for _ in 0..5 {
+            logger.messages(error.clone(), start);
+        }
+        assert_eq!(logger.num_suppressed, 2);
+
+        // the suppressed repeats are reported before the new error rather than dropped
+        let new_error = ProxyError::HeartbeatExpired.to_string();
+        assert_eq!(
+            logger.messages(new_error.clone(), start),
+            vec![
+                format!("previous relayer proxy error repeated 2 times: {}", error),
+                format!("relayer proxy error: {}", new_error),
+            ]
+        );
+        assert_eq!(logger.num_suppressed, 0);
+
+        // nothing to report if none were suppressed
+        assert_eq!(
+            logger.messages(error.clone(), start),
+            vec![format!("relayer proxy error: {}", error)]
+        );
+    }
+
+    #[test]
+    fn test_connection_state_full_connect_cycle() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let endpoint = spawn_mock_relayer(MockRelayer::default()).await;
+
+            let connection_state =
+                RelayerConnectionStateTracker::new(RelayerConnectionState::Authenticating);
+            let stage = TestStage::new(connection_state.clone());
+
+            // stop once the stream ended and the stage is waiting to reconnect
+            let stop_after_backoff = async {
+                while connection_state.get() != RelayerConnectionState::Backoff {
+                    sleep(Duration::from_millis(10)).await;
+                }
+                stage.stop();
+            };
+
+            tokio::join!(stage.start(endpoint), stop_after_backoff);
+
+            assert_eq!(connection_state.get(), RelayerConnectionState::Backoff);
+        });
+    }
+
+    #[test]
+    fn test_state_changes_streamed() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let endpoint = spawn_mock_relayer(MockRelayer::default()).await;
+
+            let (state_change_tx, state_change_rx) = unbounded();
+            let connection_state =
+                RelayerConnectionStateTracker::new(RelayerConnectionState::Authenticating)
+                    .with_state_change_tx(Some(state_change_tx));
+            let stage = TestStage::new(connection_state.clone());
+
+            let stop_after_backoff = async {
+                while connection_state.get() != RelayerConnectionState::Backoff {
+                    sleep(Duration::from_millis(10)).await;
+                }
+                stage.stop();
+            };
+
+            let start = SystemTime::now();
+            tokio::join!(stage.start(endpoint), stop_after_backoff);
+
+            let state_changes: Vec<RelayerStateChange> = state_change_rx.try_iter().collect();
+            assert_eq!(
+                state_changes
+                    .iter()
+                    .map(|state_change| (state_change.from, state_change.to))
+                    .collect::<Vec<_>>(),
+                vec![
+                    (
+                        RelayerConnectionState::Authenticating,
+                        RelayerConnectionState::Connecting
+                    ),
+                    (
+                        RelayerConnectionState::Connecting,
+                        RelayerConnectionState::Subscribing
+                    ),
+                    (
+                        RelayerConnectionState::Subscribing,
+                        RelayerConnectionState::Streaming
+                    ),
+                    (
+                        RelayerConnectionState::Streaming,
+                        RelayerConnectionState::Backoff
+                    ),
+                ]
+            );
+            assert!(state_changes
+                .iter()
+                .all(|state_change| state_change.connection_id == state_changes[0].connection_id));
+            assert!(state_changes[0].timestamp >= start);
+            assert!(state_changes
+                .windows(2)
+                .all(|pair| pair[0].timestamp <= pair[1].timestamp));
+        });
+    }
+
+    #[test]
+    fn test_state_changes_dropped_when_channel_full() {
+        let (state_change_tx, state_change_rx) = bounded(1);
+        let connection_state =
+            RelayerConnectionStateTracker::new(RelayerConnectionState::Authenticating)
+                .with_state_change_tx(Some(state_change_tx));
+
+        connection_state.transition(RelayerConnectionState::Connecting);
+        connection_state.transition(RelayerConnectionState::Backoff);
+        connection_state.transition(RelayerConnectionState::Connecting);
+
+        // the transitions still go through, only the first one fit in the channel
+        assert_eq!(connection_state.get(), RelayerConnectionState::Connecting);
+        assert_eq!(state_change_rx.try_iter().count(), 1);
+        assert_eq!(
+            connection_state
+                .num_dropped_state_changes
+                .load(Ordering::Relaxed),
+            2
+        );
+    }
+
+    #[test]
+    fn test_endpoint_failover() {
+        let endpoints: Vec<Endpoint> = (0..3)
+            .map(|i| Endpoint::from_shared(format!("http://relayer-{}:11226", i)).unwrap())
+            .collect();
+        let mut failover = EndpointFailover::new(endpoints, Duration::from_secs(60));
+        assert_eq!(failover.current().0, 0);
+
+        // failed connections and connections dropping early move on to the next endpoint
+        failover.on_disconnect(None);
+        assert_eq!(failover.current().0, 1);
+        failover.on_disconnect(Some(Duration::from_secs(1)));
+        assert_eq!(failover.current().0, 2);
+        failover.on_disconnect(None);
+        assert_eq!(failover.current().0, 0);
+
+        // a stable connection to a secondary endpoint brings the next attempt back to the primary
+        failover.on_disconnect(None);
+        failover.on_disconnect(Some(Duration::from_secs(60)));
+        assert_eq!(failover.current().0, 0);
+        assert_eq!(
+            failover.current().1.uri().to_string(),
+            "http://relayer-0:11226/"
+        );
+    }
+
+    #[test]
+    fn test_state_handle_observes_streaming() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let endpoint = spawn_mock_relayer(MockRelayer {
+                keep_stream_open: true,
+                ..MockRelayer::default()
+            })
+            .await;
+
+            let (connection_state, state_changes) =
+                StateChanges::track(RelayerConnectionState::Authenticating);
+            let state_handle = RelayerStateHandle {
+                connection_state: connection_state.clone(),
+            };
+            let created_at = Instant::now();
+            let stage = TestStage::new(connection_state.clone());
+
+            assert!(!state_handle.is_connected());
+            let stop_once_connected = async {
+                while !state_handle.is_connected() {
+                    sleep(Duration::from_millis(10)).await;
+                }
+                stage.stop();
+            };
+
+            tokio::join!(stage.start(endpoint), stop_once_connected);
+
+            // the handle saw the same transitions as the stage, stamped when they happened
+            assert!(state_changes
+                .states()
+                .contains(&RelayerConnectionState::Streaming));
+            assert!(state_handle.state_since() > created_at);
+        });
+    }
+
+    #[test]
+    fn test_failover_to_secondary_endpoint() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            // nothing listens on the primary, so connecting to it fails right away
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let primary =
+                Endpoint::from_shared(format!("http://{}", listener.local_addr().unwrap()))
+                    .unwrap();
+            drop(listener);
+
+            let secondary = spawn_mock_relayer(MockRelayer {
+                keep_stream_open: true,
+                ..MockRelayer::default()
+            })
+            .await;
+
+            let connection_state =
+                RelayerConnectionStateTracker::new(RelayerConnectionState::Authenticating);
+            let stage = TestStage::new(connection_state.clone());
+
+            let stop_once_streaming = async {
+                while connection_state.get() != RelayerConnectionState::Streaming {
+                    sleep(Duration::from_millis(10)).await;
+                }
+                stage.stop();
+            };
+
+            tokio::join!(
+                stage.start(EndpointFailover::new(
+                    vec![primary, secondary.clone()],
+                    EndpointFailover::MIN_UPTIME
+                )),
+                stop_once_streaming,
+            );
+
+            assert_eq!(
+                connection_state.num_connect_errors.load(Ordering::Relaxed),
+                1
+            );
+            assert_eq!(
+                connection_state.active_endpoint(),
+                secondary.uri().to_string()
+            );
+        });
+    }
+
+    fn relayer_config(url: &str) -> RelayerConfig {
+        RelayerConfig::from_vars(|name| {
+            (name == RelayerConfig::RELAYER_URL_VAR).then(|| url.to_string())
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_config_updates_pending_until_next_connection() {
+        let (config_tx, config_rx) = unbounded();
+        let mut config_updates =
+            RelayerConfigUpdates::new(config_rx, &relayer_config("http://relayer-0:11226"));
+        assert!(!config_updates.poll());
+        assert!(config_updates.take_pending().is_none());
+
+        // only endpoint changes require reconnecting right away
+        let mut trusting_config = relayer_config("http://relayer-0:11226");
+        trusting_config.trust_packets = true;
+        config_tx.send(trusting_config).unwrap();
+        assert!(!config_updates.poll());
+        assert!(config_updates.take_pending().unwrap().trust_packets);
+        assert!(config_updates.take_pending().is_none());
+
+        // the latest config wins
+        config_tx
+            .send(relayer_config("http://relayer-1:11226"))
+            .unwrap();
+        config_tx
+            .send(relayer_config("http://relayer-2:11226"))
+            .unwrap();
+        assert!(config_updates.poll());
+        let updated_config = config_updates.take_pending().unwrap();
+        assert_eq!(
+            updated_config.backend_endpoints[0].uri().to_string(),
+            "http://relayer-2:11226/"
+        );
+        assert!(!config_updates.poll());
+    }
+
+    #[test]
+    fn test_config_update_reconnects_to_new_endpoint() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let mut urls = vec![];
+            for _ in 0..2 {
+                let endpoint = spawn_mock_relayer(MockRelayer {
+                    keep_stream_open: true,
+                    ..MockRelayer::default()
+                })
+                .await;
+                urls.push(format!("http://{}", endpoint.uri().authority().unwrap()));
+            }
+
+            let connection_state =
+                RelayerConnectionStateTracker::new(RelayerConnectionState::Authenticating);
+            let mut stage = TestStage::new(connection_state.clone());
+            stage.context.expected_heartbeat_interval = Duration::from_secs(10);
+            stage.context.oldest_allowed_heartbeat = Duration::from_secs(10);
+
+            let (config_tx, config_rx) = unbounded();
+            let mut config_updates =
+                RelayerConfigUpdates::new(config_rx, &relayer_config(&urls[0]));
+
+            // switch to the second relayer once streaming from the first
+            let update_config = async {
+                while connection_state.get() != RelayerConnectionState::Streaming {
+                    sleep(Duration::from_millis(10)).await;
+                }
+                assert_eq!(connection_state.active_endpoint(), format!("{}/", urls[0]));
+                config_tx.send(relayer_config(&urls[1])).unwrap();
+            };
+            let (updated_config, _) = tokio::join!(
+                RelayerStage::start(
+                    &stage.context,
+                    relayer_config(&urls[0]).backend_endpoints[0].clone().into(),
+                    Some(&mut config_updates)
+                ),
+                update_config,
+            );
+            // the stream was torn down and the new config handed back to reconnect with
+            let updated_config = updated_config.unwrap();
+            assert_eq!(connection_state.get(), RelayerConnectionState::Backoff);
+
+            let stop_once_streaming = async {
+                while connection_state.get() != RelayerConnectionState::Streaming {
+                    sleep(Duration::from_millis(10)).await;
+                }
+                stage.stop();
+            };
+            let (updated_config, _) = tokio::join!(
+                RelayerStage::start(
+                    &stage.context,
+                    EndpointFailover::new(
+                        updated_config.backend_endpoints,
+                        EndpointFailover::MIN_UPTIME
+                    ),
+                    Some(&mut config_updates)
+                ),
+                stop_once_streaming,
+            );
+            assert!(updated_config.is_none());
+            assert_eq!(connection_state.active_endpoint(), format!("{}/", urls[1]));
+        });
+    }
+
+    #[test]
+    fn test_connection_timeout() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            // accepts connections but never answers
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let endpoint =
+                Endpoint::from_shared(format!("http://{}", listener.local_addr().unwrap()))
+                    .unwrap();
+            tokio::spawn(async move {
+                let mut streams = vec![];
+                while let Ok((stream, _)) = listener.accept().await {
+                    streams.push(stream);
+                }
+            });
+
+            let mut stage = TestStage::new(RelayerConnectionStateTracker::new(
+                RelayerConnectionState::Subscribing,
+            ));
+            let connection_timeout = Duration::from_millis(100);
+            stage.context.connection_timeout = connection_timeout;
+            let started_at = Instant::now();
+            // depending on the handshake, either connecting or fetching the TPU configs times out
+            let result = match RelayerStage::connect(
+                &endpoint,
+                &RelayerSocketConfig::default(),
+                connection_timeout,
+            )
+            .await
+            {
+                Ok(channel) => {
+                    RelayerStage::start_consuming_relayer_packets(
+                        &stage.context,
+                        &mut ExponentialBackoff::new(RelayerConfig::DEFAULT_MAX_RECONNECT_BACKOFF),
+                        RelayerClient::with_interceptor(
+                            channel,
+                            AuthInterceptor::new(Arc::new(Mutex::new(Token::default()))),
+                        ),
+                        None,
+                    )
+                    .await
+                }
+                Err(e) => Err(e),
+            };
+
+            assert!(matches!(
+                result,
+                Err(ProxyError::RelayerConnectionTimeout | ProxyError::TpuConfigTimeout)
+            ));
+            assert!(started_at.elapsed() < RelayerConfig::DEFAULT_CONNECTION_TIMEOUT);
+        });
+    }
+
+    #[test]
+    fn test_subscribe_timeouts() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let subscribe = |relayer: MockRelayer| async move {
+                let channel = RelayerStage::connect(
+                    &spawn_mock_relayer(relayer).await,
+                    &RelayerSocketConfig::default(),
+                    RelayerConfig::DEFAULT_CONNECTION_TIMEOUT,
+                )
+                .await
+                .unwrap();
+                let started_at = Instant::now();
+                let result = RelayerStage::subscribe(
+                    RelayerClient::with_interceptor(
+                        channel,
+                        AuthInterceptor::new(Arc::new(Mutex::new(Token::default()))),
+                    ),
+                    Duration::from_millis(100),
+                )
+                .await;
+                assert!(started_at.elapsed() < RelayerConfig::DEFAULT_CONNECTION_TIMEOUT);
+                result.map(|_| ())
+            };
+
+            assert!(matches!(
+                subscribe(MockRelayer {
+                    stall_tpu_configs: true,
+                    ..MockRelayer::default()
+                })
+                .await,
+                Err(ProxyError::TpuConfigTimeout)
+            ));
+            assert!(matches!(
+                subscribe(MockRelayer {
+                    stall_subscribe_packets: true,
+                    ..MockRelayer::default()
+                })
+                .await,
+                Err(ProxyError::SubscribePacketsTimeout)
+            ));
+            assert!(subscribe(MockRelayer::default()).await.is_ok());
+        });
+    }
+
+    #[test]
+    fn test_tpu_change_reported_across_connections() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let mut endpoints = vec![];
+            for tpu_port in [8000, 9000] {
+                endpoints.push(
+                    spawn_mock_relayer(MockRelayer {
+                        tpu_port: Some(tpu_port),
+                        ..MockRelayer::default()
+                    })
+                    .await,
+                );
+            }
+
+            let connection_state =
+                RelayerConnectionStateTracker::new(RelayerConnectionState::Authenticating);
+            let stage = TestStage::new(connection_state.clone());
+
+            // the second relayer advertises another TPU, the third connection doesn't change it
+            for endpoint in [&endpoints[0], &endpoints[1], &endpoints[1]] {
+                let channel = RelayerStage::connect(
+                    endpoint,
+                    &RelayerSocketConfig::default(),
+                    RelayerConfig::DEFAULT_CONNECTION_TIMEOUT,
+                )
+                .await
+                .unwrap();
+                // the stream ends after its only message
+                let result = RelayerStage::start_consuming_relayer_packets(
+                    &stage.context,
+                    &mut ExponentialBackoff::new(RelayerConfig::DEFAULT_MAX_RECONNECT_BACKOFF),
+                    RelayerClient::with_interceptor(
+                        channel,
+                        AuthInterceptor::new(Arc::new(Mutex::new(Token::default()))),
+                    ),
+                    None,
+                )
+                .await;
+                assert!(matches!(result, Err(ProxyError::GrpcStreamDisconnected)));
+            }
+
+            let new_tpu = SocketAddr::from(([127, 0, 0, 1], 9000));
+            assert_eq!(connection_state.num_tpu_changes.load(Ordering::Relaxed), 1);
+            assert_eq!(
+                *connection_state.last_tpu_sockets.lock().unwrap(),
+                Some((new_tpu, new_tpu))
+            );
+        });
+    }
+
+    #[test]
+    fn test_connection_id_minted_per_cycle() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let endpoint = spawn_mock_relayer(MockRelayer::default()).await;
+
+            let connection_state =
+                RelayerConnectionStateTracker::new(RelayerConnectionState::Authenticating);
+            let stage = TestStage::new(connection_state.clone());
+
+            // the mock stream ends right away, stop once two connection attempts were made
+            let mut connection_ids = vec![];
+            let stop_after_two_cycles = async {
+                while connection_ids.len() < 2 {
+                    let connection_id = connection_state.connection_id();
+                    if !connection_id.is_empty() && !connection_ids.contains(&connection_id) {
+                        connection_ids.push(connection_id);
+                    }
+                    sleep(Duration::from_millis(10)).await;
+                }
+                stage.stop();
+            };
+
+            tokio::join!(stage.start(endpoint), stop_after_two_cycles);
+
+            assert!(connection_ids
+                .iter()
+                .all(|connection_id| connection_id.len() == 8));
+        });
+    }
+
+    #[test]
+    fn test_health_of_connected_stage() {
+        let connection_state =
+            RelayerConnectionStateTracker::new(RelayerConnectionState::Authenticating)
+                .with_active_endpoint("http://relayer:11226/".to_string());
+        let access_token = Arc::new(Mutex::new(Token::default()));
+        let stage = RelayerStage {
+            t_hdls: vec![],
+            connection_state: connection_state.clone(),
+            access_token: access_token.clone(),
+            recent_batches: None,
+            refresh_freeze: RefreshFreeze::default(),
+        };
+
+        let health = stage.health();
+        assert_eq!(
+            health.connection_state,
+            RelayerConnectionState::Authenticating
+        );
+        assert_eq!(health.last_heartbeat_age_ms, None);
+        assert_eq!(health.access_token_ttl_secs, None);
+
+        // authenticate, then connect after a failed attempt
+        *access_token.lock().unwrap() = Token {
+            value: "token".to_string(),
+            expires_at_utc: Some(prost_types::Timestamp {
+                seconds: Utc::now().timestamp() + 1_800,
+                nanos: 0,
+            }),
+        };
+        connection_state.transition(RelayerConnectionState::Connecting);
+        connection_state.record_connect_error();
+        connection_state.transition(RelayerConnectionState::Backoff);
+        connection_state.transition(RelayerConnectionState::Connecting);
+        connection_state.transition(RelayerConnectionState::Subscribing);
+        connection_state.transition(RelayerConnectionState::Streaming);
+        connection_state.record_heartbeat();
+
+        let health = stage.health();
+        assert_eq!(health.connection_state, RelayerConnectionState::Streaming);
+        assert!(health.last_heartbeat_age_ms.unwrap() < 1_000);
+        assert_eq!(health.endpoint, "http://relayer:11226/");
+        let ttl = health.access_token_ttl_secs.unwrap();
+        assert!(ttl > 1_700 && ttl <= 1_800);
+        assert_eq!(health.num_connect_errors, 1);
+        assert_eq!(health.num_stream_errors, 0);
+        assert_eq!(health.num_tpu_changes, 0);
+
+        // serializable for admin rpc
+        let json = serde_json::to_value(&health).unwrap();
+        assert_eq!(json["connection_state"], "Streaming");
+    }
+
+    #[test]
+    fn test_shared_reconnect_limiter_bounds_aggregate_reconnects() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            // nothing listens here, so every connection attempt fails right away
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let endpoint =
+                Endpoint::from_shared(format!("http://{}", listener.local_addr().unwrap()))
+                    .unwrap();
+            drop(listener);
+
+            let reconnect_limiter = Arc::new(ReconnectRateLimiter::new(1, Duration::from_secs(1)));
+            let stages: Vec<_> = (0..2)
+                .map(|_| {
+                    let mut stage = TestStage::new(RelayerConnectionStateTracker::new(
+                        RelayerConnectionState::Authenticating,
+                    ));
+                    stage.context.reconnect_limiter = Some(reconnect_limiter.clone());
+                    stage
+                })
+                .collect();
+            let stop = async {
+                sleep(Duration::from_millis(1_500)).await;
+                stages.iter().for_each(TestStage::stop);
+            };
+            tokio::join!(
+                futures::future::join_all(stages.iter().map(|stage| stage.start(endpoint.clone()))),
+                stop
+            );
+
+            // without the limiter each stage would reconnect several times within its backoff,
+            // with it the burst of 1 plus a refill every second allow at most 2 connection attempts
+            // in 1.5s, and one more could sneak in while the stages are shutting down
+            let num_connect_errors: u64 = stages
+                .iter()
+                .map(|stage| {
+                    stage
+                        .context
+                        .connection_state
+                        .num_connect_errors
+                        .load(Ordering::Relaxed)
+                })
+                .sum();
+            assert!(num_connect_errors >= 1);
+            assert!(num_connect_errors <= 3, "{}", num_connect_errors);
+        });
+    }
+
+    #[test]
+    fn test_stream_recycled_at_max_lifetime() {
+        const MAX_STREAM_LIFETIME: Duration = Duration::from_millis(200);
+
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let endpoint = spawn_mock_relayer(MockRelayer {
+                keep_stream_open: true,
+                ..MockRelayer::default()
+            })
+            .await;
+
+            let connection_state =
+                RelayerConnectionStateTracker::new(RelayerConnectionState::Authenticating);
+            let mut stage = TestStage::new(connection_state.clone());
+            stage.context.oldest_allowed_heartbeat = Duration::from_secs(10);
+            stage.context.max_stream_lifetime = Some(MAX_STREAM_LIFETIME);
+
+            // the mock keeps the stream open, so streaming again after backing off means the first
+            // one was recycled
+            let start = Instant::now();
+            let stop_after_recycle = async {
+                let mut num_streams = 0;
+                let mut was_streaming = false;
+                while num_streams < 2 {
+                    let is_streaming = connection_state.get() == RelayerConnectionState::Streaming;
+                    if is_streaming && !was_streaming {
+                        num_streams += 1;
+                    }
+                    was_streaming = is_streaming;
+                    sleep(Duration::from_millis(10)).await;
+                }
+                stage.stop();
+            };
+
+            tokio::join!(stage.start(endpoint), stop_after_recycle);
+
+            // the open stream was closed by the stage rather than failing
+            assert!(start.elapsed() >= MAX_STREAM_LIFETIME);
+            assert_eq!(
+                connection_state.num_stream_errors.load(Ordering::Relaxed),
+                0
+            );
+        });
+    }
+
+    #[test]
+    fn test_standby_promoted_when_active_connection_fails() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let num_subscriptions = Arc::new(AtomicUsize::new(0));
+            let endpoint = spawn_mock_relayer(MockRelayer {
+                keep_stream_open: true,
+                tag_batches: true,
+                num_subscriptions: num_subscriptions.clone(),
+                ..MockRelayer::default()
+            })
+            .await;
+
+            let (connection_state, state_changes) =
+                StateChanges::track(RelayerConnectionState::Authenticating);
+            let mut stage = TestStage::new(connection_state.clone());
+            stage.context.expected_heartbeat_interval = Duration::from_millis(100);
+            stage.context.oldest_allowed_heartbeat = Duration::from_millis(500);
+            stage.context.warm_standby = true;
+
+            let num_streams = || {
+                state_changes
+                    .states()
+                    .iter()
+                    .filter(|state| **state == RelayerConnectionState::Streaming)
+                    .count()
+            };
+            let stop_after_promotion = async {
+                while num_streams() < 2 {
+                    sleep(Duration::from_millis(10)).await;
+                }
+                stage.stop();
+            };
+
+            // the mock never sends heartbeats, so the active connection fails once the oldest
+            // allowed heartbeat is exceeded, by which time the standby is subscribed
+            tokio::join!(stage.start(endpoint), stop_after_promotion);
+
+            assert_eq!(
+                connection_state.num_stream_errors.load(Ordering::Relaxed),
+                1
+            );
+            assert_eq!(
+                connection_state.num_connect_errors.load(Ordering::Relaxed),
+                0
+            );
+            // the standby took over without connecting or subscribing again
+            assert_eq!(
+                state_changes.states()[..6],
+                [
+                    RelayerConnectionState::Authenticating,
+                    RelayerConnectionState::Connecting,
+                    RelayerConnectionState::Subscribing,
+                    RelayerConnectionState::Streaming,
+                    RelayerConnectionState::Backoff,
+                    RelayerConnectionState::Streaming,
+                ]
+            );
+            // the standby subscribed while idle, as did its replacement once it was promoted, but
+            // the batch streamed to it then was discarded rather than forwarded after promotion
+            assert!(num_subscriptions.load(Ordering::Relaxed) >= 2);
+            let subscriptions: Vec<u8> = stage
+                .packet_rx
+                .try_iter()
+                .map(|packet_batch| packet_batch[0].data(..).unwrap()[0])
+                .collect();
+            assert_eq!(subscriptions, [0]);
+        });
     }
 }
@@ -120,6 +120,8 @@ impl Tpu {
         shared_staked_nodes_overrides: Arc<RwLock<HashMap<Pubkey, u64>>>,
         tpu_enable_udp: bool,
         preallocated_bundle_cost: u64,
+        report_bundle_tip_outcomes: bool,
+        max_bundle_lookup_tables: Option<usize>,
     ) -> Self {
         let TpuSockets {
             transactions: transactions_sockets,
@@ -265,9 +267,12 @@ impl Tpu {
             RelayerStage::new(
                 relayer_config,
                 cluster_info.clone(),
+                None,
                 heartbeat_tx,
-                packet_sender,
+                vec![packet_sender],
                 verified_sender,
+                None,
+                None,
                 exit.clone(),
             )
         });
@@ -327,6 +332,8 @@ impl Tpu {
             bundle_account_locker,
             &block_builder_fee_info,
             preallocated_bundle_cost,
+            report_bundle_tip_outcomes,
+            max_bundle_lookup_tables,
         );
 
         let broadcast_stage = broadcast_type.new_broadcast_stage(
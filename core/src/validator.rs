@@ -185,6 +185,9 @@ pub struct ValidatorConfig {
     pub shred_receiver_address: Option<SocketAddr>,
     pub tip_manager_config: TipManagerConfig,
     pub preallocated_bundle_cost: u64,
+    pub report_bundle_tip_outcomes: bool,
+    /// Bundles referencing more distinct address lookup tables than this are dropped, if set.
+    pub max_bundle_lookup_tables: Option<usize>,
 }
 
 impl Default for ValidatorConfig {
@@ -255,6 +258,8 @@ impl Default for ValidatorConfig {
             shred_receiver_address: None,
             tip_manager_config: TipManagerConfig::default(),
             preallocated_bundle_cost: u64::default(),
+            report_bundle_tip_outcomes: false,
+            max_bundle_lookup_tables: None,
         }
     }
 }
@@ -1048,6 +1053,8 @@ impl Validator {
             config.staked_nodes_overrides.clone(),
             tpu_enable_udp,
             config.preallocated_bundle_cost,
+            config.report_bundle_tip_outcomes,
+            config.max_bundle_lookup_tables,
         );
 
         datapoint_info!(
@@ -72,6 +72,8 @@ pub fn safe_clone_config(config: &ValidatorConfig) -> ValidatorConfig {
         shred_receiver_address: config.shred_receiver_address,
         tip_manager_config: config.tip_manager_config.clone(),
         preallocated_bundle_cost: config.preallocated_bundle_cost,
+        report_bundle_tip_outcomes: config.report_bundle_tip_outcomes,
+        max_bundle_lookup_tables: config.max_bundle_lookup_tables,
     }
 }
 
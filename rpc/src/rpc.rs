@@ -114,6 +114,7 @@ type RpcCustomResult<T> = std::result::Result<T, RpcCustomError>;
 
 pub const MAX_REQUEST_BODY_SIZE: usize = 50 * (1 << 10); // 50kB
 pub const PERFORMANCE_SAMPLES_LIMIT: usize = 720;
+pub const MAX_BUNDLE_SIMULATION_DURATION: Duration = Duration::from_secs(5);
 
 fn new_response<T>(bank: &Bank, value: T) -> RpcResponse<T> {
     RpcResponse {
@@ -157,6 +158,8 @@ pub struct JsonRpcConfig {
     pub obsolete_v1_7_api: bool,
     pub rpc_scan_and_fix_roots: bool,
     pub max_request_body_size: Option<usize>,
+    /// How long simulating a bundle may take, [MAX_BUNDLE_SIMULATION_DURATION] if unset.
+    pub max_bundle_simulation_duration: Option<Duration>,
 }
 
 impl JsonRpcConfig {
@@ -3442,7 +3445,10 @@ pub mod rpc_full {
         crate::rpc::utils::{build_simulate_bundle_params, rpc_bundle_result_from_bank_result},
         itertools::izip,
         solana_runtime::bank::SimulateBundleError,
-        solana_sdk::message::{SanitizedVersionedMessage, VersionedMessage},
+        solana_sdk::{
+            bundle::error::BundleExecutionError,
+            message::{SanitizedVersionedMessage, VersionedMessage},
+        },
     };
 
     #[rpc]
@@ -3612,6 +3618,17 @@ pub mod rpc_full {
             SimulateBundleError::AccountNotFoundInBank(pubkey) => {
                 Error::invalid_params(format!("account {:?} not found in bank", pubkey))
             }
+            SimulateBundleError::SanitizeTransaction { index, error } => {
+                Error::invalid_params(format!("transaction {} is invalid: {}", index, error))
+            }
+            SimulateBundleError::BundleExecution(BundleExecutionError::TimedOut { tx_index }) => {
+                RpcCustomError::BundleSimulationTimedOut { tx_index }.into()
+            }
+            SimulateBundleError::BundleExecution(e) => Error {
+                code: error::ErrorCode::InternalError,
+                message: e.to_string(),
+                data: None,
+            },
         }
     }
 
@@ -4092,7 +4109,14 @@ pub mod rpc_full {
             }
 
             let bank_result = bank
-                .simulate_bundle(sanitized_txs, pre_execution_pks, post_execution_pks)
+                .simulate_bundle(
+                    sanitized_txs,
+                    pre_execution_pks,
+                    post_execution_pks,
+                    meta.config
+                        .max_bundle_simulation_duration
+                        .unwrap_or(MAX_BUNDLE_SIMULATION_DURATION),
+                )
                 .map_err(jsonrpc_error_from_simulate_bundle_error)?;
 
             let rpc_bundle_result = rpc_bundle_result_from_bank_result(bank_result, config)?;
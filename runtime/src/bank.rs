@@ -527,6 +527,15 @@ pub struct BundleSimulationResult {
 pub enum SimulateBundleError {
     #[error("account missing from bank: {0}")]
     AccountNotFoundInBank(Pubkey),
+
+    #[error("transaction {index} failed to sanitize: {error}")]
+    SanitizeTransaction {
+        index: usize,
+        error: TransactionError,
+    },
+
+    #[error(transparent)]
+    BundleExecution(#[from] BundleExecutionError),
 }
 
 #[derive(Clone)]
@@ -3851,15 +3860,22 @@ impl Bank {
     /// It is expected that the following is returned:
     /// Returned Pre-Execution Accounts: [None, [T0(A), D], [T0(B)], [T0(T2(A)), T0(T2(C)), F]]
     /// Returned Post-Execution Accounts: [None, [T1(D)], None, [T0(T2(A), T0(B), T3(F)]]
+    ///
+    /// Simulation is aborted with [BundleExecutionError::TimedOut] if it runs for longer than
+    /// `max_simulation_duration`, the deadline being checked after each batch. Since nothing is
+    /// committed, an aborted simulation leaves no trace.
     pub fn simulate_bundle(
         &self,
         bundle: Vec<SanitizedTransaction>,
         pre_execution_accounts_requested: Vec<Option<Vec<Pubkey>>>,
         post_execution_accounts_requested: Vec<Option<Vec<Pubkey>>>,
+        max_simulation_duration: Duration,
     ) -> result::Result<BundleSimulationResult, SimulateBundleError> {
         assert_eq!(pre_execution_accounts_requested.len(), bundle.len());
         assert_eq!(post_execution_accounts_requested.len(), bundle.len());
 
+        let simulation_start = Instant::now();
+
         // Used to cache account data in between batch execution iterations
         let mut account_overrides = AccountOverrides::default();
 
@@ -4059,6 +4075,15 @@ impl Bank {
                 });
             }
 
+            // A batch can't be interrupted while executing, so the deadline is checked after each
+            // one, the last included, reporting the last transaction of the batch that overran.
+            if simulation_start.elapsed() > max_simulation_duration {
+                return Err(BundleExecutionError::TimedOut {
+                    tx_index: chunk_end - 1,
+                }
+                .into());
+            }
+
             // Welcome to Rust & Solana where we optimize for performance over readability!
             // Remember chunk_end was updated above based on whether or not there was the
             // batch was not continuous.
@@ -9017,7 +9042,59 @@ pub(crate) mod tests {
         let bank = Bank::new_for_tests(&genesis_config);
         bank.freeze();
 
-        let _ = bank.simulate_bundle(vec![], vec![None], vec![None]);
+        let _ = bank.simulate_bundle(vec![], vec![None], vec![None], Duration::MAX);
+    }
+
+    #[test]
+    fn test_simulate_bundle_timed_out() {
+        let (genesis_config, mint_keypair) = create_genesis_config(1_000_000);
+        let bank = Bank::new_for_tests(&genesis_config);
+        let recent_blockhash = bank.last_blockhash();
+        bank.freeze();
+
+        // every transaction write locks the mint so each one executes in its own batch
+        let bundle: Vec<SanitizedTransaction> = (0..3)
+            .map(|_| {
+                SanitizedTransaction::from_transaction_for_tests(system_transaction::transfer(
+                    &mint_keypair,
+                    &solana_sdk::pubkey::new_rand(),
+                    100,
+                    recent_blockhash,
+                ))
+            })
+            .collect();
+        let num_transactions = bundle.len();
+
+        assert!(matches!(
+            bank.simulate_bundle(
+                bundle.clone(),
+                vec![None; num_transactions],
+                vec![None; num_transactions],
+                Duration::ZERO,
+            ),
+            Err(SimulateBundleError::BundleExecution(
+                BundleExecutionError::TimedOut { tx_index: 0 }
+            ))
+        ));
+
+        // a bundle executed in a single batch is held to the deadline as well
+        assert!(matches!(
+            bank.simulate_bundle(bundle[..1].to_vec(), vec![None], vec![None], Duration::ZERO,),
+            Err(SimulateBundleError::BundleExecution(
+                BundleExecutionError::TimedOut { tx_index: 0 }
+            ))
+        ));
+
+        let result = bank
+            .simulate_bundle(
+                bundle,
+                vec![None; num_transactions],
+                vec![None; num_transactions],
+                Duration::MAX,
+            )
+            .unwrap();
+        assert_eq!(result.summary, BundleSimulationSummary::Succeeded);
+        assert_eq!(bank.get_balance(&mint_keypair.pubkey()), 1_000_000);
     }
 
     fn setup_system_accounts(
@@ -9190,6 +9267,7 @@ pub(crate) mod tests {
                 bundle.clone(),
                 pre_execution_accounts,
                 post_execution_accounts,
+                Duration::MAX,
             )
             .unwrap();
 
@@ -9380,6 +9458,7 @@ pub(crate) mod tests {
                 bundle.clone(),
                 pre_execution_accounts,
                 post_execution_accounts,
+                Duration::MAX,
             )
             .unwrap();
 
@@ -9551,6 +9630,7 @@ pub(crate) mod tests {
                 bundle.clone(),
                 pre_execution_accounts,
                 post_execution_accounts,
+                Duration::MAX,
             )
             .unwrap();
 
@@ -0,0 +1,133 @@
+//! Re-executes previously captured bundles against a bank for post-mortem analysis.
+
+use {
+    crate::bank::{Bank, BundleTransactionSimulationResult, SimulateBundleError},
+    solana_sdk::{
+        bundle::VersionedBundle,
+        transaction::{MessageHash, SanitizedTransaction},
+    },
+    std::time::Duration,
+};
+
+/// Replays `bundle` against `bank` to reproduce how it executed at the bank's slot, returning the
+/// result of each transaction.
+///
+/// The bank is never written to: the bundle is executed through [`Bank::simulate_bundle`], which
+/// keeps intermediate account state in a throwaway cache and doesn't commit anything.
+pub fn replay_bundle(
+    bundle: &VersionedBundle,
+    bank: &Bank,
+) -> Result<Vec<BundleTransactionSimulationResult>, SimulateBundleError> {
+    let transactions = bundle
+        .transactions
+        .iter()
+        .enumerate()
+        .map(|(index, tx)| {
+            SanitizedTransaction::try_create(tx.clone(), MessageHash::Compute, None, bank, true)
+                .map_err(|error| SimulateBundleError::SanitizeTransaction { index, error })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let num_transactions = transactions.len();
+    Ok(bank
+        .simulate_bundle(
+            transactions,
+            vec![None; num_transactions],
+            vec![None; num_transactions],
+            Duration::MAX,
+        )?
+        .transaction_results)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::genesis_utils::create_genesis_config,
+        solana_sdk::{
+            instruction::InstructionError,
+            pubkey::Pubkey,
+            signature::{Keypair, Signer},
+            system_transaction,
+            transaction::{TransactionError, VersionedTransaction},
+        },
+    };
+
+    #[test]
+    fn test_replay_bundle() {
+        let (genesis_config, mint_keypair) = create_genesis_config(1_000_000);
+        let bank = Bank::new_for_tests(&genesis_config);
+        let recent_blockhash = bank.last_blockhash();
+        bank.freeze();
+
+        let kp = Keypair::new();
+        let bundle = VersionedBundle {
+            transactions: vec![
+                VersionedTransaction::from(system_transaction::transfer(
+                    &mint_keypair,
+                    &kp.pubkey(),
+                    10_000,
+                    recent_blockhash,
+                )),
+                VersionedTransaction::from(system_transaction::transfer(
+                    &kp,
+                    &Pubkey::new_unique(),
+                    1_000,
+                    recent_blockhash,
+                )),
+            ],
+        };
+
+        let results = replay_bundle(&bundle, &bank).unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|result| result.result.is_ok()));
+
+        // nothing was committed to the bank
+        assert_eq!(bank.get_balance(&kp.pubkey()), 0);
+        assert_eq!(bank.get_balance(&mint_keypair.pubkey()), 1_000_000);
+        assert_eq!(bank.transaction_count(), 0);
+    }
+
+    #[test]
+    fn test_replay_bundle_failing_transaction() {
+        let (genesis_config, mint_keypair) = create_genesis_config(1_000_000);
+        let bank = Bank::new_for_tests(&genesis_config);
+        let recent_blockhash = bank.last_blockhash();
+        bank.freeze();
+
+        let kp = Keypair::new();
+        let bundle = VersionedBundle {
+            transactions: vec![
+                VersionedTransaction::from(system_transaction::transfer(
+                    &mint_keypair,
+                    &kp.pubkey(),
+                    10_000,
+                    recent_blockhash,
+                )),
+                VersionedTransaction::from(system_transaction::transfer(
+                    &kp,
+                    &Pubkey::new_unique(),
+                    20_000,
+                    recent_blockhash,
+                )),
+            ],
+        };
+
+        let results: Vec<_> = replay_bundle(&bundle, &bank)
+            .unwrap()
+            .into_iter()
+            .map(|result| result.result)
+            .collect();
+        assert_eq!(
+            results,
+            vec![
+                Ok(()),
+                Err(TransactionError::InstructionError(
+                    0,
+                    InstructionError::Custom(1)
+                )),
+            ]
+        );
+        assert_eq!(bank.get_balance(&kp.pubkey()), 0);
+    }
+}
@@ -8,8 +8,9 @@ use {
     crate::{block_cost_limits::*, execute_cost_table::ExecuteCostTable},
     log::*,
     solana_sdk::{
-        instruction::CompiledInstruction, program_utils::limited_deserialize, pubkey::Pubkey,
-        system_instruction::SystemInstruction, system_program, transaction::SanitizedTransaction,
+        bundle::sanitized::SanitizedBundle, instruction::CompiledInstruction,
+        program_utils::limited_deserialize, pubkey::Pubkey, system_instruction::SystemInstruction,
+        system_program, transaction::SanitizedTransaction,
     },
 };
 
@@ -108,6 +109,41 @@ impl CostModel {
         tx_cost
     }
 
+    /// Returns the estimated cost of all the transactions of the bundle.
+    pub fn calculate_bundle_cost(&self, bundle: &SanitizedBundle) -> u64 {
+        bundle
+            .transactions
+            .iter()
+            .map(|tx| self.calculate_cost(tx).sum())
+            .fold(0, u64::saturating_add)
+    }
+
+    /// Returns true if the bundle's estimated cost fits in the `remaining_cu` of the block. A
+    /// bundle that doesn't fit on its own can't be included, so it can be dropped before any of
+    /// its transactions reserve block space.
+    pub fn bundle_fits_in_remaining_block(
+        &self,
+        bundle: &SanitizedBundle,
+        remaining_cu: u64,
+    ) -> bool {
+        self.calculate_bundle_cost(bundle) <= remaining_cu
+    }
+
+    /// Returns how many of the bundle's leading transactions fit in `budget`, for scheduling modes
+    /// that allow executing a prefix of a bundle. Atomic bundles are all-or-nothing and should use
+    /// [`Self::bundle_fits_in_remaining_block`] instead.
+    pub fn bundle_prefix_within_cost(&self, bundle: &SanitizedBundle, budget: u64) -> usize {
+        let mut total_cost: u64 = 0;
+        bundle
+            .transactions
+            .iter()
+            .take_while(|tx| {
+                total_cost = total_cost.saturating_add(self.calculate_cost(tx).sum());
+                total_cost <= budget
+            })
+            .count()
+    }
+
     pub fn upsert_instruction_cost(&mut self, program_key: &Pubkey, cost: u64) {
         self.instruction_execution_cost_table
             .upsert(program_key, cost);
@@ -511,6 +547,73 @@ mod tests {
         assert_eq!(2, tx_cost.writable_accounts.len());
     }
 
+    #[test]
+    fn test_bundle_fits_in_remaining_block() {
+        let (mint_keypair, start_hash) = test_setup();
+        let bundle = SanitizedBundle {
+            transactions: (0..3)
+                .map(|_| {
+                    SanitizedTransaction::from_transaction_for_tests(system_transaction::transfer(
+                        &mint_keypair,
+                        &Keypair::new().pubkey(),
+                        2,
+                        start_hash,
+                    ))
+                })
+                .collect(),
+            uuid: Default::default(),
+            packet_flags: None,
+        };
+
+        let cost_model = CostModel::default();
+        let bundle_cost = cost_model.calculate_bundle_cost(&bundle);
+        let tx_cost = cost_model.calculate_cost(&bundle.transactions[0]).sum();
+        assert_eq!(bundle_cost, tx_cost * 3);
+
+        assert!(cost_model.bundle_fits_in_remaining_block(&bundle, MAX_BLOCK_UNITS));
+        assert!(cost_model.bundle_fits_in_remaining_block(&bundle, bundle_cost));
+        assert!(!cost_model.bundle_fits_in_remaining_block(&bundle, bundle_cost - 1));
+        // fitting each transaction on its own isn't enough
+        assert!(!cost_model.bundle_fits_in_remaining_block(&bundle, tx_cost));
+    }
+
+    #[test]
+    fn test_bundle_prefix_within_cost() {
+        let (mint_keypair, start_hash) = test_setup();
+        let bundle = SanitizedBundle {
+            transactions: (0..3)
+                .map(|_| {
+                    SanitizedTransaction::from_transaction_for_tests(system_transaction::transfer(
+                        &mint_keypair,
+                        &Keypair::new().pubkey(),
+                        2,
+                        start_hash,
+                    ))
+                })
+                .collect(),
+            uuid: Default::default(),
+            packet_flags: None,
+        };
+
+        let cost_model = CostModel::default();
+        let tx_cost = cost_model.calculate_cost(&bundle.transactions[0]).sum();
+        assert_eq!(cost_model.bundle_prefix_within_cost(&bundle, 0), 0);
+        assert_eq!(
+            cost_model.bundle_prefix_within_cost(&bundle, tx_cost - 1),
+            0
+        );
+        assert_eq!(cost_model.bundle_prefix_within_cost(&bundle, tx_cost), 1);
+        assert_eq!(
+            cost_model.bundle_prefix_within_cost(&bundle, tx_cost * 3 - 1),
+            2
+        );
+        assert_eq!(
+            cost_model.bundle_prefix_within_cost(&bundle, tx_cost * 3),
+            3
+        );
+        assert_eq!(cost_model.bundle_prefix_within_cost(&bundle, u64::MAX), 3);
+    }
+
     #[test]
     fn test_cost_model_update_instruction_cost() {
         let key1 = Pubkey::new_unique();
@@ -0,0 +1,125 @@
+#![cfg(feature = "full")]
+
+use {
+    crate::{bundle::VersionedBundle, hash::Hash},
+    std::collections::{BTreeMap, HashMap},
+};
+
+/// Remembers the content hashes of the last bundles seen, see [`VersionedBundle::content_hash`],
+/// so the same bundle received from several relayers or block engines is only processed once.
+/// Once full, the least recently seen bundle is forgotten.
+#[derive(Debug)]
+pub struct BundleDeduper {
+    capacity: usize,
+    /// Content hash of each remembered bundle, with the tick it was last seen at.
+    last_seen: HashMap<Hash, u64>,
+    /// The remembered bundles by the tick they were last seen at, least recent first.
+    by_last_seen: BTreeMap<u64, Hash>,
+    tick: u64,
+}
+
+impl BundleDeduper {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            last_seen: HashMap::with_capacity(capacity),
+            by_last_seen: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    /// Returns true if the bundle wasn't seen recently, remembering it either way. A bundle that
+    /// can't be serialized, and so can't be hashed, is never considered seen: it can't be
+    /// forwarded either and is rejected on its own downstream.
+    pub fn check_and_insert(&mut self, bundle: &VersionedBundle) -> bool {
+        if self.capacity == 0 {
+            return true;
+        }
+        let content_hash = match bundle.content_hash() {
+            Ok(content_hash) => content_hash,
+            Err(_) => return true,
+        };
+        self.tick += 1;
+
+        if let Some(last_seen) = self.last_seen.insert(content_hash, self.tick) {
+            self.by_last_seen.remove(&last_seen);
+            self.by_last_seen.insert(self.tick, content_hash);
+            return false;
+        }
+        self.by_last_seen.insert(self.tick, content_hash);
+
+        if self.last_seen.len() > self.capacity {
+            if let Some(&least_recent_tick) = self.by_last_seen.keys().next() {
+                if let Some(least_recent) = self.by_last_seen.remove(&least_recent_tick) {
+                    self.last_seen.remove(&least_recent);
+                }
+            }
+        }
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.last_seen.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.last_seen.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            pubkey::Pubkey, signature::Keypair, system_transaction,
+            transaction::VersionedTransaction,
+        },
+    };
+
+    fn bundle(lamports: u64) -> VersionedBundle {
+        VersionedBundle {
+            transactions: vec![VersionedTransaction::from(system_transaction::transfer(
+                &Keypair::new(),
+                &Pubkey::new_unique(),
+                lamports,
+                Hash::default(),
+            ))],
+        }
+    }
+
+    #[test]
+    fn test_repeated_and_distinct_bundles() {
+        let mut deduper = BundleDeduper::new(10);
+        let (first, second) = (bundle(1), bundle(2));
+
+        assert!(deduper.check_and_insert(&first));
+        assert!(!deduper.check_and_insert(&first));
+        assert!(!deduper.check_and_insert(&first.clone()));
+        assert!(deduper.check_and_insert(&second));
+        assert!(!deduper.check_and_insert(&second));
+        assert_eq!(deduper.len(), 2);
+    }
+
+    #[test]
+    fn test_least_recently_seen_evicted() {
+        let mut deduper = BundleDeduper::new(2);
+        let bundles: Vec<VersionedBundle> = (0..3).map(bundle).collect();
+
+        assert!(deduper.check_and_insert(&bundles[0]));
+        assert!(deduper.check_and_insert(&bundles[1]));
+        // seeing the first bundle again makes the second one the least recent
+        assert!(!deduper.check_and_insert(&bundles[0]));
+        assert!(deduper.check_and_insert(&bundles[2]));
+        assert_eq!(deduper.len(), 2);
+
+        assert!(!deduper.check_and_insert(&bundles[0]));
+        assert!(!deduper.check_and_insert(&bundles[2]));
+        assert!(deduper.check_and_insert(&bundles[1]));
+
+        let mut deduper = BundleDeduper::new(0);
+        assert!(deduper.check_and_insert(&bundles[0]));
+        assert!(deduper.check_and_insert(&bundles[0]));
+        assert!(deduper.is_empty());
+    }
+}
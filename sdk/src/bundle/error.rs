@@ -1,8 +1,13 @@
 #![cfg(feature = "full")]
 
 use {
-    anchor_lang::error::Error, serde::Deserialize, solana_program::pubkey::Pubkey,
-    solana_sdk::transaction::TransactionError, std::time::Duration, thiserror::Error,
+    crate::bundle::{LookupTableStatus, TipPosition},
+    anchor_lang::error::Error,
+    serde::Deserialize,
+    solana_program::{hash::Hash, pubkey::Pubkey},
+    solana_sdk::transaction::TransactionError,
+    std::time::Duration,
+    thiserror::Error,
 };
 
 #[derive(Error, Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -27,6 +32,108 @@ pub enum BundleExecutionError {
 
     #[error("Error locking bundle because the transaction is malformed")]
     LockError,
+
+    #[error("Simulation exceeded the maximum duration while executing transaction {tx_index}")]
+    TimedOut { tx_index: usize },
+}
+
+#[derive(Error, Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum BundleError {
+    #[error("Bundle {index} has {len} transactions which exceeds the {max} allowed in an entry")]
+    ExceedsEntryTransactionLimit {
+        index: usize,
+        len: usize,
+        max: usize,
+    },
+
+    #[error(
+        "Transaction {index} has {num_instructions} instructions which exceeds the {max} allowed"
+    )]
+    TooManyInstructions {
+        index: usize,
+        num_instructions: usize,
+        max: usize,
+    },
+
+    #[error(
+        "Transaction {index} has {len} bytes of instruction data which exceeds the {max} allowed"
+    )]
+    InstructionDataTooLarge {
+        index: usize,
+        len: usize,
+        max: usize,
+    },
+
+    #[error("Bundle references {count} address lookup tables which exceeds the {max} allowed")]
+    TooManyLookupTables { count: usize, max: usize },
+
+    #[error("Transaction {index} references lookup table {table} which is {status:?}")]
+    UnusableLookupTable {
+        index: usize,
+        table: Pubkey,
+        status: LookupTableStatus,
+    },
+
+    #[error("Expected the tip transaction at {expected:?} but found it at {found:?}")]
+    TipNotInExpectedPosition {
+        expected: TipPosition,
+        found: Option<usize>,
+    },
+
+    #[error("Bundle has no transactions")]
+    Empty,
+
+    #[error("Bundle has {len} transactions which exceeds the {max} allowed")]
+    TooManyTransactions { len: usize, max: usize },
+
+    #[error("Bundle serializes to {bytes} bytes which exceeds the {max} allowed")]
+    TooLarge { bytes: usize, max: usize },
+
+    #[error("Transaction {index} failed to sanitize")]
+    FailedToSanitize { index: usize },
+
+    #[error("Bundle {bundle_index} of the batch is invalid: {error}")]
+    InvalidBundleInBatch {
+        bundle_index: usize,
+        error: Box<BundleError>,
+    },
+
+    #[error("Failed to deserialize bundle: {0}")]
+    Deserialize(String),
+
+    #[error("Failed to serialize bundle: {0}")]
+    Serialize(String),
+
+    #[error("Transaction {index} has no signature")]
+    MissingSignature { index: usize },
+
+    #[error("Transaction {index} is missing signatures {signature_indices:?}")]
+    MissingSignatures {
+        index: usize,
+        signature_indices: Vec<usize>,
+    },
+
+    #[error("Transaction {index} duplicates transaction {first_index}")]
+    DuplicateTransaction { index: usize, first_index: usize },
+
+    #[error("Nonce account {0} is advanced by more than one transaction")]
+    DuplicateNonceAccount(Pubkey),
+
+    #[error("Transaction {index} uses blockhash {blockhash} which isn't recent")]
+    UnknownBlockhash { index: usize, blockhash: Hash },
+
+    #[error("Transaction {index} uses a different blockhash than transaction {first_index}")]
+    NonUniformBlockhash { index: usize, first_index: usize },
+
+    #[error("Bundle doesn't pay a tip")]
+    MissingTip,
+
+    #[error("Fee payer {fee_payer} needs {required} lamports but only has {balance}")]
+    FeePayerInsolvent {
+        fee_payer: Pubkey,
+        required: u64,
+        balance: u64,
+    },
 }
 
 #[derive(Error, Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
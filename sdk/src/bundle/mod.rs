@@ -1,12 +1,2118 @@
 #![cfg(feature = "full")]
 
-use crate::transaction::VersionedTransaction;
+use {
+    crate::{
+        borsh::try_from_slice_unchecked,
+        bundle::error::BundleError,
+        clock::{Slot, MAX_PROCESSING_AGE},
+        compute_budget::{self, ComputeBudgetInstruction},
+        fee_calculator::DEFAULT_TARGET_LAMPORTS_PER_SIGNATURE,
+        hash::{hashv, Hash, Hasher},
+        packet::PACKET_DATA_SIZE,
+        program_utils::limited_deserialize,
+        pubkey::Pubkey,
+        signature::Signature,
+        system_instruction::SystemInstruction,
+        system_program,
+        transaction::VersionedTransaction,
+        vote,
+    },
+    std::collections::{hash_map::Entry, HashMap, HashSet},
+};
 
+pub mod deduper;
 pub mod error;
 pub mod sanitized;
 pub mod utils;
 
+/// Domain separator of [`VersionedBundle::canonical_id`], bumped if the preimage ever changes.
+pub const CANONICAL_ID_DOMAIN: &[u8] = b"jito-bundle-id-v1";
+
+/// Mirrors `solana_program_runtime::compute_budget::DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT`.
+const DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT: u32 = 200_000;
+/// Mirrors `solana_program_runtime::compute_budget::MAX_COMPUTE_UNIT_LIMIT`.
+const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+/// Lamports charged per signature at the rate the clusters run with.
+const LAMPORTS_PER_SIGNATURE: u64 = DEFAULT_TARGET_LAMPORTS_PER_SIGNATURE / 2;
+/// Mirrors `solana_sdk::fee::MICRO_LAMPORTS_PER_LAMPORT` of the prioritization fee.
+const MICRO_LAMPORTS_PER_LAMPORT: u64 = 1_000_000;
+/// Mirrors `solana_program_runtime::compute_budget::ComputeBudget::max_instruction_trace_length`.
+const MAX_INSTRUCTIONS_PER_TRANSACTION: usize = 64;
+/// A serialized transaction must fit in a single packet, so its instruction data can't exceed it either.
+const MAX_INSTRUCTION_DATA_PER_TRANSACTION: usize = PACKET_DATA_SIZE;
+
+/// Where a bundle is expected to carry its tip transaction, see
+/// [`VersionedBundle::validate_tip_position`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum TipPosition {
+    First,
+    Last,
+    Index(usize),
+}
+
+/// Status of an address lookup table, see [`VersionedBundle::validate_lookup_tables`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum LookupTableStatus {
+    Active,
+    /// Deactivated tables can no longer be used to load addresses, even before they're closed.
+    Deactivated,
+    NotFound,
+}
+
+/// Limits and cluster state a bundle is checked against, see
+/// [`VersionedBundle::validate_for_submission`].
+#[derive(Debug, Clone, Default)]
+pub struct SubmissionContext {
+    pub max_len: usize,
+    pub max_serialized_bytes: usize,
+    /// Accounts one of which the bundle must tip, the tip isn't checked if empty.
+    pub tip_accounts: HashSet<Pubkey>,
+    /// Blockhashes recent enough for the bundle to use.
+    pub recent_blockhashes: HashSet<Hash>,
+}
+
+impl TipPosition {
+    /// Returns the expected index of the tip transaction in a bundle of `len` transactions.
+    fn index(&self, len: usize) -> Option<usize> {
+        let index = match self {
+            Self::First => 0,
+            Self::Last => len.checked_sub(1)?,
+            Self::Index(index) => *index,
+        };
+        if index < len {
+            Some(index)
+        } else {
+            None
+        }
+    }
+}
+
+/// A difference between the transactions at the same index of two bundles.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum TransactionDiff {
+    /// Both bundles have a transaction at this index but they differ.
+    Changed {
+        index: usize,
+        signatures_differ: bool,
+        messages_differ: bool,
+    },
+    /// Only the other bundle has a transaction at this index.
+    Added { index: usize },
+    /// Only this bundle has a transaction at this index.
+    Removed { index: usize },
+}
+
+/// The per-index differences between two bundles, see [`VersionedBundle::diff`].
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct BundleDiff {
+    pub differences: Vec<TransactionDiff>,
+}
+
+impl BundleDiff {
+    /// Returns true if the bundles are identical.
+    pub fn is_empty(&self) -> bool {
+        self.differences.is_empty()
+    }
+}
+
 #[derive(Debug, PartialEq, Default, Eq, Clone, Serialize, Deserialize)]
 pub struct VersionedBundle {
     pub transactions: Vec<VersionedTransaction>,
 }
+
+impl VersionedBundle {
+    /// Returns the compute-unit weighted price of the bundle in micro-lamports per compute unit.
+    /// This is the aggregate priority fee divided by the aggregate compute unit limit, which allows
+    /// bundles of different sizes to be ranked against one another.
+    /// Returns 0 if the bundle doesn't request any compute units.
+    pub fn effective_cu_price(&self) -> u64 {
+        let (total_priority_micro_lamports, total_compute_units) = self
+            .transactions
+            .iter()
+            .map(compute_budget_limits)
+            .fold((0u128, 0u128), |(fees, units), (cu_limit, cu_price)| {
+                (
+                    fees.saturating_add((cu_limit as u128).saturating_mul(cu_price as u128)),
+                    units.saturating_add(cu_limit as u128),
+                )
+            });
+
+        total_priority_micro_lamports
+            .checked_div(total_compute_units)
+            .map(|price| price.min(u64::MAX as u128) as u64)
+            .unwrap_or_default()
+    }
+
+    /// Returns true if any transaction in the bundle is a simple vote transaction.
+    pub fn contains_vote_transaction(&self) -> bool {
+        self.transactions.iter().any(is_simple_vote_transaction)
+    }
+
+    /// Returns the indices of the transactions that aren't simple vote transactions.
+    /// The bundle itself is left untouched since removing transactions would break its atomicity.
+    pub fn non_vote_transaction_indices(&self) -> Vec<usize> {
+        self.transactions
+            .iter()
+            .enumerate()
+            .filter(|(_, tx)| !is_simple_vote_transaction(tx))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Compares this bundle against `other` index by index, reporting transactions whose signatures
+    /// or message digests differ along with transactions present in only one of the bundles.
+    pub fn diff(&self, other: &VersionedBundle) -> BundleDiff {
+        let num_transactions = self.transactions.len().max(other.transactions.len());
+        let differences = (0..num_transactions)
+            .filter_map(|index| {
+                match (self.transactions.get(index), other.transactions.get(index)) {
+                    (Some(tx), Some(other_tx)) => {
+                        let signatures_differ = tx.signatures != other_tx.signatures;
+                        let messages_differ = tx.message.hash() != other_tx.message.hash();
+                        (signatures_differ || messages_differ).then(|| TransactionDiff::Changed {
+                            index,
+                            signatures_differ,
+                            messages_differ,
+                        })
+                    }
+                    (None, Some(_)) => Some(TransactionDiff::Added { index }),
+                    (Some(_), None) => Some(TransactionDiff::Removed { index }),
+                    (None, None) => None,
+                }
+            })
+            .collect();
+        BundleDiff { differences }
+    }
+
+    /// Checks each transaction against the runtime's per-transaction instruction limits so bundles
+    /// that would fail during execution can be rejected early. Returns an error naming the first
+    /// offending transaction.
+    pub fn validate_instruction_limits(&self) -> Result<(), BundleError> {
+        for (index, tx) in self.transactions.iter().enumerate() {
+            let instructions = tx.message.instructions();
+            if instructions.len() > MAX_INSTRUCTIONS_PER_TRANSACTION {
+                return Err(BundleError::TooManyInstructions {
+                    index,
+                    num_instructions: instructions.len(),
+                    max: MAX_INSTRUCTIONS_PER_TRANSACTION,
+                });
+            }
+
+            let data_len = instructions
+                .iter()
+                .fold(0usize, |len, ix| len.saturating_add(ix.data.len()));
+            if data_len > MAX_INSTRUCTION_DATA_PER_TRANSACTION {
+                return Err(BundleError::InstructionDataTooLarge {
+                    index,
+                    len: data_len,
+                    max: MAX_INSTRUCTION_DATA_PER_TRANSACTION,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the number of distinct address lookup tables referenced by the bundle.
+    pub fn lookup_table_count(&self) -> usize {
+        sanitized::lookup_table_count(self.transactions.iter().map(|tx| &tx.message))
+    }
+
+    /// Checks that every address lookup table referenced by the bundle is active according to
+    /// `status`, since transactions referencing deactivated or nonexistent tables fail to sanitize.
+    /// Returns an error naming the first offending table and transaction.
+    pub fn validate_lookup_tables(
+        &self,
+        status: impl Fn(&Pubkey) -> LookupTableStatus,
+    ) -> Result<(), BundleError> {
+        for (index, tx) in self.transactions.iter().enumerate() {
+            for lookup in tx.message.address_table_lookups().unwrap_or_default() {
+                match status(&lookup.account_key) {
+                    LookupTableStatus::Active => {}
+                    status => {
+                        return Err(BundleError::UnusableLookupTable {
+                            index,
+                            table: lookup.account_key,
+                            status,
+                        })
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the indices of the transactions invoking any of the `disabled` programs, such as
+    /// deprecated loaders or programs whose feature isn't active, which would always fail.
+    pub fn invokes_disabled_programs(&self, disabled: &HashSet<Pubkey>) -> Vec<usize> {
+        self.transactions
+            .iter()
+            .enumerate()
+            .filter(|(_, tx)| {
+                let account_keys = tx.message.static_account_keys();
+                tx.message.instructions().iter().any(|ix| {
+                    account_keys
+                        .get(ix.program_id_index as usize)
+                        .map_or(false, |program_id| disabled.contains(program_id))
+                })
+            })
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Returns the index of the first transaction that writes to one of the `tip_accounts`.
+    /// Tip accounts loaded through address lookup tables aren't considered.
+    pub fn tip_transaction_index(&self, tip_accounts: &HashSet<Pubkey>) -> Option<usize> {
+        self.transactions.iter().position(|tx| {
+            tx.message
+                .static_account_keys()
+                .iter()
+                .enumerate()
+                .any(|(i, key)| tip_accounts.contains(key) && tx.message.is_maybe_writable(i))
+        })
+    }
+
+    /// Checks that the tip transaction is at the `expected` position, catching bundles built with
+    /// the tip in the wrong place or without a tip at all.
+    pub fn validate_tip_position(
+        &self,
+        tip_accounts: &HashSet<Pubkey>,
+        expected: TipPosition,
+    ) -> Result<(), BundleError> {
+        let found = self.tip_transaction_index(tip_accounts);
+        match found {
+            Some(index) if Some(index) == expected.index(self.transactions.len()) => Ok(()),
+            _ => Err(BundleError::TipNotInExpectedPosition { expected, found }),
+        }
+    }
+
+    /// Returns the inclusive (first, last) slot range in which every transaction's recent
+    /// blockhash is still valid, given the slot at which each blockhash was produced.
+    ///
+    /// Durable nonce transactions don't constrain the window. Returns `None` if the bundle only
+    /// contains durable nonce transactions, references an unknown blockhash, or its blockhashes
+    /// have no overlapping validity.
+    pub fn validity_window(
+        &self,
+        recent_blockhash_slots: &HashMap<Hash, Slot>,
+    ) -> Option<(Slot, Slot)> {
+        let mut window: Option<(Slot, Slot)> = None;
+        for tx in self
+            .transactions
+            .iter()
+            .filter(|tx| !tx.uses_durable_nonce())
+        {
+            let slot = *recent_blockhash_slots.get(tx.message.recent_blockhash())?;
+            let expiry = slot.saturating_add(MAX_PROCESSING_AGE as Slot);
+            window = Some(match window {
+                Some((first, last)) => (first.max(slot), last.min(expiry)),
+                None => (slot, expiry),
+            });
+        }
+        window.filter(|(first, last)| first <= last)
+    }
+
+    /// Returns true if the bundle looks like it meant to tip but sent the tip elsewhere: none of
+    /// its transactions write to the `tip_accounts` and the last one, where tips usually go, is a
+    /// lone system transfer. Meant as a conservative diagnostic for searchers, so single
+    /// transaction bundles and anything more involved than a plain transfer aren't flagged.
+    pub fn tip_to_unrecognized_account(&self, tip_accounts: &HashSet<Pubkey>) -> bool {
+        if self.transactions.len() < 2 || self.tip_transaction_index(tip_accounts).is_some() {
+            return false;
+        }
+        self.transactions
+            .last()
+            .and_then(lone_system_transfer_destination)
+            .map_or(false, |destination| !tip_accounts.contains(destination))
+    }
+
+    /// Returns a concise summary of the bundle for logs, such as
+    /// `bundle[3 txs, sigs=5j7s6NiJ..,4Hc8zXSa..,3Wkq9e7K.., ~1.2KB]`: the number of transactions, the
+    /// first characters of each transaction's signature and the serialized size. Only public,
+    /// on-chain data is included.
+    pub fn summary(&self) -> String {
+        const SIGNATURE_PREFIX_LEN: usize = 8;
+
+        let signatures: Vec<String> = self
+            .signatures()
+            .iter()
+            .map(|signature| {
+                let prefix: String = signature
+                    .to_string()
+                    .chars()
+                    .take(SIGNATURE_PREFIX_LEN)
+                    .collect();
+                format!("{}..", prefix)
+            })
+            .collect();
+        let size: u64 = self
+            .transactions
+            .iter()
+            .map(|tx| bincode::serialized_size(tx).unwrap_or_default())
+            .sum();
+        format!(
+            "bundle[{} txs, sigs={}, ~{:.1}KB]",
+            self.transactions.len(),
+            signatures.join(","),
+            size as f64 / 1024.0
+        )
+    }
+
+    /// Returns the largest number of distinct accounts referenced by a single transaction.
+    pub fn max_accounts_per_transaction(&self) -> usize {
+        self.transactions
+            .iter()
+            .map(|tx| account_references(tx).len())
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Returns the number of distinct accounts referenced across all the transactions.
+    pub fn total_unique_accounts(&self) -> usize {
+        self.transactions
+            .iter()
+            .flat_map(account_references)
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    /// Returns true if every signature required by the bundle's transactions is present.
+    pub fn is_fully_signed(&self) -> bool {
+        self.missing_signatures().is_empty()
+    }
+
+    /// Returns the (transaction index, signature index) of every required signature that's missing,
+    /// either left as the all-zero placeholder or absent altogether.
+    pub fn missing_signatures(&self) -> Vec<(usize, usize)> {
+        self.transactions
+            .iter()
+            .enumerate()
+            .flat_map(|(tx_index, tx)| {
+                let num_required_signatures = tx.message.header().num_required_signatures as usize;
+                (0..num_required_signatures.max(tx.signatures.len()))
+                    .filter(move |signature_index| {
+                        tx.signatures
+                            .get(*signature_index)
+                            .map_or(true, |signature| *signature == Signature::default())
+                    })
+                    .map(move |signature_index| (tx_index, signature_index))
+            })
+            .collect()
+    }
+
+    /// Returns an identifier for the bundle that relayers, searchers and validators can agree on.
+    /// Only the transaction signatures are hashed so the id doesn't depend on how the transactions
+    /// are encoded. The id is the SHA-256 of:
+    ///
+    /// ```text
+    /// CANONICAL_ID_DOMAIN || num transactions as a little-endian u64 || first signature of each
+    /// transaction, in bundle order (64 bytes each, all zeroes for an unsigned transaction)
+    /// ```
+    pub fn canonical_id(&self) -> [u8; 32] {
+        let num_transactions = (self.transactions.len() as u64).to_le_bytes();
+        let signatures = self.signatures();
+
+        let mut preimage: Vec<&[u8]> = Vec::with_capacity(signatures.len() + 2);
+        preimage.push(CANONICAL_ID_DOMAIN);
+        preimage.push(&num_transactions);
+        preimage.extend(signatures.iter().map(|signature| signature.as_ref()));
+        hashv(&preimage).to_bytes()
+    }
+
+    /// Returns the indices of the transactions that don't fit in a packet on their own and would
+    /// be dropped before reaching the validator, regardless of the bundle's overall size.
+    pub fn oversized_transaction_indices(&self) -> Vec<usize> {
+        self.transactions
+            .iter()
+            .enumerate()
+            .filter(|(_, tx)| {
+                bincode::serialized_size(tx).map_or(true, |size| size > PACKET_DATA_SIZE as u64)
+            })
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Returns the hex encoded SHA-256 of the concatenated first signature of each transaction, in
+    /// bundle order, so searchers can correlate a submitted bundle with what landed. Unlike
+    /// [`Self::canonical_id`] the preimage isn't domain separated, matching the ids searchers
+    /// already compute from the signatures alone.
+    pub fn uuid(&self) -> String {
+        let signatures = self.signatures();
+        let preimage: Vec<&[u8]> = signatures
+            .iter()
+            .map(|signature| signature.as_ref())
+            .collect();
+        hashv(&preimage)
+            .to_bytes()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    /// Checks the bundle isn't empty and stays within `max_len` transactions and
+    /// `max_serialized_bytes` of bincode serialized transactions, so oversized bundles can be
+    /// rejected before sanitization.
+    pub fn validate(&self, max_len: usize, max_serialized_bytes: usize) -> Result<(), BundleError> {
+        match self
+            .limit_errors(max_len, max_serialized_bytes)
+            .into_iter()
+            .next()
+        {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns every limit [`Self::validate`] checks that the bundle exceeds, an empty bundle only
+    /// being reported as such.
+    fn limit_errors(&self, max_len: usize, max_serialized_bytes: usize) -> Vec<BundleError> {
+        if self.transactions.is_empty() {
+            return vec![BundleError::Empty];
+        }
+
+        let mut errors = Vec::new();
+        if self.transactions.len() > max_len {
+            errors.push(BundleError::TooManyTransactions {
+                len: self.transactions.len(),
+                max: max_len,
+            });
+        }
+
+        let bytes = self
+            .transactions
+            .iter()
+            .map(|tx| bincode::serialized_size(tx).unwrap_or(u64::MAX))
+            .fold(0u64, u64::saturating_add);
+        if bytes > max_serialized_bytes as u64 {
+            errors.push(BundleError::TooLarge {
+                bytes: usize::try_from(bytes).unwrap_or(usize::MAX),
+                max: max_serialized_bytes,
+            });
+        }
+        errors
+    }
+
+    /// Serializes the bundle to a JSON array holding each transaction as base64 encoded bincode,
+    /// in order, the same encoding the `sendBundle` RPC endpoint accepts. Fails if a transaction
+    /// can't be serialized, e.g. because it has more signatures or instructions than fit in its
+    /// compact length prefix.
+    pub fn to_json(&self) -> Result<String, BundleError> {
+        let transactions = self
+            .transactions
+            .iter()
+            .enumerate()
+            .map(|(index, tx)| {
+                bincode::serialize(tx).map(base64::encode).map_err(|e| {
+                    BundleError::Serialize(format!("transaction {} failed: {}", index, e))
+                })
+            })
+            .collect::<Result<Vec<String>, _>>()?;
+        serde_json::to_string(&transactions)
+            .map_err(|e| BundleError::Serialize(format!("invalid json: {}", e)))
+    }
+
+    /// Parses a bundle serialized by [`Self::to_json`].
+    pub fn from_json(json: &str) -> Result<Self, BundleError> {
+        let encoded: Vec<String> = serde_json::from_str(json)
+            .map_err(|e| BundleError::Deserialize(format!("invalid json: {}", e)))?;
+        let transactions = encoded
+            .iter()
+            .enumerate()
+            .map(|(index, encoded)| {
+                let bytes = base64::decode(encoded).map_err(|e| {
+                    BundleError::Deserialize(format!("transaction {} isn't base64: {}", index, e))
+                })?;
+                bincode::deserialize(&bytes).map_err(|e| {
+                    BundleError::Deserialize(format!("transaction {} is malformed: {}", index, e))
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { transactions })
+    }
+
+    /// Returns the first signature of each transaction in order, the all-zero signature standing
+    /// in for a transaction without any. See [`Self::try_signatures`] to reject those instead.
+    pub fn signatures(&self) -> Vec<Signature> {
+        self.transactions
+            .iter()
+            .map(|tx| tx.signatures.first().copied().unwrap_or_default())
+            .collect()
+    }
+
+    /// Returns the first signature of each transaction in order, failing on the first transaction
+    /// without any.
+    pub fn try_signatures(&self) -> Result<Vec<Signature>, BundleError> {
+        self.transactions
+            .iter()
+            .enumerate()
+            .map(|(index, tx)| {
+                tx.signatures
+                    .first()
+                    .copied()
+                    .ok_or(BundleError::MissingSignature { index })
+            })
+            .collect()
+    }
+
+    /// Returns every signature of every transaction, in order.
+    pub fn all_signatures(&self) -> Vec<Signature> {
+        self.transactions
+            .iter()
+            .flat_map(|tx| tx.signatures.iter().copied())
+            .collect()
+    }
+
+    /// Returns the compute units requested across the transactions. A transaction's last
+    /// `SetComputeUnitLimit` instruction wins as in the runtime, transactions without one get the
+    /// default limit per instruction.
+    pub fn requested_compute_units(&self) -> u64 {
+        self.transactions
+            .iter()
+            .map(|transaction| compute_budget_limits(transaction).0)
+            .fold(0, u64::saturating_add)
+    }
+
+    /// Returns the durable nonce accounts advanced by more than one transaction, in order of first
+    /// use. Such bundles can't land: the nonce has changed by the time the second transaction
+    /// executes. Nonce accounts loaded through address lookup tables aren't considered.
+    pub fn duplicate_nonce_accounts(&self) -> Vec<Pubkey> {
+        let mut num_transactions_by_nonce: HashMap<&Pubkey, usize> = HashMap::new();
+        let mut nonce_accounts = Vec::new();
+        for tx in &self.transactions {
+            let tx_nonce_accounts: HashSet<&Pubkey> = advanced_nonce_accounts(tx).collect();
+            for nonce_account in tx_nonce_accounts {
+                let num_transactions = num_transactions_by_nonce.entry(nonce_account).or_default();
+                *num_transactions += 1;
+                if *num_transactions == 2 {
+                    nonce_accounts.push(*nonce_account);
+                }
+            }
+        }
+        nonce_accounts
+    }
+
+    /// Returns the lamports sent to the `tip_accounts` across the bundle. Only top-level system
+    /// transfers to statically referenced accounts are counted: tips paid through CPIs or to
+    /// accounts loaded from address lookup tables can't be seen without executing the bundle.
+    pub fn tip_lamports(&self, tip_accounts: &HashSet<Pubkey>) -> u64 {
+        self.transactions
+            .iter()
+            .flat_map(|tx| {
+                let account_keys = tx.message.static_account_keys();
+                tx.message.instructions().iter().filter_map(move |ix| {
+                    if !system_program::check_id(account_keys.get(ix.program_id_index as usize)?) {
+                        return None;
+                    }
+                    match limited_deserialize(&ix.data) {
+                        Ok(SystemInstruction::Transfer { lamports }) => {
+                            let destination = account_keys.get(*ix.accounts.get(1)? as usize)?;
+                            tip_accounts.contains(destination).then_some(lamports)
+                        }
+                        _ => None,
+                    }
+                })
+            })
+            .fold(0, u64::saturating_add)
+    }
+
+    /// Returns the hash of the bincode serialized transactions, in bundle order. Unlike
+    /// [`Self::canonical_id`], which only covers the signatures, any change to the transactions
+    /// changes the hash, making it suitable to dedup bundles received from several sources. Fails
+    /// if a transaction can't be serialized, see [`Self::to_json`].
+    pub fn content_hash(&self) -> Result<Hash, BundleError> {
+        let mut hasher = Hasher::default();
+        for (index, tx) in self.transactions.iter().enumerate() {
+            let serialized = bincode::serialize(tx).map_err(|e| {
+                BundleError::Serialize(format!("transaction {} failed: {}", index, e))
+            })?;
+            hasher.hash(&serialized);
+        }
+        Ok(hasher.result())
+    }
+
+    /// Checks that every fee payer's balance, as returned by `balances`, covers what the bundle
+    /// takes from it: signature and prioritization fees plus the lamports it sends through top-level
+    /// system transfers, tips included, summed over all the transactions it pays for. Returns an
+    /// error naming the first payer that falls short. Lamports a payer receives within the bundle
+    /// aren't credited, so this errs on the side of flagging.
+    pub fn check_fee_payer_solvency(
+        &self,
+        balances: impl Fn(&Pubkey) -> u64,
+    ) -> Result<(), BundleError> {
+        let mut required_by_payer: Vec<(Pubkey, u64)> = Vec::new();
+        for tx in &self.transactions {
+            let account_keys = tx.message.static_account_keys();
+            let fee_payer = match account_keys.first() {
+                Some(fee_payer) => fee_payer,
+                None => continue,
+            };
+
+            let (compute_unit_limit, compute_unit_price) = compute_budget_limits(tx);
+            let prioritization_fee = (compute_unit_limit as u128)
+                .saturating_mul(compute_unit_price as u128)
+                .saturating_add(MICRO_LAMPORTS_PER_LAMPORT as u128 - 1)
+                / MICRO_LAMPORTS_PER_LAMPORT as u128;
+            let signature_fee = LAMPORTS_PER_SIGNATURE
+                .saturating_mul(tx.message.header().num_required_signatures as u64);
+            let transferred: u64 = tx
+                .message
+                .instructions()
+                .iter()
+                .filter_map(|ix| {
+                    if !system_program::check_id(account_keys.get(ix.program_id_index as usize)?)
+                        || account_keys.get(*ix.accounts.first()? as usize)? != fee_payer
+                    {
+                        return None;
+                    }
+                    match limited_deserialize(&ix.data) {
+                        Ok(SystemInstruction::Transfer { lamports }) => Some(lamports),
+                        _ => None,
+                    }
+                })
+                .fold(0, u64::saturating_add);
+            let required = signature_fee
+                .saturating_add(prioritization_fee.min(u64::MAX as u128) as u64)
+                .saturating_add(transferred);
+
+            match required_by_payer
+                .iter_mut()
+                .find(|(payer, _)| payer == fee_payer)
+            {
+                Some((_, total)) => *total = total.saturating_add(required),
+                None => required_by_payer.push((*fee_payer, required)),
+            }
+        }
+
+        for (fee_payer, required) in required_by_payer {
+            let balance = balances(&fee_payer);
+            if balance < required {
+                return Err(BundleError::FeePayerInsolvent {
+                    fee_payer,
+                    required,
+                    balance,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs every cheap static check against `ctx` and returns all the failures rather than only
+    /// the first one, so a searcher can fix everything wrong with a bundle in one go: size,
+    /// signatures, a single recent blockhash, duplicate transactions and nonce accounts, and the
+    /// tip. Durable nonce transactions don't use a recent blockhash and aren't held to it. An
+    /// empty bundle is only reported as such.
+    pub fn validate_for_submission(&self, ctx: &SubmissionContext) -> Result<(), Vec<BundleError>> {
+        if self.transactions.is_empty() {
+            return Err(vec![BundleError::Empty]);
+        }
+
+        let mut errors = self.limit_errors(ctx.max_len, ctx.max_serialized_bytes);
+
+        for (index, signature_index) in self.missing_signatures() {
+            match errors.last_mut() {
+                Some(BundleError::MissingSignatures {
+                    index: last_index,
+                    signature_indices,
+                }) if *last_index == index => signature_indices.push(signature_index),
+                _ => errors.push(BundleError::MissingSignatures {
+                    index,
+                    signature_indices: vec![signature_index],
+                }),
+            }
+        }
+
+        let mut first_blockhash: Option<(usize, &Hash)> = None;
+        for (index, tx) in self.transactions.iter().enumerate() {
+            if tx.uses_durable_nonce() {
+                continue;
+            }
+            let blockhash = tx.message.recent_blockhash();
+            match first_blockhash {
+                None => {
+                    first_blockhash = Some((index, blockhash));
+                    if !ctx.recent_blockhashes.contains(blockhash) {
+                        errors.push(BundleError::UnknownBlockhash {
+                            index,
+                            blockhash: *blockhash,
+                        });
+                    }
+                }
+                Some((first_index, first_blockhash)) if blockhash != first_blockhash => {
+                    errors.push(BundleError::NonUniformBlockhash { index, first_index })
+                }
+                Some(_) => {}
+            }
+        }
+
+        let signatures = self.signatures();
+        let mut first_index_by_signature: HashMap<&Signature, usize> = HashMap::new();
+        for (index, signature) in signatures.iter().enumerate() {
+            if *signature == Signature::default() {
+                continue;
+            }
+            match first_index_by_signature.entry(signature) {
+                Entry::Occupied(entry) => errors.push(BundleError::DuplicateTransaction {
+                    index,
+                    first_index: *entry.get(),
+                }),
+                Entry::Vacant(entry) => {
+                    entry.insert(index);
+                }
+            }
+        }
+        errors.extend(
+            self.duplicate_nonce_accounts()
+                .into_iter()
+                .map(BundleError::DuplicateNonceAccount),
+        );
+
+        if !ctx.tip_accounts.is_empty() && self.tip_transaction_index(&ctx.tip_accounts).is_none() {
+            errors.push(BundleError::MissingTip);
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Uses the same heuristic as [`crate::transaction::SanitizedTransaction`]: a simple vote
+/// transaction is one whose first instruction invokes the vote program.
+fn is_simple_vote_transaction(transaction: &VersionedTransaction) -> bool {
+    let account_keys = transaction.message.static_account_keys();
+    transaction
+        .message
+        .instructions()
+        .first()
+        .and_then(|ix| account_keys.get(ix.program_id_index as usize))
+        == Some(&vote::program::id())
+}
+
+/// Returns the (compute unit limit, compute unit price) requested by the transaction, using the
+/// same defaults the runtime applies when no compute budget instructions are present.
+fn compute_budget_limits(transaction: &VersionedTransaction) -> (u64, u64) {
+    let account_keys = transaction.message.static_account_keys();
+    compute_budget_limits_of_instructions(transaction.message.instructions().iter().map(|ix| {
+        (
+            account_keys.get(ix.program_id_index as usize),
+            ix.data.as_slice(),
+        )
+    }))
+}
+
+/// Same as [`compute_budget_limits`] for instructions given as their program id, None if it can't
+/// be resolved, and data.
+fn compute_budget_limits_of_instructions<'a>(
+    instructions: impl IntoIterator<Item = (Option<&'a Pubkey>, &'a [u8])>,
+) -> (u64, u64) {
+    let mut num_non_compute_budget_instructions: u32 = 0;
+    let mut compute_unit_limit = None;
+    let mut compute_unit_price = 0;
+    for (program_id, data) in instructions {
+        match program_id {
+            Some(program_id) if compute_budget::check_id(program_id) => {
+                match try_from_slice_unchecked(data) {
+                    Ok(ComputeBudgetInstruction::SetComputeUnitLimit(units)) => {
+                        compute_unit_limit = Some(units);
+                    }
+                    Ok(ComputeBudgetInstruction::SetComputeUnitPrice(micro_lamports)) => {
+                        compute_unit_price = micro_lamports;
+                    }
+                    _ => {}
+                }
+            }
+            _ => {
+                num_non_compute_budget_instructions =
+                    num_non_compute_budget_instructions.saturating_add(1);
+            }
+        }
+    }
+
+    let compute_unit_limit = compute_unit_limit
+        .unwrap_or_else(|| {
+            num_non_compute_budget_instructions
+                .saturating_mul(DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT)
+        })
+        .min(MAX_COMPUTE_UNIT_LIMIT);
+
+    (compute_unit_limit as u64, compute_unit_price)
+}
+
+/// Returns the destination of the transaction's transfer if it's made of a single system transfer,
+/// ignoring compute budget instructions.
+fn lone_system_transfer_destination(transaction: &VersionedTransaction) -> Option<&Pubkey> {
+    let account_keys = transaction.message.static_account_keys();
+    let mut instructions = transaction.message.instructions().iter().filter(|ix| {
+        !matches!(
+            account_keys.get(ix.program_id_index as usize),
+            Some(program_id) if compute_budget::check_id(program_id)
+        )
+    });
+    let ix = instructions.next()?;
+    if instructions.next().is_some()
+        || !system_program::check_id(account_keys.get(ix.program_id_index as usize)?)
+    {
+        return None;
+    }
+    match limited_deserialize(&ix.data) {
+        Ok(SystemInstruction::Transfer { .. }) => account_keys.get(*ix.accounts.get(1)? as usize),
+        _ => None,
+    }
+}
+
+/// Returns the nonce accounts of the transaction's `AdvanceNonceAccount` instructions that are
+/// statically referenced.
+fn advanced_nonce_accounts(transaction: &VersionedTransaction) -> impl Iterator<Item = &Pubkey> {
+    let account_keys = transaction.message.static_account_keys();
+    transaction
+        .message
+        .instructions()
+        .iter()
+        .filter(move |ix| {
+            matches!(
+                account_keys.get(ix.program_id_index as usize),
+                Some(program_id) if system_program::check_id(program_id)
+            ) && matches!(
+                limited_deserialize(&ix.data),
+                Ok(SystemInstruction::AdvanceNonceAccount)
+            )
+        })
+        .filter_map(move |ix| account_keys.get(*ix.accounts.first()? as usize))
+}
+
+/// Returns the distinct accounts referenced by the transaction. Accounts loaded from address lookup
+/// tables can't be resolved here, so they're identified by their table and index instead.
+fn account_references(transaction: &VersionedTransaction) -> HashSet<(Pubkey, Option<u8>)> {
+    let static_accounts = transaction
+        .message
+        .static_account_keys()
+        .iter()
+        .map(|key| (*key, None));
+    let lookup_accounts = transaction
+        .message
+        .address_table_lookups()
+        .unwrap_or_default()
+        .iter()
+        .flat_map(|lookup| {
+            lookup
+                .writable_indexes
+                .iter()
+                .chain(lookup.readonly_indexes.iter())
+                .map(|index| (lookup.account_key, Some(*index)))
+        });
+    static_accounts.chain(lookup_accounts).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            hash::Hash,
+            instruction::{AccountMeta, Instruction},
+            message::{v0, Message, VersionedMessage},
+            pubkey::Pubkey,
+            signature::{Keypair, Signature, Signer},
+            system_instruction, system_transaction,
+            transaction::Transaction,
+        },
+    };
+
+    fn priced_transfer(payer: &Keypair, cu_limit: u32, cu_price: u64) -> VersionedTransaction {
+        let instructions: Vec<Instruction> = vec![
+            ComputeBudgetInstruction::set_compute_unit_limit(cu_limit),
+            ComputeBudgetInstruction::set_compute_unit_price(cu_price),
+            system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1),
+        ];
+        VersionedTransaction::from(Transaction::new_signed_with_payer(
+            &instructions,
+            Some(&payer.pubkey()),
+            &[payer],
+            Hash::default(),
+        ))
+    }
+
+    #[test]
+    fn test_effective_cu_price_small_high_price_beats_large_low_price() {
+        let payer = Keypair::new();
+
+        let small_bundle = VersionedBundle {
+            transactions: vec![priced_transfer(&payer, 10_000, 1_000)],
+        };
+        let large_bundle = VersionedBundle {
+            transactions: (0..5)
+                .map(|_| priced_transfer(&payer, 200_000, 10))
+                .collect(),
+        };
+
+        assert_eq!(small_bundle.effective_cu_price(), 1_000);
+        assert_eq!(large_bundle.effective_cu_price(), 10);
+        assert!(small_bundle.effective_cu_price() > large_bundle.effective_cu_price());
+    }
+
+    #[test]
+    fn test_effective_cu_price_is_weighted_by_compute_units() {
+        let payer = Keypair::new();
+        let bundle = VersionedBundle {
+            transactions: vec![
+                priced_transfer(&payer, 100_000, 100),
+                priced_transfer(&payer, 300_000, 0),
+            ],
+        };
+        assert_eq!(bundle.effective_cu_price(), 25);
+    }
+
+    #[test]
+    fn test_effective_cu_price_zero_compute_units() {
+        assert_eq!(VersionedBundle::default().effective_cu_price(), 0);
+
+        let payer = Keypair::new();
+        let bundle = VersionedBundle {
+            transactions: vec![priced_transfer(&payer, 0, 1_000)],
+        };
+        assert_eq!(bundle.effective_cu_price(), 0);
+    }
+
+    fn vote_transaction(payer: &Keypair) -> VersionedTransaction {
+        VersionedTransaction::from(Transaction::new_signed_with_payer(
+            &[Instruction::new_with_bytes(
+                vote::program::id(),
+                &[],
+                vec![],
+            )],
+            Some(&payer.pubkey()),
+            &[payer],
+            Hash::default(),
+        ))
+    }
+
+    #[test]
+    fn test_non_vote_transaction_indices() {
+        let payer = Keypair::new();
+        let bundle = VersionedBundle {
+            transactions: vec![
+                vote_transaction(&payer),
+                priced_transfer(&payer, 10_000, 1),
+                vote_transaction(&payer),
+                priced_transfer(&payer, 10_000, 2),
+            ],
+        };
+        let original = bundle.clone();
+
+        assert!(bundle.contains_vote_transaction());
+        assert_eq!(bundle.non_vote_transaction_indices(), vec![1, 3]);
+        assert_eq!(bundle, original);
+    }
+
+    #[test]
+    fn test_non_vote_transaction_indices_without_votes() {
+        let payer = Keypair::new();
+        let bundle = VersionedBundle {
+            transactions: vec![
+                priced_transfer(&payer, 10_000, 1),
+                priced_transfer(&payer, 10_000, 2),
+            ],
+        };
+        assert!(!bundle.contains_vote_transaction());
+        assert_eq!(bundle.non_vote_transaction_indices(), vec![0, 1]);
+    }
+
+    #[test]
+    fn test_diff_identical_bundles() {
+        let payer = Keypair::new();
+        let bundle = VersionedBundle {
+            transactions: vec![
+                priced_transfer(&payer, 10_000, 1),
+                priced_transfer(&payer, 10_000, 2),
+            ],
+        };
+        assert!(bundle.diff(&bundle.clone()).is_empty());
+    }
+
+    #[test]
+    fn test_diff_added_and_removed_transaction() {
+        let payer = Keypair::new();
+        let bundle = VersionedBundle {
+            transactions: vec![priced_transfer(&payer, 10_000, 1)],
+        };
+        let mut longer_bundle = bundle.clone();
+        longer_bundle
+            .transactions
+            .push(priced_transfer(&payer, 10_000, 2));
+
+        assert_eq!(
+            bundle.diff(&longer_bundle).differences,
+            vec![TransactionDiff::Added { index: 1 }]
+        );
+        assert_eq!(
+            longer_bundle.diff(&bundle).differences,
+            vec![TransactionDiff::Removed { index: 1 }]
+        );
+    }
+
+    #[test]
+    fn test_diff_changed_transaction() {
+        let payer = Keypair::new();
+        let bundle = VersionedBundle {
+            transactions: vec![
+                priced_transfer(&payer, 10_000, 1),
+                priced_transfer(&payer, 10_000, 2),
+            ],
+        };
+
+        let mut changed_bundle = bundle.clone();
+        changed_bundle.transactions[1] = priced_transfer(&payer, 10_000, 3);
+        assert_eq!(
+            bundle.diff(&changed_bundle).differences,
+            vec![TransactionDiff::Changed {
+                index: 1,
+                signatures_differ: true,
+                messages_differ: true,
+            }]
+        );
+
+        // same message with a different signature
+        let mut resigned_bundle = bundle.clone();
+        resigned_bundle.transactions[0].signatures[0] = Signature::default();
+        assert_eq!(
+            bundle.diff(&resigned_bundle).differences,
+            vec![TransactionDiff::Changed {
+                index: 0,
+                signatures_differ: true,
+                messages_differ: false,
+            }]
+        );
+    }
+
+    fn transaction_with_instructions(
+        payer: &Keypair,
+        num_instructions: usize,
+        data_len: usize,
+        v0: bool,
+    ) -> VersionedTransaction {
+        let program_id = Pubkey::new_unique();
+        let instructions: Vec<Instruction> = (0..num_instructions)
+            .map(|_| Instruction::new_with_bytes(program_id, &vec![0; data_len], vec![]))
+            .collect();
+        let message = if v0 {
+            VersionedMessage::V0(
+                v0::Message::try_compile(&payer.pubkey(), &instructions, &[], Hash::default())
+                    .unwrap(),
+            )
+        } else {
+            VersionedMessage::Legacy(Message::new(&instructions, Some(&payer.pubkey())))
+        };
+        VersionedTransaction::try_new(message, &[payer]).unwrap()
+    }
+
+    #[test]
+    fn test_validate_instruction_limits_compliant() {
+        let payer = Keypair::new();
+        let bundle = VersionedBundle {
+            transactions: vec![
+                priced_transfer(&payer, 10_000, 1),
+                transaction_with_instructions(&payer, MAX_INSTRUCTIONS_PER_TRANSACTION, 1, false),
+                transaction_with_instructions(&payer, MAX_INSTRUCTIONS_PER_TRANSACTION, 1, true),
+            ],
+        };
+        assert_eq!(bundle.validate_instruction_limits(), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_instruction_limits_too_many_instructions() {
+        let payer = Keypair::new();
+        for v0 in [false, true] {
+            let bundle = VersionedBundle {
+                transactions: vec![
+                    priced_transfer(&payer, 10_000, 1),
+                    transaction_with_instructions(
+                        &payer,
+                        MAX_INSTRUCTIONS_PER_TRANSACTION + 1,
+                        0,
+                        v0,
+                    ),
+                ],
+            };
+            assert_eq!(
+                bundle.validate_instruction_limits(),
+                Err(BundleError::TooManyInstructions {
+                    index: 1,
+                    num_instructions: MAX_INSTRUCTIONS_PER_TRANSACTION + 1,
+                    max: MAX_INSTRUCTIONS_PER_TRANSACTION,
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_instruction_limits_data_too_large() {
+        let payer = Keypair::new();
+        let bundle = VersionedBundle {
+            transactions: vec![transaction_with_instructions(
+                &payer,
+                2,
+                MAX_INSTRUCTION_DATA_PER_TRANSACTION,
+                true,
+            )],
+        };
+        assert_eq!(
+            bundle.validate_instruction_limits(),
+            Err(BundleError::InstructionDataTooLarge {
+                index: 0,
+                len: 2 * MAX_INSTRUCTION_DATA_PER_TRANSACTION,
+                max: MAX_INSTRUCTION_DATA_PER_TRANSACTION,
+            })
+        );
+    }
+
+    #[test]
+    fn test_invokes_disabled_programs() {
+        let payer = Keypair::new();
+        let disabled_program = Pubkey::new_unique();
+        let disabled = HashSet::from([disabled_program]);
+
+        let clean_bundle = VersionedBundle {
+            transactions: vec![priced_transfer(&payer, 10_000, 1), vote_transaction(&payer)],
+        };
+        assert!(clean_bundle.invokes_disabled_programs(&disabled).is_empty());
+
+        let mut bundle = clean_bundle;
+        bundle.transactions.push(VersionedTransaction::from(
+            Transaction::new_signed_with_payer(
+                &[
+                    system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1),
+                    Instruction::new_with_bytes(disabled_program, &[], vec![]),
+                ],
+                Some(&payer.pubkey()),
+                &[&payer],
+                Hash::default(),
+            ),
+        ));
+        assert_eq!(bundle.invokes_disabled_programs(&disabled), vec![2]);
+        assert!(bundle.invokes_disabled_programs(&HashSet::new()).is_empty());
+    }
+
+    #[test]
+    fn test_validate_tip_position() {
+        let payer = Keypair::new();
+        let tip_account = Pubkey::new_unique();
+        let tip_accounts = HashSet::from([tip_account]);
+        let tip_transaction = VersionedTransaction::from(Transaction::new_signed_with_payer(
+            &[system_instruction::transfer(
+                &payer.pubkey(),
+                &tip_account,
+                1,
+            )],
+            Some(&payer.pubkey()),
+            &[&payer],
+            Hash::default(),
+        ));
+
+        let bundle = VersionedBundle {
+            transactions: vec![priced_transfer(&payer, 10_000, 1), tip_transaction.clone()],
+        };
+        assert_eq!(bundle.tip_transaction_index(&tip_accounts), Some(1));
+        assert_eq!(
+            bundle.validate_tip_position(&tip_accounts, TipPosition::Last),
+            Ok(())
+        );
+        assert_eq!(
+            bundle.validate_tip_position(&tip_accounts, TipPosition::Index(1)),
+            Ok(())
+        );
+        assert_eq!(
+            bundle.validate_tip_position(&tip_accounts, TipPosition::First),
+            Err(BundleError::TipNotInExpectedPosition {
+                expected: TipPosition::First,
+                found: Some(1),
+            })
+        );
+
+        let bundle = VersionedBundle {
+            transactions: vec![tip_transaction, priced_transfer(&payer, 10_000, 1)],
+        };
+        assert_eq!(
+            bundle.validate_tip_position(&tip_accounts, TipPosition::First),
+            Ok(())
+        );
+        assert!(bundle
+            .validate_tip_position(&tip_accounts, TipPosition::Last)
+            .is_err());
+    }
+
+    #[test]
+    fn test_validate_tip_position_without_tip() {
+        let payer = Keypair::new();
+        let tip_accounts = HashSet::from([Pubkey::new_unique()]);
+        let bundle = VersionedBundle {
+            transactions: vec![priced_transfer(&payer, 10_000, 1)],
+        };
+        assert_eq!(bundle.tip_transaction_index(&tip_accounts), None);
+        assert_eq!(
+            bundle.validate_tip_position(&tip_accounts, TipPosition::Last),
+            Err(BundleError::TipNotInExpectedPosition {
+                expected: TipPosition::Last,
+                found: None,
+            })
+        );
+    }
+
+    fn transfer_with_blockhash(payer: &Keypair, blockhash: Hash) -> VersionedTransaction {
+        VersionedTransaction::from(system_transaction::transfer(
+            payer,
+            &Pubkey::new_unique(),
+            1,
+            blockhash,
+        ))
+    }
+
+    #[test]
+    fn test_validity_window_fresh_blockhash() {
+        let payer = Keypair::new();
+        let blockhash = Hash::new_unique();
+        let recent_blockhash_slots = HashMap::from([(blockhash, 100)]);
+
+        let bundle = VersionedBundle {
+            transactions: vec![
+                transfer_with_blockhash(&payer, blockhash),
+                transfer_with_blockhash(&payer, blockhash),
+            ],
+        };
+        assert_eq!(
+            bundle.validity_window(&recent_blockhash_slots),
+            Some((100, 100 + MAX_PROCESSING_AGE as Slot))
+        );
+
+        // unknown blockhashes have no window
+        let bundle = VersionedBundle {
+            transactions: vec![transfer_with_blockhash(&payer, Hash::new_unique())],
+        };
+        assert_eq!(bundle.validity_window(&recent_blockhash_slots), None);
+    }
+
+    #[test]
+    fn test_validity_window_about_to_expire() {
+        let payer = Keypair::new();
+        let old_blockhash = Hash::new_unique();
+        let new_blockhash = Hash::new_unique();
+        let recent_blockhash_slots = HashMap::from([
+            (old_blockhash, 10),
+            (new_blockhash, 10 + MAX_PROCESSING_AGE as Slot),
+        ]);
+
+        // the window is narrowed down to the single slot both blockhashes are valid in
+        let bundle = VersionedBundle {
+            transactions: vec![
+                transfer_with_blockhash(&payer, old_blockhash),
+                transfer_with_blockhash(&payer, new_blockhash),
+            ],
+        };
+        let last_valid_slot = 10 + MAX_PROCESSING_AGE as Slot;
+        assert_eq!(
+            bundle.validity_window(&recent_blockhash_slots),
+            Some((last_valid_slot, last_valid_slot))
+        );
+
+        // no overlap once the old blockhash expires first
+        let recent_blockhash_slots = HashMap::from([
+            (old_blockhash, 10),
+            (new_blockhash, 11 + MAX_PROCESSING_AGE as Slot),
+        ]);
+        assert_eq!(bundle.validity_window(&recent_blockhash_slots), None);
+    }
+
+    #[test]
+    fn test_validity_window_durable_nonce() {
+        let payer = Keypair::new();
+        let nonce_account = Pubkey::new_unique();
+        let durable_nonce = Hash::new_unique();
+        let nonced_transfer = VersionedTransaction::from(Transaction::new_signed_with_payer(
+            &[
+                system_instruction::advance_nonce_account(&nonce_account, &payer.pubkey()),
+                system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer],
+            durable_nonce,
+        ));
+        assert!(nonced_transfer.uses_durable_nonce());
+
+        let bundle = VersionedBundle {
+            transactions: vec![nonced_transfer.clone()],
+        };
+        assert_eq!(bundle.validity_window(&HashMap::new()), None);
+
+        // durable nonce transactions don't narrow the window of the rest of the bundle
+        let blockhash = Hash::new_unique();
+        let bundle = VersionedBundle {
+            transactions: vec![nonced_transfer, transfer_with_blockhash(&payer, blockhash)],
+        };
+        assert_eq!(
+            bundle.validity_window(&HashMap::from([(blockhash, 5)])),
+            Some((5, 5 + MAX_PROCESSING_AGE as Slot))
+        );
+    }
+
+    #[test]
+    fn test_tip_to_unrecognized_account() {
+        let searcher = Keypair::new();
+        let tip_account = Pubkey::new_unique();
+        let tip_accounts = HashSet::from([tip_account]);
+        let searcher_tx = priced_transfer(&searcher, 10_000, 1);
+        let tip = |destination: &Pubkey| {
+            VersionedTransaction::from(system_transaction::transfer(
+                &searcher,
+                destination,
+                1_000,
+                Hash::default(),
+            ))
+        };
+
+        let bundle = VersionedBundle {
+            transactions: vec![searcher_tx.clone(), tip(&tip_account)],
+        };
+        assert!(!bundle.tip_to_unrecognized_account(&tip_accounts));
+
+        let bundle = VersionedBundle {
+            transactions: vec![searcher_tx.clone(), tip(&Pubkey::new_unique())],
+        };
+        assert!(bundle.tip_to_unrecognized_account(&tip_accounts));
+
+        // a lone transfer isn't necessarily a tip
+        let bundle = VersionedBundle {
+            transactions: vec![tip(&Pubkey::new_unique())],
+        };
+        assert!(!bundle.tip_to_unrecognized_account(&tip_accounts));
+
+        // neither is a transaction doing more than transferring
+        let bundle = VersionedBundle {
+            transactions: vec![
+                searcher_tx,
+                VersionedTransaction::from(Transaction::new_signed_with_payer(
+                    &[
+                        system_instruction::transfer(&searcher.pubkey(), &Pubkey::new_unique(), 1),
+                        system_instruction::transfer(&searcher.pubkey(), &Pubkey::new_unique(), 1),
+                    ],
+                    Some(&searcher.pubkey()),
+                    &[&searcher],
+                    Hash::default(),
+                )),
+            ],
+        };
+        assert!(!bundle.tip_to_unrecognized_account(&tip_accounts));
+    }
+
+    #[test]
+    fn test_summary() {
+        let transaction = |signature_byte: u8| VersionedTransaction {
+            signatures: vec![Signature::new(&[signature_byte; 64])],
+            message: VersionedMessage::default(),
+        };
+        let bundle = VersionedBundle {
+            transactions: vec![transaction(1), transaction(2)],
+        };
+        assert_eq!(
+            bundle.summary(),
+            "bundle[2 txs, sigs=2AXDGYSE..,3L3RY5sT.., ~0.2KB]"
+        );
+        assert_eq!(
+            VersionedBundle::default().summary(),
+            "bundle[0 txs, sigs=, ~0.0KB]"
+        );
+    }
+
+    #[test]
+    fn test_account_counts() {
+        let payer = Keypair::new();
+        let recipient = Pubkey::new_unique();
+        let transfer = |recipient: &Pubkey| {
+            VersionedTransaction::from(system_transaction::transfer(
+                &payer,
+                recipient,
+                1,
+                Hash::default(),
+            ))
+        };
+
+        // payer, recipient and the system program, shared by both transactions
+        let bundle = VersionedBundle {
+            transactions: vec![transfer(&recipient), transfer(&recipient)],
+        };
+        assert_eq!(bundle.max_accounts_per_transaction(), 3);
+        assert_eq!(bundle.total_unique_accounts(), 3);
+
+        // accounts repeated within a transaction are only counted once
+        let accounts: Vec<AccountMeta> = (0..30)
+            .map(|_| AccountMeta::new_readonly(Pubkey::new_unique(), false))
+            .collect();
+        let program_id = Pubkey::new_unique();
+        let account_heavy = VersionedTransaction::from(Transaction::new_signed_with_payer(
+            &[
+                Instruction::new_with_bytes(program_id, &[], accounts.clone()),
+                Instruction::new_with_bytes(program_id, &[], accounts),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer],
+            Hash::default(),
+        ));
+        let bundle = VersionedBundle {
+            transactions: vec![transfer(&recipient), account_heavy],
+        };
+        assert_eq!(bundle.max_accounts_per_transaction(), 32);
+        assert_eq!(bundle.total_unique_accounts(), 34);
+
+        // lookup table accounts are counted by table and index
+        let lookup_table = Pubkey::new_unique();
+        let v0_tx = VersionedTransaction {
+            signatures: vec![Signature::default()],
+            message: VersionedMessage::V0(v0::Message {
+                account_keys: vec![payer.pubkey()],
+                address_table_lookups: vec![v0::MessageAddressTableLookup {
+                    account_key: lookup_table,
+                    writable_indexes: vec![0, 1],
+                    readonly_indexes: vec![2],
+                }],
+                ..v0::Message::default()
+            }),
+        };
+        let bundle = VersionedBundle {
+            transactions: vec![transfer(&recipient), v0_tx],
+        };
+        assert_eq!(bundle.max_accounts_per_transaction(), 4);
+        assert_eq!(bundle.total_unique_accounts(), 6);
+
+        assert_eq!(VersionedBundle::default().max_accounts_per_transaction(), 0);
+    }
+
+    #[test]
+    fn test_missing_signatures() {
+        let payer = Keypair::new();
+        let signer = Keypair::new();
+        let instruction = Instruction::new_with_bytes(
+            Pubkey::new_unique(),
+            &[],
+            vec![AccountMeta::new_readonly(signer.pubkey(), true)],
+        );
+        let message = Message::new(&[instruction], Some(&payer.pubkey()));
+
+        let fully_signed = VersionedTransaction::from(Transaction::new(
+            &[&payer, &signer],
+            message.clone(),
+            Hash::default(),
+        ));
+        let bundle = VersionedBundle {
+            transactions: vec![fully_signed.clone(), fully_signed.clone()],
+        };
+        assert!(bundle.is_fully_signed());
+        assert!(bundle.missing_signatures().is_empty());
+
+        // only the payer signed, the other signature is a zeroed placeholder
+        let mut partially_signed = Transaction::new_unsigned(message);
+        partially_signed.partial_sign(&[&payer], Hash::default());
+        let partially_signed = VersionedTransaction::from(partially_signed);
+        assert_ne!(partially_signed.signatures[0], Signature::default());
+
+        // a transaction with fewer signatures than required
+        let mut truncated = fully_signed.clone();
+        truncated.signatures.truncate(1);
+
+        let bundle = VersionedBundle {
+            transactions: vec![fully_signed, partially_signed, truncated],
+        };
+        assert!(!bundle.is_fully_signed());
+        assert_eq!(bundle.missing_signatures(), vec![(1, 1), (2, 1)]);
+    }
+
+    #[test]
+    fn test_canonical_id() {
+        let transaction = |signature: Signature| VersionedTransaction {
+            signatures: vec![signature],
+            message: VersionedMessage::Legacy(Message::default()),
+        };
+        let first = transaction(Signature::new(&[1; 64]));
+        let second = transaction(Signature::new(&[2; 64]));
+
+        let bundle = VersionedBundle {
+            transactions: vec![first.clone(), second.clone()],
+        };
+        // pinned so that independent implementations can check against it
+        assert_eq!(
+            Hash::new_from_array(bundle.canonical_id()).to_string(),
+            "7ZKb4P3X35BM4aTxqQGB8kTmrVkTvoG3TkHLyyxa4jJe"
+        );
+
+        // only the signatures matter
+        let mut reencoded = bundle.clone();
+        reencoded.transactions[0].message = VersionedMessage::V0(v0::Message::default());
+        assert_eq!(reencoded.canonical_id(), bundle.canonical_id());
+
+        // but their order does
+        let reordered = VersionedBundle {
+            transactions: vec![second, first],
+        };
+        assert_ne!(reordered.canonical_id(), bundle.canonical_id());
+    }
+
+    #[test]
+    fn test_oversized_transaction_indices() {
+        let payer = Keypair::new();
+        let transfer = VersionedTransaction::from(system_transaction::transfer(
+            &payer,
+            &Pubkey::new_unique(),
+            1,
+            Hash::default(),
+        ));
+        let oversized = VersionedTransaction::from(Transaction::new_signed_with_payer(
+            &[Instruction::new_with_bytes(
+                Pubkey::new_unique(),
+                &[0; PACKET_DATA_SIZE],
+                vec![],
+            )],
+            Some(&payer.pubkey()),
+            &[&payer],
+            Hash::default(),
+        ));
+
+        let bundle = VersionedBundle {
+            transactions: vec![transfer.clone(), oversized, transfer],
+        };
+        assert_eq!(bundle.oversized_transaction_indices(), vec![1]);
+        assert!(VersionedBundle::default()
+            .oversized_transaction_indices()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_uuid() {
+        let transaction = |signature_byte: u8| VersionedTransaction {
+            signatures: vec![Signature::new(&[signature_byte; 64])],
+            message: VersionedMessage::default(),
+        };
+
+        // the SHA-256 of no signatures at all
+        assert_eq!(
+            VersionedBundle::default().uuid(),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+
+        let bundle = VersionedBundle {
+            transactions: vec![transaction(1)],
+        };
+        assert_eq!(
+            bundle.uuid(),
+            "7c8975e1e60a5c8337f28edf8c33c3b180360b7279644a9bc1af3c51e6220bf5"
+        );
+        let round_tripped: VersionedBundle =
+            bincode::deserialize(&bincode::serialize(&bundle).unwrap()).unwrap();
+        assert_eq!(round_tripped.uuid(), bundle.uuid());
+
+        let bundle = VersionedBundle {
+            transactions: vec![transaction(1), transaction(2)],
+        };
+        let reordered = VersionedBundle {
+            transactions: vec![transaction(2), transaction(1)],
+        };
+        assert_ne!(reordered.uuid(), bundle.uuid());
+    }
+
+    #[test]
+    fn test_validate() {
+        let transfer = VersionedTransaction::from(system_transaction::transfer(
+            &Keypair::new(),
+            &Pubkey::new_unique(),
+            1,
+            Hash::default(),
+        ));
+        let tx_size = bincode::serialized_size(&transfer).unwrap() as usize;
+        let bundle = VersionedBundle {
+            transactions: vec![transfer; 3],
+        };
+
+        assert_eq!(
+            VersionedBundle::default().validate(5, usize::MAX),
+            Err(BundleError::Empty)
+        );
+
+        assert_eq!(bundle.validate(3, usize::MAX), Ok(()));
+        assert_eq!(
+            bundle.validate(2, usize::MAX),
+            Err(BundleError::TooManyTransactions { len: 3, max: 2 })
+        );
+
+        assert_eq!(bundle.validate(3, tx_size * 3), Ok(()));
+        assert_eq!(
+            bundle.validate(3, tx_size * 3 - 1),
+            Err(BundleError::TooLarge {
+                bytes: tx_size * 3,
+                max: tx_size * 3 - 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let payer = Keypair::new();
+        for num_transactions in [1, 5] {
+            let bundle = VersionedBundle {
+                transactions: (0..num_transactions)
+                    .map(|lamports| {
+                        VersionedTransaction::from(system_transaction::transfer(
+                            &payer,
+                            &Pubkey::new_unique(),
+                            lamports,
+                            Hash::default(),
+                        ))
+                    })
+                    .collect(),
+            };
+            let json = bundle.to_json().unwrap();
+            assert_eq!(VersionedBundle::from_json(&json).unwrap(), bundle);
+        }
+    }
+
+    #[test]
+    fn test_to_json_unserializable_transaction() {
+        // more signatures than the u16 length prefix can encode
+        let bundle = VersionedBundle {
+            transactions: vec![VersionedTransaction {
+                signatures: vec![Signature::default(); u16::MAX as usize + 1],
+                message: VersionedMessage::default(),
+            }],
+        };
+        assert!(matches!(bundle.to_json(), Err(BundleError::Serialize(_))));
+    }
+
+    #[test]
+    fn test_from_json_malformed() {
+        let transfer = VersionedTransaction::from(system_transaction::transfer(
+            &Keypair::new(),
+            &Pubkey::new_unique(),
+            1,
+            Hash::default(),
+        ));
+        let serialized = bincode::serialize(&transfer).unwrap();
+
+        let is_deserialize_error = |json: &str| {
+            matches!(
+                VersionedBundle::from_json(json),
+                Err(BundleError::Deserialize(_))
+            )
+        };
+        assert!(is_deserialize_error("not json"));
+        assert!(is_deserialize_error(r#"["not base64!"]"#));
+        let truncated = base64::encode(&serialized[..serialized.len() / 2]);
+        assert!(is_deserialize_error(&format!(r#"["{}"]"#, truncated)));
+    }
+
+    #[test]
+    fn test_signatures() {
+        let transaction = |signatures: Vec<Signature>| VersionedTransaction {
+            signatures,
+            message: VersionedMessage::default(),
+        };
+        let (a, b, c) = (
+            Signature::new_unique(),
+            Signature::new_unique(),
+            Signature::new_unique(),
+        );
+
+        let bundle = VersionedBundle {
+            transactions: vec![transaction(vec![a, b]), transaction(vec![c])],
+        };
+        assert_eq!(bundle.signatures(), vec![a, c]);
+        assert_eq!(bundle.try_signatures(), Ok(vec![a, c]));
+        assert_eq!(bundle.all_signatures(), vec![a, b, c]);
+
+        let bundle = VersionedBundle {
+            transactions: vec![transaction(vec![a]), transaction(vec![])],
+        };
+        assert_eq!(bundle.signatures(), vec![a, Signature::default()]);
+        assert_eq!(
+            bundle.try_signatures(),
+            Err(BundleError::MissingSignature { index: 1 })
+        );
+        assert_eq!(bundle.all_signatures(), vec![a]);
+    }
+
+    #[test]
+    fn test_requested_compute_units() {
+        let payer = Keypair::new();
+        assert_eq!(VersionedBundle::default().requested_compute_units(), 0);
+
+        // explicit limits
+        let bundle = VersionedBundle {
+            transactions: vec![
+                priced_transfer(&payer, 10_000, 1),
+                priced_transfer(&payer, 20_000, 1),
+            ],
+        };
+        assert_eq!(bundle.requested_compute_units(), 30_000);
+
+        // defaults, one transfer instruction
+        let transfer = VersionedTransaction::from(system_transaction::transfer(
+            &payer,
+            &Pubkey::new_unique(),
+            1,
+            Hash::default(),
+        ));
+        let bundle = VersionedBundle {
+            transactions: vec![transfer.clone()],
+        };
+        assert_eq!(
+            bundle.requested_compute_units(),
+            DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT as u64
+        );
+
+        // conflicting limits, the last one wins
+        let conflicting = VersionedTransaction::from(Transaction::new_signed_with_payer(
+            &[
+                ComputeBudgetInstruction::set_compute_unit_limit(50_000),
+                system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1),
+                ComputeBudgetInstruction::set_compute_unit_limit(5_000),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer],
+            Hash::default(),
+        ));
+        let bundle = VersionedBundle {
+            transactions: vec![priced_transfer(&payer, 10_000, 1), transfer, conflicting],
+        };
+        assert_eq!(
+            bundle.requested_compute_units(),
+            10_000 + DEFAULT_INSTRUCTION_COMPUTE_UNIT_LIMIT as u64 + 5_000
+        );
+    }
+
+    fn nonced_transfer(payer: &Keypair, nonce_account: &Pubkey) -> VersionedTransaction {
+        VersionedTransaction::from(Transaction::new_signed_with_payer(
+            &[
+                system_instruction::advance_nonce_account(nonce_account, &payer.pubkey()),
+                system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1),
+            ],
+            Some(&payer.pubkey()),
+            &[payer],
+            Hash::default(),
+        ))
+    }
+
+    #[test]
+    fn test_duplicate_nonce_accounts() {
+        let payer = Keypair::new();
+        let nonce_account = Pubkey::new_unique();
+        let other_nonce_account = Pubkey::new_unique();
+
+        assert!(VersionedBundle::default()
+            .duplicate_nonce_accounts()
+            .is_empty());
+
+        let bundle = VersionedBundle {
+            transactions: vec![
+                nonced_transfer(&payer, &nonce_account),
+                nonced_transfer(&payer, &other_nonce_account),
+                priced_transfer(&payer, 10_000, 1),
+            ],
+        };
+        assert!(bundle.duplicate_nonce_accounts().is_empty());
+
+        let bundle = VersionedBundle {
+            transactions: vec![
+                nonced_transfer(&payer, &nonce_account),
+                nonced_transfer(&payer, &other_nonce_account),
+                nonced_transfer(&payer, &nonce_account),
+                nonced_transfer(&payer, &nonce_account),
+            ],
+        };
+        assert_eq!(bundle.duplicate_nonce_accounts(), vec![nonce_account]);
+    }
+
+    #[test]
+    fn test_tip_lamports() {
+        let payer = Keypair::new();
+        let tip_accounts: HashSet<Pubkey> = (0..2).map(|_| Pubkey::new_unique()).collect();
+        let mut tip_accounts_iter = tip_accounts.iter();
+        let (tip_account, other_tip_account) = (
+            tip_accounts_iter.next().unwrap(),
+            tip_accounts_iter.next().unwrap(),
+        );
+        let transfer = |instructions: &[Instruction]| {
+            VersionedTransaction::from(Transaction::new_signed_with_payer(
+                instructions,
+                Some(&payer.pubkey()),
+                &[&payer],
+                Hash::default(),
+            ))
+        };
+
+        assert_eq!(VersionedBundle::default().tip_lamports(&tip_accounts), 0);
+
+        let bundle = VersionedBundle {
+            transactions: vec![
+                priced_transfer(&payer, 10_000, 1),
+                transfer(&[system_instruction::transfer(
+                    &payer.pubkey(),
+                    tip_account,
+                    1_000,
+                )]),
+            ],
+        };
+        assert_eq!(bundle.tip_lamports(&tip_accounts), 1_000);
+
+        // tips in several instructions and transactions add up, other transfers don't count
+        let bundle = VersionedBundle {
+            transactions: vec![
+                transfer(&[
+                    system_instruction::transfer(&payer.pubkey(), tip_account, 1_000),
+                    system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 5_000),
+                    system_instruction::transfer(&payer.pubkey(), other_tip_account, 2_000),
+                ]),
+                transfer(&[system_instruction::transfer(
+                    &payer.pubkey(),
+                    tip_account,
+                    3_000,
+                )]),
+            ],
+        };
+        assert_eq!(bundle.tip_lamports(&tip_accounts), 6_000);
+    }
+
+    #[test]
+    fn test_content_hash() {
+        let payer = Keypair::new();
+        let first = priced_transfer(&payer, 10_000, 1);
+        let second = priced_transfer(&payer, 10_000, 2);
+
+        let bundle = VersionedBundle {
+            transactions: vec![first.clone(), second.clone()],
+        };
+        let content_hash = bundle.content_hash().unwrap();
+        assert_eq!(bundle.clone().content_hash().unwrap(), content_hash);
+
+        let reordered = VersionedBundle {
+            transactions: vec![second, first.clone()],
+        };
+        assert_ne!(reordered.content_hash().unwrap(), content_hash);
+
+        // a change that keeps the signatures, and so the canonical id, still changes the hash
+        let mut tampered = bundle.clone();
+        tampered.transactions[0].message = VersionedMessage::Legacy(Message::default());
+        assert_eq!(tampered.canonical_id(), bundle.canonical_id());
+        assert_ne!(tampered.content_hash().unwrap(), content_hash);
+
+        // rather than hashing it as empty, a transaction that can't be serialized is an error
+        tampered.transactions[0].signatures = vec![Signature::default(); u16::MAX as usize + 1];
+        assert!(matches!(
+            tampered.content_hash(),
+            Err(BundleError::Serialize(_))
+        ));
+    }
+
+    #[test]
+    fn test_check_fee_payer_solvency() {
+        let payer = Keypair::new();
+        let other_payer = Keypair::new();
+        let tip_account = Pubkey::new_unique();
+        // 5_000 lamports of signature fee, 1_000 of prioritization fee and a 1 lamport transfer
+        let priced_transfer_cost = LAMPORTS_PER_SIGNATURE + 1_000 + 1;
+        let tip = VersionedTransaction::from(system_transaction::transfer(
+            &other_payer,
+            &tip_account,
+            10_000,
+            Hash::default(),
+        ));
+        let bundle = VersionedBundle {
+            transactions: vec![
+                priced_transfer(&payer, 100_000, 10_000),
+                priced_transfer(&payer, 100_000, 10_000),
+                tip,
+            ],
+        };
+        let balances = |payer_balance: u64, other_payer_balance: u64| {
+            let payer = payer.pubkey();
+            move |pubkey: &Pubkey| {
+                if *pubkey == payer {
+                    payer_balance
+                } else {
+                    other_payer_balance
+                }
+            }
+        };
+
+        // a payer funding several transactions needs to cover all of them
+        let payer_required = 2 * priced_transfer_cost;
+        let other_payer_required = LAMPORTS_PER_SIGNATURE + 10_000;
+        assert_eq!(
+            bundle.check_fee_payer_solvency(balances(payer_required, other_payer_required)),
+            Ok(())
+        );
+        assert_eq!(
+            bundle.check_fee_payer_solvency(balances(priced_transfer_cost, other_payer_required)),
+            Err(BundleError::FeePayerInsolvent {
+                fee_payer: payer.pubkey(),
+                required: payer_required,
+                balance: priced_transfer_cost,
+            })
+        );
+        assert_eq!(
+            bundle.check_fee_payer_solvency(balances(payer_required, 10_000)),
+            Err(BundleError::FeePayerInsolvent {
+                fee_payer: other_payer.pubkey(),
+                required: other_payer_required,
+                balance: 10_000,
+            })
+        );
+    }
+
+    #[test]
+    fn test_validate_lookup_tables() {
+        let payer = Keypair::new();
+        let (active_table, deactivated_table) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let v0_tx = |lookup_tables: &[Pubkey]| VersionedTransaction {
+            signatures: vec![Signature::default()],
+            message: VersionedMessage::V0(v0::Message {
+                account_keys: vec![payer.pubkey()],
+                address_table_lookups: lookup_tables
+                    .iter()
+                    .map(|account_key| v0::MessageAddressTableLookup {
+                        account_key: *account_key,
+                        writable_indexes: vec![0],
+                        readonly_indexes: vec![],
+                    })
+                    .collect(),
+                ..v0::Message::default()
+            }),
+        };
+        let status = |table: &Pubkey| {
+            if *table == active_table {
+                LookupTableStatus::Active
+            } else if *table == deactivated_table {
+                LookupTableStatus::Deactivated
+            } else {
+                LookupTableStatus::NotFound
+            }
+        };
+        let legacy_tx = VersionedTransaction::from(system_transaction::transfer(
+            &payer,
+            &Pubkey::new_unique(),
+            1,
+            Hash::default(),
+        ));
+
+        let bundle = VersionedBundle {
+            transactions: vec![legacy_tx.clone(), v0_tx(&[active_table])],
+        };
+        assert_eq!(bundle.validate_lookup_tables(status), Ok(()));
+
+        let bundle = VersionedBundle {
+            transactions: vec![
+                legacy_tx,
+                v0_tx(&[active_table]),
+                v0_tx(&[active_table, deactivated_table]),
+            ],
+        };
+        assert_eq!(
+            bundle.validate_lookup_tables(status),
+            Err(BundleError::UnusableLookupTable {
+                index: 2,
+                table: deactivated_table,
+                status: LookupTableStatus::Deactivated,
+            })
+        );
+
+        let missing_table = Pubkey::new_unique();
+        let bundle = VersionedBundle {
+            transactions: vec![v0_tx(&[missing_table])],
+        };
+        assert_eq!(
+            bundle.validate_lookup_tables(status),
+            Err(BundleError::UnusableLookupTable {
+                index: 0,
+                table: missing_table,
+                status: LookupTableStatus::NotFound,
+            })
+        );
+    }
+
+    #[test]
+    fn test_validate_for_submission() {
+        let payer = Keypair::new();
+        let tip_account = Pubkey::new_unique();
+        let blockhash = Hash::new_unique();
+        let ctx = SubmissionContext {
+            max_len: 3,
+            max_serialized_bytes: 512,
+            tip_accounts: HashSet::from([tip_account]),
+            recent_blockhashes: HashSet::from([blockhash]),
+        };
+        let transfer = |to: &Pubkey, blockhash: Hash| {
+            VersionedTransaction::from(system_transaction::transfer(&payer, to, 1, blockhash))
+        };
+
+        let bundle = VersionedBundle {
+            transactions: vec![
+                transfer(&Pubkey::new_unique(), blockhash),
+                transfer(&tip_account, blockhash),
+            ],
+        };
+        assert_eq!(bundle.validate_for_submission(&ctx), Ok(()));
+        assert_eq!(
+            VersionedBundle::default().validate_for_submission(&ctx),
+            Err(vec![BundleError::Empty])
+        );
+
+        // too many transactions and too large, one of them repeated and one unsigned, a
+        // different blockhash and no tip
+        let recipient = Pubkey::new_unique();
+        let mut unsigned = transfer(&Pubkey::new_unique(), blockhash);
+        unsigned.signatures[0] = Signature::default();
+        let bundle = VersionedBundle {
+            transactions: vec![
+                transfer(&recipient, blockhash),
+                transfer(&recipient, blockhash),
+                unsigned,
+                transfer(&Pubkey::new_unique(), Hash::new_unique()),
+            ],
+        };
+        let bytes = bundle
+            .transactions
+            .iter()
+            .map(|tx| bincode::serialized_size(tx).unwrap() as usize)
+            .sum();
+        assert_eq!(
+            bundle.validate_for_submission(&ctx),
+            Err(vec![
+                BundleError::TooManyTransactions { len: 4, max: 3 },
+                BundleError::TooLarge { bytes, max: 512 },
+                BundleError::MissingSignatures {
+                    index: 2,
+                    signature_indices: vec![0],
+                },
+                BundleError::NonUniformBlockhash {
+                    index: 3,
+                    first_index: 0,
+                },
+                BundleError::DuplicateTransaction {
+                    index: 1,
+                    first_index: 0,
+                },
+                BundleError::MissingTip,
+            ])
+        );
+
+        // the blockhash every transaction shares isn't recent
+        let stale_blockhash = Hash::new_unique();
+        let bundle = VersionedBundle {
+            transactions: vec![
+                transfer(&Pubkey::new_unique(), stale_blockhash),
+                transfer(&tip_account, stale_blockhash),
+            ],
+        };
+        assert_eq!(
+            bundle.validate_for_submission(&ctx),
+            Err(vec![BundleError::UnknownBlockhash {
+                index: 0,
+                blockhash: stale_blockhash,
+            }])
+        );
+    }
+}
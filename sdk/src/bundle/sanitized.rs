@@ -1,9 +1,813 @@
 #![cfg(feature = "full")]
 
-use {solana_sdk::transaction::SanitizedTransaction, uuid::Uuid};
+use {
+    crate::bundle::{compute_budget_limits_of_instructions, error::BundleError, VersionedBundle},
+    solana_sdk::{
+        clock::Slot,
+        feature_set::FeatureSet,
+        message::{
+            v0::{LoadedAddresses, MessageAddressTableLookup},
+            AddressLoader, AddressLoaderError, VersionedMessage,
+        },
+        packet::PacketFlags,
+        pubkey::Pubkey,
+        slot_hashes::SlotHashes,
+        transaction::{MessageHash, SanitizedTransaction},
+    },
+    std::{
+        collections::{HashMap, HashSet},
+        sync::Arc,
+    },
+    uuid::Uuid,
+};
 
 #[derive(Clone, Debug)]
 pub struct SanitizedBundle {
     pub transactions: Vec<SanitizedTransaction>,
     pub uuid: Uuid,
+    /// Flags of the packet each transaction was deserialized from, parallel to `transactions`.
+    /// None if the bundle wasn't built from packets.
+    pub packet_flags: Option<Vec<PacketFlags>>,
+}
+
+/// The accounts locked across all the transactions of a bundle, see
+/// [`SanitizedBundle::account_locks`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BundleAccountLocks {
+    pub writable: HashSet<Pubkey>,
+    pub readonly: HashSet<Pubkey>,
+}
+
+impl SanitizedBundle {
+    /// Returns the compute units requested by the transactions, with the runtime's defaults for
+    /// transactions that don't set a limit.
+    pub fn requested_compute_units(&self) -> u64 {
+        self.transactions
+            .iter()
+            .map(|tx| {
+                let (compute_unit_limit, _) = compute_budget_limits_of_instructions(
+                    tx.message()
+                        .program_instructions_iter()
+                        .map(|(program_id, ix)| (Some(program_id), ix.data.as_slice())),
+                );
+                compute_unit_limit
+            })
+            .fold(0, u64::saturating_add)
+    }
+
+    /// Returns the union of the accounts locked by the transactions. An account written by any
+    /// transaction is only listed as writable.
+    pub fn account_locks(&self) -> BundleAccountLocks {
+        let mut locks = BundleAccountLocks::default();
+        for tx in &self.transactions {
+            let tx_locks = tx.get_account_locks_unchecked();
+            locks
+                .writable
+                .extend(tx_locks.writable.into_iter().copied());
+            locks
+                .readonly
+                .extend(tx_locks.readonly.into_iter().copied());
+        }
+        locks.readonly.retain(|key| !locks.writable.contains(key));
+        locks
+    }
+
+    /// Returns true if an account is written by one transaction and only read by another, so the
+    /// transactions' own locks can't all be taken at once and the bundle must lock the account as
+    /// writable.
+    pub fn has_conflicting_locks(&self) -> bool {
+        let mut writable = HashSet::new();
+        let mut readonly = HashSet::new();
+        for tx in &self.transactions {
+            let tx_locks = tx.get_account_locks_unchecked();
+            writable.extend(tx_locks.writable);
+            readonly.extend(tx_locks.readonly);
+        }
+        !writable.is_disjoint(&readonly)
+    }
+
+    /// Returns the accounts written by a transaction and read by a later one, whose results depend
+    /// on the transactions executing in order.
+    pub fn read_after_write_accounts(&self) -> HashSet<Pubkey> {
+        let mut written = HashSet::new();
+        let mut read_after_write = HashSet::new();
+        for tx in &self.transactions {
+            let tx_locks = tx.get_account_locks_unchecked();
+            read_after_write.extend(
+                tx_locks
+                    .readonly
+                    .iter()
+                    .filter(|key| written.contains(**key))
+                    .map(|key| **key),
+            );
+            written.extend(tx_locks.writable.into_iter().copied());
+        }
+        read_after_write
+    }
+}
+
+impl VersionedBundle {
+    /// Sanitizes each transaction, resolving its address lookup tables with `address_loader` and
+    /// verifying its signatures and precompiles, failing with the index of the first transaction
+    /// that doesn't pass.
+    pub fn into_sanitized(
+        self,
+        feature_set: &Arc<FeatureSet>,
+        address_loader: impl AddressLoader + Copy,
+    ) -> Result<SanitizedBundle, BundleError> {
+        let transactions = self
+            .transactions
+            .into_iter()
+            .enumerate()
+            .map(|(index, tx)| {
+                let tx = SanitizedTransaction::try_create(
+                    tx,
+                    MessageHash::Compute,
+                    None,
+                    address_loader,
+                    true,
+                )
+                .map_err(|_| BundleError::FailedToSanitize { index })?;
+                tx.verify()
+                    .and_then(|_| tx.verify_precompiles(feature_set))
+                    .map_err(|_| BundleError::FailedToSanitize { index })?;
+                Ok(tx)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(SanitizedBundle {
+            transactions,
+            uuid: Uuid::new_v4(),
+            packet_flags: None,
+        })
+    }
+}
+
+/// Sanitized bundles packed under a compute unit ceiling, bounding the work pulled per iteration.
+#[derive(Clone, Debug, Default)]
+pub struct SanitizedBundleBatch {
+    bundles: Vec<SanitizedBundle>,
+    total_compute_units: u64,
+}
+
+impl SanitizedBundleBatch {
+    /// Packs the `bundles` in order until the next one would take the batch past `max_batch_cus`
+    /// of requested compute units, returning the bundles left for the next batch. A bundle
+    /// exceeding the ceiling on its own is batched alone so that it can't hold up the queue.
+    pub fn from_bundles(
+        bundles: Vec<SanitizedBundle>,
+        max_batch_cus: u64,
+    ) -> (Self, Vec<SanitizedBundle>) {
+        let mut batch = Self::default();
+        let mut bundles = bundles.into_iter();
+        for bundle in bundles.by_ref() {
+            let compute_units = bundle.requested_compute_units();
+            let total_compute_units = batch.total_compute_units.saturating_add(compute_units);
+            if total_compute_units > max_batch_cus && !batch.is_empty() {
+                let mut leftover = vec![bundle];
+                leftover.extend(bundles);
+                return (batch, leftover);
+            }
+            batch.bundles.push(bundle);
+            batch.total_compute_units = total_compute_units;
+        }
+        (batch, Vec::new())
+    }
+
+    /// Sanitizes all the `bundles` into one batch, resolving their address lookup tables with the
+    /// same `address_loader`, typically a [`LookupTableCache`] built for the whole batch. Fails
+    /// with the index of the first invalid bundle, wrapping the index of its invalid transaction.
+    pub fn try_from_bundles(
+        bundles: Vec<VersionedBundle>,
+        address_loader: impl AddressLoader + Copy,
+        feature_set: &Arc<FeatureSet>,
+    ) -> Result<Self, BundleError> {
+        let mut batch = Self::default();
+        for (bundle_index, bundle) in bundles.into_iter().enumerate() {
+            let bundle = bundle
+                .into_sanitized(feature_set, address_loader)
+                .map_err(|error| BundleError::InvalidBundleInBatch {
+                    bundle_index,
+                    error: Box::new(error),
+                })?;
+            batch.total_compute_units = batch
+                .total_compute_units
+                .saturating_add(bundle.requested_compute_units());
+            batch.bundles.push(bundle);
+        }
+        Ok(batch)
+    }
+
+    pub fn bundles(&self) -> &[SanitizedBundle] {
+        &self.bundles
+    }
+
+    pub fn into_bundles(self) -> Vec<SanitizedBundle> {
+        self.bundles
+    }
+
+    /// Returns the compute units requested by all the bundles of the batch.
+    pub fn total_compute_units(&self) -> u64 {
+        self.total_compute_units
+    }
+
+    pub fn len(&self) -> usize {
+        self.bundles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bundles.is_empty()
+    }
+}
+
+/// Returns the distinct address lookup tables referenced across the messages.
+pub fn lookup_table_keys<'a>(
+    messages: impl IntoIterator<Item = &'a VersionedMessage>,
+) -> HashSet<Pubkey> {
+    messages
+        .into_iter()
+        .filter_map(|message| message.address_table_lookups())
+        .flatten()
+        .map(|lookup| lookup.account_key)
+        .collect()
+}
+
+/// Returns the number of distinct address lookup tables referenced across the messages.
+pub fn lookup_table_count<'a>(messages: impl IntoIterator<Item = &'a VersionedMessage>) -> usize {
+    lookup_table_keys(messages).len()
+}
+
+/// Rejects bundles referencing more than `max_lookup_tables` distinct address lookup tables.
+/// Sanitizing a transaction loads every table it references, so this must be checked beforehand
+/// to bound the sanitization cost of a bundle. No limit is enforced if `max_lookup_tables` is None.
+pub fn check_lookup_table_limit<'a>(
+    messages: impl IntoIterator<Item = &'a VersionedMessage>,
+    max_lookup_tables: Option<usize>,
+) -> Result<(), BundleError> {
+    match max_lookup_tables {
+        Some(max) => {
+            let count = lookup_table_count(messages);
+            if count > max {
+                Err(BundleError::TooManyLookupTables { count, max })
+            } else {
+                Ok(())
+            }
+        }
+        None => Ok(()),
+    }
+}
+
+/// An address lookup table as stored by the address lookup table program, with what's needed to
+/// tell which of its addresses can be looked up in a given slot.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LookupTableState {
+    pub addresses: Vec<Pubkey>,
+    /// The slot the table was deactivated in, `Slot::MAX` if it hasn't been.
+    pub deactivation_slot: Slot,
+    /// The slot the table was last extended in.
+    pub last_extended_slot: Slot,
+    /// The number of addresses the table held before it was last extended.
+    pub last_extended_slot_start_index: u8,
+}
+
+impl LookupTableState {
+    /// Returns the addresses transactions executing in `current_slot` can look up, following the
+    /// same rules as the runtime, or None if the table can't be used anymore:
+    /// - A deactivated table can still be used until its deactivation slot falls out of the
+    ///   `slot_hashes`.
+    /// - Addresses appended to the table in `current_slot` can't be used until the next slot.
+    pub fn active_addresses(
+        &self,
+        current_slot: Slot,
+        slot_hashes: &SlotHashes,
+    ) -> Option<&[Pubkey]> {
+        let is_active = self.deactivation_slot == Slot::MAX
+            || self.deactivation_slot == current_slot
+            || slot_hashes.position(&self.deactivation_slot).is_some();
+        if !is_active {
+            return None;
+        }
+        let active_addresses_len = if current_slot > self.last_extended_slot {
+            self.addresses.len()
+        } else {
+            self.last_extended_slot_start_index as usize
+        };
+        self.addresses.get(..active_addresses_len)
+    }
+}
+
+/// Address lookup tables resolved once for a batch of bundles, so that tables shared between
+/// bundles aren't loaded again for each transaction referencing them.
+///
+/// Only the addresses that can be looked up in the slot the cache was built for are kept, see
+/// [`LookupTableState::active_addresses`], so the tables must all be resolved from the bank of
+/// that slot and the cache is only valid for that bank.
+#[derive(Clone, Debug, Default)]
+pub struct LookupTableCache {
+    tables: HashMap<Pubkey, Vec<Pubkey>>,
+}
+
+impl LookupTableCache {
+    /// Resolves the addresses of every table referenced by the `bundles` that transactions
+    /// executing in `current_slot` can look up, calling `resolve_table` once per table. Tables
+    /// that can't be resolved or are deactivated are left out, so transactions referencing them
+    /// fail to sanitize.
+    pub fn new<'a>(
+        bundles: impl IntoIterator<Item = &'a VersionedBundle>,
+        current_slot: Slot,
+        slot_hashes: &SlotHashes,
+        mut resolve_table: impl FnMut(&Pubkey) -> Option<LookupTableState>,
+    ) -> Self {
+        let table_keys = lookup_table_keys(
+            bundles
+                .into_iter()
+                .flat_map(|bundle| bundle.transactions.iter().map(|tx| &tx.message)),
+        );
+        let tables = table_keys
+            .into_iter()
+            .filter_map(|key| {
+                let table = resolve_table(&key)?;
+                let addresses = table.active_addresses(current_slot, slot_hashes)?;
+                Some((key, addresses.to_vec()))
+            })
+            .collect();
+        Self { tables }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tables.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+}
+
+impl AddressLoader for &LookupTableCache {
+    fn load_addresses(
+        self,
+        lookups: &[MessageAddressTableLookup],
+    ) -> Result<LoadedAddresses, AddressLoaderError> {
+        let mut loaded_addresses = LoadedAddresses::default();
+        for lookup in lookups {
+            let table = self
+                .tables
+                .get(&lookup.account_key)
+                .ok_or(AddressLoaderError::LookupTableAccountNotFound)?;
+            let lookup_addresses = |indexes: &[u8]| {
+                indexes
+                    .iter()
+                    .map(|index| {
+                        table
+                            .get(*index as usize)
+                            .copied()
+                            .ok_or(AddressLoaderError::InvalidLookupIndex)
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            };
+            loaded_addresses
+                .writable
+                .extend(lookup_addresses(&lookup.writable_indexes)?);
+            loaded_addresses
+                .readonly
+                .extend(lookup_addresses(&lookup.readonly_indexes)?);
+        }
+        Ok(loaded_addresses)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_sdk::{
+            address_lookup_table_account::AddressLookupTableAccount,
+            compute_budget::ComputeBudgetInstruction,
+            hash::Hash,
+            instruction::{AccountMeta, Instruction},
+            message::{v0, MessageHeader},
+            signature::{Keypair, Signer},
+            system_transaction,
+            transaction::{Transaction, VersionedTransaction},
+        },
+        std::cell::Cell,
+    };
+
+    fn message_with_lookup_tables(lookup_tables: &[Pubkey]) -> VersionedMessage {
+        VersionedMessage::V0(v0::Message {
+            header: MessageHeader {
+                num_required_signatures: 1,
+                num_readonly_signed_accounts: 0,
+                num_readonly_unsigned_accounts: 0,
+            },
+            account_keys: vec![Pubkey::new_unique()],
+            recent_blockhash: Hash::default(),
+            instructions: vec![],
+            address_table_lookups: lookup_tables
+                .iter()
+                .map(|account_key| v0::MessageAddressTableLookup {
+                    account_key: *account_key,
+                    writable_indexes: vec![0],
+                    readonly_indexes: vec![],
+                })
+                .collect(),
+        })
+    }
+
+    #[test]
+    fn test_check_lookup_table_limit() {
+        let tables: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        // tables shared between transactions are only counted once
+        let messages = vec![
+            message_with_lookup_tables(&tables[..2]),
+            message_with_lookup_tables(&tables[1..]),
+            VersionedMessage::default(),
+        ];
+        assert_eq!(lookup_table_count(&messages), 3);
+
+        assert_eq!(check_lookup_table_limit(&messages, None), Ok(()));
+        assert_eq!(check_lookup_table_limit(&messages, Some(3)), Ok(()));
+        assert_eq!(
+            check_lookup_table_limit(&messages, Some(2)),
+            Err(BundleError::TooManyLookupTables { count: 3, max: 2 })
+        );
+    }
+
+    fn transaction_using_lookup_table(
+        payer: &Keypair,
+        table: &AddressLookupTableAccount,
+    ) -> VersionedTransaction {
+        let instruction = Instruction::new_with_bytes(
+            Pubkey::new_unique(),
+            &[],
+            vec![AccountMeta::new_readonly(table.addresses[0], false)],
+        );
+        let message = v0::Message::try_compile(
+            &payer.pubkey(),
+            &[instruction],
+            std::slice::from_ref(table),
+            Hash::default(),
+        )
+        .unwrap();
+        VersionedTransaction::try_new(VersionedMessage::V0(message), &[payer]).unwrap()
+    }
+
+    const CURRENT_SLOT: Slot = 100;
+
+    /// Resolves `table` as an active table which wasn't extended in `CURRENT_SLOT`.
+    fn resolve_active_table(
+        table: &AddressLookupTableAccount,
+    ) -> impl FnMut(&Pubkey) -> Option<LookupTableState> + '_ {
+        move |key| {
+            (*key == table.key).then(|| LookupTableState {
+                addresses: table.addresses.clone(),
+                deactivation_slot: Slot::MAX,
+                last_extended_slot: CURRENT_SLOT - 1,
+                last_extended_slot_start_index: 0,
+            })
+        }
+    }
+
+    #[test]
+    fn test_lookup_table_cache_resolves_shared_lookup_table_once() {
+        let payer = Keypair::new();
+        let table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: vec![Pubkey::new_unique()],
+        };
+        let bundles: Vec<VersionedBundle> = (0..3)
+            .map(|_| VersionedBundle {
+                transactions: vec![
+                    transaction_using_lookup_table(&payer, &table),
+                    transaction_using_lookup_table(&payer, &table),
+                ],
+            })
+            .collect();
+
+        let num_resolved = Cell::new(0);
+        let mut resolve_table = resolve_active_table(&table);
+        let cache = LookupTableCache::new(&bundles, CURRENT_SLOT, &SlotHashes::default(), |key| {
+            num_resolved.set(num_resolved.get() + 1);
+            resolve_table(key)
+        });
+        assert_eq!(num_resolved.get(), 1);
+        assert_eq!(cache.len(), 1);
+
+        let feature_set = Arc::new(FeatureSet::all_enabled());
+        let batch =
+            SanitizedBundleBatch::try_from_bundles(bundles.clone(), &cache, &feature_set).unwrap();
+        assert_eq!(batch.len(), 3);
+        for bundle in batch.bundles() {
+            for tx in &bundle.transactions {
+                assert!(tx
+                    .message()
+                    .account_keys()
+                    .iter()
+                    .any(|key| *key == table.addresses[0]));
+            }
+        }
+
+        // bundles referencing a table that couldn't be resolved fail to sanitize
+        assert_eq!(
+            SanitizedBundleBatch::try_from_bundles(
+                bundles[..1].to_vec(),
+                &LookupTableCache::default(),
+                &feature_set
+            )
+            .unwrap_err(),
+            BundleError::InvalidBundleInBatch {
+                bundle_index: 0,
+                error: Box::new(BundleError::FailedToSanitize { index: 0 }),
+            }
+        );
+    }
+
+    #[test]
+    fn test_lookup_table_active_addresses() {
+        let addresses = vec![Pubkey::new_unique(), Pubkey::new_unique()];
+        let table = LookupTableState {
+            addresses: addresses.clone(),
+            deactivation_slot: Slot::MAX,
+            last_extended_slot: CURRENT_SLOT - 1,
+            last_extended_slot_start_index: 1,
+        };
+        let slot_hashes = SlotHashes::new(&[(CURRENT_SLOT - 1, Hash::default())]);
+        assert_eq!(
+            table.active_addresses(CURRENT_SLOT, &slot_hashes),
+            Some(&addresses[..])
+        );
+
+        // the addresses appended in the current slot can't be looked up yet
+        let extended_table = LookupTableState {
+            last_extended_slot: CURRENT_SLOT,
+            ..table.clone()
+        };
+        assert_eq!(
+            extended_table.active_addresses(CURRENT_SLOT, &slot_hashes),
+            Some(&addresses[..1])
+        );
+
+        // a deactivated table can be used until its deactivation slot leaves the slot hashes
+        for deactivation_slot in [CURRENT_SLOT, CURRENT_SLOT - 1] {
+            let deactivating_table = LookupTableState {
+                deactivation_slot,
+                ..table.clone()
+            };
+            assert_eq!(
+                deactivating_table.active_addresses(CURRENT_SLOT, &slot_hashes),
+                Some(&addresses[..])
+            );
+        }
+        let deactivated_table = LookupTableState {
+            deactivation_slot: CURRENT_SLOT - 2,
+            ..table
+        };
+        assert_eq!(
+            deactivated_table.active_addresses(CURRENT_SLOT, &slot_hashes),
+            None
+        );
+
+        // and is left out of the cache
+        let payer = Keypair::new();
+        let table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses,
+        };
+        let bundle = VersionedBundle {
+            transactions: vec![transaction_using_lookup_table(&payer, &table)],
+        };
+        let cache = LookupTableCache::new([&bundle], CURRENT_SLOT, &slot_hashes, |_| {
+            Some(deactivated_table.clone())
+        });
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_into_sanitized() {
+        let payer = Keypair::new();
+        let table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: vec![Pubkey::new_unique()],
+        };
+        let legacy_tx = VersionedTransaction::from(system_transaction::transfer(
+            &payer,
+            &Pubkey::new_unique(),
+            1,
+            Hash::default(),
+        ));
+        let v0_tx = transaction_using_lookup_table(&payer, &table);
+        let bundle = VersionedBundle {
+            transactions: vec![legacy_tx.clone(), v0_tx],
+        };
+        let cache = LookupTableCache::new(
+            [&bundle],
+            CURRENT_SLOT,
+            &SlotHashes::default(),
+            resolve_active_table(&table),
+        );
+        let feature_set = Arc::new(FeatureSet::all_enabled());
+
+        let sanitized_bundle = bundle.clone().into_sanitized(&feature_set, &cache).unwrap();
+        assert_eq!(sanitized_bundle.transactions.len(), 2);
+        assert_eq!(
+            sanitized_bundle.transactions[0].signature(),
+            &legacy_tx.signatures[0]
+        );
+        assert!(sanitized_bundle.transactions[1]
+            .message()
+            .account_keys()
+            .iter()
+            .any(|key| *key == table.addresses[0]));
+
+        // the lookup table can't be resolved
+        assert_eq!(
+            bundle
+                .clone()
+                .into_sanitized(&feature_set, &LookupTableCache::default())
+                .unwrap_err(),
+            BundleError::FailedToSanitize { index: 1 }
+        );
+
+        // the signature doesn't match
+        let mut bundle = bundle;
+        bundle.transactions[0].signatures[0] = Default::default();
+        assert_eq!(
+            bundle.into_sanitized(&feature_set, &cache).unwrap_err(),
+            BundleError::FailedToSanitize { index: 0 }
+        );
+    }
+
+    fn sanitized_bundle(accounts: &[Vec<AccountMeta>]) -> SanitizedBundle {
+        let payer = Keypair::new();
+        SanitizedBundle {
+            transactions: accounts
+                .iter()
+                .map(|accounts| {
+                    SanitizedTransaction::from_transaction_for_tests(
+                        Transaction::new_signed_with_payer(
+                            &[Instruction::new_with_bytes(
+                                Pubkey::new_unique(),
+                                &[],
+                                accounts.clone(),
+                            )],
+                            Some(&payer.pubkey()),
+                            &[&payer],
+                            Hash::default(),
+                        ),
+                    )
+                })
+                .collect(),
+            uuid: Uuid::new_v4(),
+            packet_flags: None,
+        }
+    }
+
+    #[test]
+    fn test_account_locks_disjoint() {
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let bundle = sanitized_bundle(&[
+            vec![AccountMeta::new(a, false)],
+            vec![AccountMeta::new_readonly(b, false)],
+        ]);
+
+        let locks = bundle.account_locks();
+        assert!(locks.writable.contains(&a));
+        assert!(locks.readonly.contains(&b));
+        assert!(!bundle.has_conflicting_locks());
+        assert!(bundle.read_after_write_accounts().is_empty());
+    }
+
+    #[test]
+    fn test_account_locks_overlapping() {
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let bundle = sanitized_bundle(&[
+            vec![AccountMeta::new_readonly(b, false)],
+            vec![AccountMeta::new(a, false), AccountMeta::new(b, false)],
+            vec![AccountMeta::new_readonly(a, false)],
+        ]);
+
+        // accounts written by any transaction are locked as writable for the whole bundle
+        let locks = bundle.account_locks();
+        assert!(locks.writable.contains(&a));
+        assert!(locks.writable.contains(&b));
+        assert!(!locks.readonly.contains(&a));
+        assert!(!locks.readonly.contains(&b));
+        assert!(bundle.has_conflicting_locks());
+        // b is only read before it's written
+        assert_eq!(bundle.read_after_write_accounts(), HashSet::from([a]));
+    }
+
+    /// A bundle of `num_transactions` transactions requesting `compute_units` each.
+    fn bundle_requesting(num_transactions: usize, compute_units: u32) -> SanitizedBundle {
+        let payer = Keypair::new();
+        SanitizedBundle {
+            transactions: (0..num_transactions)
+                .map(|_| {
+                    SanitizedTransaction::from_transaction_for_tests(
+                        Transaction::new_signed_with_payer(
+                            &[
+                                ComputeBudgetInstruction::set_compute_unit_limit(compute_units),
+                                Instruction::new_with_bytes(Pubkey::new_unique(), &[], vec![]),
+                            ],
+                            Some(&payer.pubkey()),
+                            &[&payer],
+                            Hash::default(),
+                        ),
+                    )
+                })
+                .collect(),
+            uuid: Uuid::new_v4(),
+            packet_flags: None,
+        }
+    }
+
+    #[test]
+    fn test_sanitized_bundle_batch_stops_at_ceiling() {
+        let bundles = vec![
+            bundle_requesting(2, 100_000),
+            bundle_requesting(1, 300_000),
+            bundle_requesting(1, 100_000),
+            bundle_requesting(1, 100_000),
+        ];
+        assert_eq!(bundles[0].requested_compute_units(), 200_000);
+
+        let (batch, leftover) = SanitizedBundleBatch::from_bundles(bundles, 500_000);
+        assert_eq!(batch.len(), 2);
+        assert_eq!(batch.total_compute_units(), 500_000);
+        // packing stops at the first bundle that doesn't fit, keeping the bundles in order
+        assert_eq!(leftover.len(), 2);
+
+        let (batch, leftover) = SanitizedBundleBatch::from_bundles(leftover, 500_000);
+        assert_eq!(batch.len(), 2);
+        assert_eq!(batch.total_compute_units(), 200_000);
+        assert!(leftover.is_empty());
+
+        let (batch, leftover) = SanitizedBundleBatch::from_bundles(vec![], 500_000);
+        assert!(batch.is_empty());
+        assert!(leftover.is_empty());
+    }
+
+    #[test]
+    fn test_sanitized_bundle_batch_oversized_bundle_alone() {
+        let bundles = vec![bundle_requesting(1, 600_000), bundle_requesting(1, 100_000)];
+        let (batch, leftover) = SanitizedBundleBatch::from_bundles(bundles, 500_000);
+        assert_eq!(batch.len(), 1);
+        assert_eq!(batch.total_compute_units(), 600_000);
+        assert_eq!(leftover.len(), 1);
+    }
+
+    #[test]
+    fn test_sanitized_bundle_batch_try_from_bundles() {
+        let payer = Keypair::new();
+        let table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: vec![Pubkey::new_unique()],
+        };
+        let bundles: Vec<VersionedBundle> = (0..3)
+            .map(|_| VersionedBundle {
+                transactions: vec![
+                    VersionedTransaction::from(system_transaction::transfer(
+                        &payer,
+                        &Pubkey::new_unique(),
+                        1,
+                        Hash::default(),
+                    )),
+                    transaction_using_lookup_table(&payer, &table),
+                ],
+            })
+            .collect();
+        let cache = LookupTableCache::new(
+            &bundles,
+            CURRENT_SLOT,
+            &SlotHashes::default(),
+            resolve_active_table(&table),
+        );
+        let feature_set = Arc::new(FeatureSet::all_enabled());
+
+        let batch =
+            SanitizedBundleBatch::try_from_bundles(bundles.clone(), &cache, &feature_set).unwrap();
+        assert_eq!(batch.len(), 3);
+        assert!(batch
+            .bundles()
+            .iter()
+            .all(|bundle| bundle.transactions.len() == 2));
+        assert_eq!(
+            batch.total_compute_units(),
+            batch
+                .bundles()
+                .iter()
+                .map(SanitizedBundle::requested_compute_units)
+                .sum::<u64>()
+        );
+
+        // the second transaction of the third bundle has a bad signature
+        let mut bundles = bundles;
+        bundles[2].transactions[1].signatures[0] = Default::default();
+        assert_eq!(
+            SanitizedBundleBatch::try_from_bundles(bundles, &cache, &feature_set).unwrap_err(),
+            BundleError::InvalidBundleInBatch {
+                bundle_index: 2,
+                error: Box::new(BundleError::FailedToSanitize { index: 1 }),
+            }
+        );
+    }
 }
@@ -1,4 +1,14 @@
-use {crate::bundle::error::BundleExecutionError, solana_sdk::transaction::TransactionError};
+use {
+    crate::bundle::{
+        error::{BundleError, BundleExecutionError},
+        sanitized::SanitizedBundle,
+    },
+    solana_sdk::{
+        pubkey::Pubkey,
+        transaction::{SanitizedTransaction, TransactionError},
+    },
+    std::{collections::HashSet, mem},
+};
 
 type LockResult = Result<(), TransactionError>;
 
@@ -17,4 +27,225 @@ pub fn check_bundle_lock_results(lock_results: &[LockResult]) -> Option<(Transac
     None
 }
 
+/// The accounts locked by the transactions of an entry so far.
+#[derive(Default)]
+struct EntryLocks {
+    writable: HashSet<Pubkey>,
+    readonly: HashSet<Pubkey>,
+}
+
+impl EntryLocks {
+    /// Returns true if the transaction's locks conflict with the entry's, in which case replay
+    /// couldn't lock the entry's transactions as one batch.
+    fn conflicts(&self, tx: &SanitizedTransaction) -> bool {
+        let tx_locks = tx.get_account_locks_unchecked();
+        tx_locks
+            .writable
+            .iter()
+            .any(|key| self.writable.contains(*key) || self.readonly.contains(*key))
+            || tx_locks
+                .readonly
+                .iter()
+                .any(|key| self.writable.contains(*key))
+    }
+
+    /// Takes the transaction's locks, failing if they conflict with the entry's.
+    fn try_lock(&mut self, tx: &SanitizedTransaction) -> bool {
+        if self.conflicts(tx) {
+            return false;
+        }
+        let tx_locks = tx.get_account_locks_unchecked();
+        self.writable.extend(tx_locks.writable.into_iter().copied());
+        self.readonly.extend(tx_locks.readonly.into_iter().copied());
+        true
+    }
+}
+
+/// Packs bundles, in order, into groups of transactions where each group is recorded as a single entry.
+/// Since replay locks an entry's transactions as one batch, a new entry is started whenever the next
+/// bundle doesn't fit into the current one or locks accounts that conflict with it. A bundle is only
+/// split when its own transactions conflict, the same way bundle stage executes it in chunks.
+/// Returns an error naming the first bundle that can't fit into an entry on its own.
+pub fn pack_bundles_into_entries(
+    bundles: Vec<SanitizedBundle>,
+    max_transactions_per_entry: usize,
+) -> Result<Vec<Vec<SanitizedTransaction>>, BundleError> {
+    let mut entries = Vec::new();
+    let mut current_entry: Vec<SanitizedTransaction> = Vec::new();
+    let mut entry_locks = EntryLocks::default();
+
+    for (index, bundle) in bundles.into_iter().enumerate() {
+        let len = bundle.transactions.len();
+        if len > max_transactions_per_entry {
+            return Err(BundleError::ExceedsEntryTransactionLimit {
+                index,
+                len,
+                max: max_transactions_per_entry,
+            });
+        }
+        if current_entry.len() + len > max_transactions_per_entry
+            || bundle
+                .transactions
+                .iter()
+                .any(|tx| entry_locks.conflicts(tx))
+        {
+            entries.push(mem::take(&mut current_entry));
+            entry_locks = EntryLocks::default();
+        }
+        for tx in bundle.transactions {
+            if !entry_locks.try_lock(&tx) {
+                entries.push(mem::take(&mut current_entry));
+                entry_locks = EntryLocks::default();
+                entry_locks.try_lock(&tx);
+            }
+            current_entry.push(tx);
+        }
+    }
+    if !current_entry.is_empty() {
+        entries.push(current_entry);
+    }
+
+    Ok(entries)
+}
+
 pub type BundleExecutionResult<T> = Result<T, BundleExecutionError>;
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            hash::Hash,
+            pubkey::Pubkey,
+            signature::{Keypair, Signature},
+            system_transaction,
+        },
+        uuid::Uuid,
+    };
+
+    fn transfer(payer: &Keypair) -> SanitizedTransaction {
+        SanitizedTransaction::from_transaction_for_tests(system_transaction::transfer(
+            payer,
+            &Pubkey::new_unique(),
+            1,
+            Hash::default(),
+        ))
+    }
+
+    fn bundle(transactions: Vec<SanitizedTransaction>) -> SanitizedBundle {
+        SanitizedBundle {
+            transactions,
+            uuid: Uuid::new_v4(),
+            packet_flags: None,
+        }
+    }
+
+    /// A bundle of transactions that don't lock any account in common.
+    fn test_bundle(num_transactions: usize) -> SanitizedBundle {
+        bundle(
+            (0..num_transactions)
+                .map(|_| transfer(&Keypair::new()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_pack_bundles_into_entries() {
+        let bundles: Vec<SanitizedBundle> = [2, 3, 1, 4, 5].into_iter().map(test_bundle).collect();
+        let expected_signatures: Vec<Signature> = bundles
+            .iter()
+            .flat_map(|bundle| bundle.transactions.iter().map(|tx| *tx.signature()))
+            .collect();
+
+        let entries = pack_bundles_into_entries(bundles, 5).unwrap();
+        assert_eq!(
+            entries.iter().map(|entry| entry.len()).collect::<Vec<_>>(),
+            vec![5, 5, 5]
+        );
+
+        let packed_signatures: Vec<Signature> = entries
+            .iter()
+            .flat_map(|entry| entry.iter().map(|tx| *tx.signature()))
+            .collect();
+        assert_eq!(packed_signatures, expected_signatures);
+    }
+
+    #[test]
+    fn test_pack_bundles_into_entries_keeps_bundles_whole() {
+        let bundles: Vec<SanitizedBundle> = [3, 3, 2].into_iter().map(test_bundle).collect();
+        let entries = pack_bundles_into_entries(bundles, 4).unwrap();
+        assert_eq!(
+            entries.iter().map(|entry| entry.len()).collect::<Vec<_>>(),
+            vec![3, 3, 2]
+        );
+    }
+
+    #[test]
+    fn test_pack_bundles_into_entries_oversized_bundle() {
+        let bundles: Vec<SanitizedBundle> = [1, 6, 2].into_iter().map(test_bundle).collect();
+        assert_eq!(
+            pack_bundles_into_entries(bundles, 5),
+            Err(BundleError::ExceedsEntryTransactionLimit {
+                index: 1,
+                len: 6,
+                max: 5
+            })
+        );
+    }
+
+    #[test]
+    fn test_pack_bundles_into_entries_splits_conflicting_locks() {
+        // both transactions write the payer
+        let payer = Keypair::new();
+        let entries =
+            pack_bundles_into_entries(vec![bundle(vec![transfer(&payer), transfer(&payer)])], 5)
+                .unwrap();
+        assert_eq!(
+            entries.iter().map(|entry| entry.len()).collect::<Vec<_>>(),
+            vec![1, 1]
+        );
+
+        // a bundle conflicting with an earlier one starts a new entry, while bundles that don't
+        // conflict keep sharing an entry
+        let bundles = vec![
+            bundle(vec![transfer(&payer)]),
+            test_bundle(1),
+            bundle(vec![transfer(&payer), transfer(&Keypair::new())]),
+        ];
+        let entries = pack_bundles_into_entries(bundles, 5).unwrap();
+        assert_eq!(
+            entries.iter().map(|entry| entry.len()).collect::<Vec<_>>(),
+            vec![2, 2]
+        );
+    }
+
+    #[test]
+    fn test_pack_bundles_into_entries_keeps_conflicting_bundles_whole() {
+        // the second bundle only conflicts with the first through its last transaction, which
+        // mustn't leave its first transaction behind in the first bundle's entry
+        let payer = Keypair::new();
+        let first = bundle(vec![transfer(&payer)]);
+        let second = bundle(vec![transfer(&Keypair::new()), transfer(&payer)]);
+        let expected_entries = vec![
+            vec![*first.transactions[0].signature()],
+            vec![
+                *second.transactions[0].signature(),
+                *second.transactions[1].signature(),
+            ],
+        ];
+
+        let entries = pack_bundles_into_entries(vec![first, second], 5).unwrap();
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| entry.iter().map(|tx| *tx.signature()).collect::<Vec<_>>())
+                .collect::<Vec<_>>(),
+            expected_entries
+        );
+    }
+
+    #[test]
+    fn test_pack_bundles_into_entries_empty() {
+        assert!(pack_bundles_into_entries(vec![], 5).unwrap().is_empty());
+    }
+}
@@ -21,7 +21,16 @@ use {
     },
     solana_core::{
         ledger_cleanup_service::{DEFAULT_MAX_LEDGER_SHREDS, DEFAULT_MIN_MAX_LEDGER_SHREDS},
-        proxy::{block_engine_stage::BlockEngineConfig, relayer_stage::RelayerConfig},
+        proxy::{
+            block_engine_stage::{BlockEngineConfig, DropEvent},
+            bundle_acceptance_policy::{
+                BundleAcceptancePolicy, CompositePolicy, MaxTransactionsPolicy,
+                ProgramAllowlistPolicy, RejectVotesPolicy,
+            },
+            relayer_stage::{
+                PacketDistribution, RelayerConfig, RelayerSocketConfig, TrustedPacketBackpressure,
+            },
+        },
         system_monitor_service::SystemMonitorService,
         tip_manager::{TipDistributionAccountConfig, TipManagerConfig},
         tower_storage,
@@ -36,7 +45,9 @@ use {
     solana_perf::recycler::enable_recycler_warming,
     solana_poh::poh_service,
     solana_rpc::{
-        rpc::{JsonRpcConfig, RpcBigtableConfig, MAX_REQUEST_BODY_SIZE},
+        rpc::{
+            JsonRpcConfig, RpcBigtableConfig, MAX_BUNDLE_SIMULATION_DURATION, MAX_REQUEST_BODY_SIZE,
+        },
         rpc_pubsub_service::PubSubConfig,
     },
     solana_rpc_client::rpc_client::RpcClient,
@@ -509,6 +520,8 @@ pub fn main() {
     let default_accounts_shrink_ratio = &DEFAULT_ACCOUNTS_SHRINK_RATIO.to_string();
     let default_tpu_connection_pool_size = &DEFAULT_TPU_CONNECTION_POOL_SIZE.to_string();
     let default_rpc_max_request_body_size = &MAX_REQUEST_BODY_SIZE.to_string();
+    let default_rpc_max_bundle_simulation_duration_ms =
+        &MAX_BUNDLE_SIMULATION_DURATION.as_millis().to_string();
     let default_preallocated_bundle_cost = &DEFAULT_PREALLOCATED_BUNDLE_COST.to_string();
 
     let matches = App::new(crate_name!()).about(crate_description!())
@@ -1489,6 +1502,15 @@ pub fn main() {
                 .default_value(default_rpc_max_request_body_size)
                 .help("The maximum request body size accepted by rpc service"),
         )
+        .arg(
+            Arg::with_name("rpc_max_bundle_simulation_duration_ms")
+                .long("rpc-max-bundle-simulation-duration-ms")
+                .value_name("MILLISECONDS")
+                .takes_value(true)
+                .validator(is_parsable::<u64>)
+                .default_value(default_rpc_max_bundle_simulation_duration_ms)
+                .help("The maximum time simulating a bundle may take before it's aborted"),
+        )
         .arg(
             Arg::with_name("enable_accountsdb_repl")
                 .long("enable-accountsdb-repl")
@@ -1819,6 +1841,35 @@ pub fn main() {
                 .takes_value(false)
                 .help("Skip signature verification on block engine packets. Not recommended unless the block engine is trusted.")
         )
+        .arg(
+            Arg::with_name("block_engine_max_bundle_transactions")
+                .long("block-engine-max-bundle-transactions")
+                .value_name("NUM")
+                .takes_value(true)
+                .validator(is_parsable::<usize>)
+                .help("Drop block engine bundles with more transactions than this.")
+        )
+        .arg(
+            Arg::with_name("block_engine_reject_vote_bundles")
+                .long("block-engine-reject-vote-bundles")
+                .takes_value(false)
+                .help("Drop block engine bundles containing vote transactions.")
+        )
+        .arg(
+            Arg::with_name("block_engine_allowed_program")
+                .long("block-engine-allowed-program")
+                .value_name("PROGRAM_ID")
+                .takes_value(true)
+                .multiple(true)
+                .validator(is_pubkey)
+                .help("Only accept block engine bundles whose transactions invoke these programs. May be specified multiple times. If unset, any program is allowed.")
+        )
+        .arg(
+            Arg::with_name("log_drop_events")
+                .long("log-drop-events")
+                .takes_value(false)
+                .help("Log every bundle and batch of packets dropped at ingest along with why.")
+        )
         .arg(
             Arg::with_name("tip_payment_program_pubkey")
                 .long("tip-payment-program-pubkey")
@@ -1855,6 +1906,20 @@ pub fn main() {
                 .default_value(default_preallocated_bundle_cost)
                 .help("Number of CUs to allocate for bundles at beginning of slot.")
         )
+        .arg(
+            Arg::with_name("report_bundle_tip_outcomes")
+                .long("report-bundle-tip-outcomes")
+                .takes_value(false)
+                .help("Report the tip paid by each executed bundle and whether it landed.")
+        )
+        .arg(
+            Arg::with_name("max_bundle_lookup_tables")
+                .long("max-bundle-lookup-tables")
+                .value_name("COUNT")
+                .takes_value(true)
+                .validator(is_parsable::<usize>)
+                .help("Drop bundles referencing more distinct address lookup tables than this, unlimited if unset.")
+        )
         .arg(
             Arg::with_name("shred_receiver_address")
                 .long("shred-receiver-address")
@@ -2666,6 +2731,10 @@ pub fn main() {
     let voting_disabled = matches.is_present("no_voting") || restricted_repair_only_mode;
     let tip_manager_config = tip_manager_config_from_matches(&matches, voting_disabled);
 
+    let drop_event_tx = matches
+        .is_present("log_drop_events")
+        .then(DropEvent::spawn_logger);
+
     let is_block_engine_enabled = matches.is_present("block_engine_url")
         || matches.is_present("block_engine_address")
         || matches.is_present("block_engine_auth_service_address")
@@ -2695,10 +2764,30 @@ pub fn main() {
                 .expect("failed to set tls_config");
         }
 
+        let mut acceptance_policies: Vec<Box<dyn BundleAcceptancePolicy>> = Vec::new();
+        if let Some(max_transactions) = value_of(&matches, "block_engine_max_bundle_transactions") {
+            acceptance_policies.push(Box::new(MaxTransactionsPolicy(max_transactions)));
+        }
+        if matches.is_present("block_engine_reject_vote_bundles") {
+            acceptance_policies.push(Box::new(RejectVotesPolicy));
+        }
+        if let Ok(allowed_programs) = values_t!(matches, "block_engine_allowed_program", Pubkey) {
+            acceptance_policies.push(Box::new(ProgramAllowlistPolicy(
+                allowed_programs.into_iter().collect(),
+            )));
+        }
+        let acceptance_policy = (!acceptance_policies.is_empty()).then(|| {
+            Arc::new(CompositePolicy(acceptance_policies)) as Arc<dyn BundleAcceptancePolicy>
+        });
+
         BlockEngineConfig {
             auth_service_endpoint,
             backend_endpoint,
             trust_packets: matches.is_present("trust_block_engine_packets"),
+            max_stream_lifetime: None,
+            acceptance_policy,
+            drop_event_tx: drop_event_tx.clone(),
+            loose_packet_shedding: None,
         }
     });
 
@@ -2745,10 +2834,27 @@ pub fn main() {
 
         RelayerConfig {
             auth_service_endpoint,
-            backend_endpoint,
+            refresh_service_endpoint: None,
+            token_cache_path: None,
+            clock_skew_tolerance: RelayerConfig::DEFAULT_CLOCK_SKEW_TOLERANCE,
+            auth_refresh_lookahead: RelayerConfig::DEFAULT_AUTH_REFRESH_LOOKAHEAD,
+            max_auth_retries: RelayerConfig::DEFAULT_MAX_AUTH_RETRIES,
+            backend_endpoints: vec![backend_endpoint],
             expected_heartbeat_interval,
             oldest_allowed_heartbeat,
             trust_packets: matches.is_present("trust_relayer_packets"),
+            trust_audit_sample_rate: None,
+            trusted_backpressure: TrustedPacketBackpressure::default(),
+            drop_event_tx,
+            socket_config: RelayerSocketConfig::default(),
+            connection_timeout: RelayerConfig::DEFAULT_CONNECTION_TIMEOUT,
+            warm_standby: false,
+            max_reconnect_backoff: RelayerConfig::DEFAULT_MAX_RECONNECT_BACKOFF,
+            reconnect_limiter: None,
+            max_stream_lifetime: None,
+            empty_message_threshold: None,
+            recent_batches_capacity: None,
+            packet_distribution: PacketDistribution::default(),
         }
     });
 
@@ -2793,6 +2899,11 @@ pub fn main() {
                 "rpc_max_request_body_size",
                 usize
             )),
+            max_bundle_simulation_duration: Some(Duration::from_millis(value_t_or_exit!(
+                matches,
+                "rpc_max_bundle_simulation_duration_ms",
+                u64
+            ))),
         },
         geyser_plugin_config_files,
         rpc_addrs: value_t!(matches, "rpc_port", u16).ok().map(|rpc_port| {
@@ -2895,6 +3006,8 @@ pub fn main() {
         replay_slots_concurrently: matches.is_present("replay_slots_concurrently"),
         preallocated_bundle_cost: value_of(&matches, "preallocated_bundle_cost")
             .unwrap_or(DEFAULT_PREALLOCATED_BUNDLE_COST),
+        report_bundle_tip_outcomes: matches.is_present("report_bundle_tip_outcomes"),
+        max_bundle_lookup_tables: value_of(&matches, "max_bundle_lookup_tables"),
         ..ValidatorConfig::default()
     };
 